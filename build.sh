@@ -6,15 +6,27 @@ WEB_CONTENT_DIR="./web/content"
 CONTENT_DIR="./content"
 WEB_DIR="./web"
 
-# Sanitize /web/content if it exists
-if [ -d "$WEB_CONTENT_DIR" ]; then
-    rm -rf "${WEB_CONTENT_DIR:?}/"*
-else
-    mkdir -p "$WEB_CONTENT_DIR"
+# Build helper project, it publishes the content
+echo "Building helper project"
+cargo build --release
+
+# Check if olifm-helper exists and remove it
+if [ -e ./olifm-helper ]; then
+    rm ./olifm-helper
 fi
 
-# Copy /content folder to /web
-cp -r "$CONTENT_DIR/." "$WEB_CONTENT_DIR/"
+# Move the binary
+mv ./target/release/olifm-master ./olifm-helper
+
+# Sync /content into /web/content and index it. Only changed files are
+# written, deleted ones are pruned, and GPS and private EXIF are stripped with
+# the report of what was removed kept out of /web. Pass --dry-run to preview.
+echo "Publishing content"
+./olifm-helper publish --content "$CONTENT_DIR" --out "$WEB_DIR" "$@"
+if [ "$1" = "--dry-run" ]; then
+    exit 0
+fi
+./olifm-helper check --content "$CONTENT_DIR" --published "$WEB_CONTENT_DIR"
 
 # Build wasm-pack in /web directory
 echo "Building WASM"
@@ -24,18 +36,9 @@ echo "Building WASM"
 )
 echo ""
 
-# Build helper project
-echo "Building helper project"
-cargo build --release
-
-# Check if olifm-helper exists and remove it
-if [ -e ./olifm-helper ]; then
-    rm ./olifm-helper
-fi
-
-# Move the binary
-mv ./target/release/olifm-master ./olifm-helper
+# Poster and mp4 rendition for the logo, the site falls back to the gif
+# when ffmpeg isn't available to write the mp4
+echo "Transcoding logo"
+./olifm-helper transcode --input ./web/assets/radio.mkv0001-0250.gif --format mp4 \
+    || echo "Logo rendition skipped, serving the gif"
 
-# Build site map
-echo "Building site map"
-./olifm-helper --content ./web/content --out ./web
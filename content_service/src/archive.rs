@@ -0,0 +1,118 @@
+//! Dated documents of each section grouped by year and month. The helper
+//! writes this into the index, indexes from before it existed get it built
+//! on load.
+
+use crate::dates::parse_content_date;
+use crate::models::{entry_types, ArchiveMonth, ArchiveSection, ArchiveYear, JsonEntry};
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+
+/// A document's day and path
+type Dated<'a> = (NaiveDate, &'a str);
+
+/// Archive of every section with dated documents. Readmes describe their
+/// section rather than being posts in it, so they are left out.
+pub fn build_archive(entries: &[JsonEntry]) -> Vec<ArchiveSection> {
+    let mut sections: BTreeMap<&str, BTreeMap<(i32, u32), Vec<Dated>>> = BTreeMap::new();
+
+    for entry in entries {
+        if entry.entry_type != entry_types::DOCUMENT
+            || entry.path.to_lowercase().ends_with("/readme.md")
+        {
+            continue;
+        }
+        let Some(day) = entry.date.as_deref().and_then(parse_content_date) else {
+            continue;
+        };
+        let Some((section, _)) = entry.path.trim_start_matches('/').split_once('/') else {
+            continue;
+        };
+        sections
+            .entry(section)
+            .or_default()
+            .entry((day.year(), day.month()))
+            .or_default()
+            .push((day, entry.path.as_str()));
+    }
+
+    sections
+        .into_iter()
+        .map(|(section, months)| {
+            let mut years: Vec<ArchiveYear> = Vec::new();
+            for ((year, month), mut dated) in months.into_iter().rev() {
+                dated.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+                let month = ArchiveMonth {
+                    month,
+                    count: dated.len() as u32,
+                    entries: dated
+                        .into_iter()
+                        .map(|(_, path)| path.to_string())
+                        .collect(),
+                };
+                match years.last_mut() {
+                    Some(last) if last.year == year => {
+                        last.count += month.count;
+                        last.months.push(month);
+                    }
+                    _ => years.push(ArchiveYear {
+                        year,
+                        count: month.count,
+                        months: vec![month],
+                    }),
+                }
+            }
+            ArchiveSection {
+                section: format!("/{}", section),
+                count: years.iter().map(|year| year.count).sum(),
+                years,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, entry_type: &str, date: Option<&str>) -> JsonEntry {
+        JsonEntry {
+            path: path.to_string(),
+            entry_type: entry_type.to_string(),
+            name: path.rsplit('/').next().unwrap().to_string(),
+            date: date.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn groups_sections_by_year_and_month() {
+        let doc = entry_types::DOCUMENT;
+        let entries = vec![
+            entry("/blog", entry_types::DIRECTORY, Some("2024-05-20")),
+            entry("/blog/readme.md", doc, Some("2024-05-20")),
+            entry("/blog/a.md", doc, Some("2023-12-01")),
+            entry("/blog/b.md", doc, Some("2024-05-02")),
+            entry("/blog/c.md", doc, Some("20/05/2024")),
+            entry("/blog/d.md", doc, Some("2024-01-15")),
+            entry("/blog/undated.md", doc, None),
+            entry("/blog/cv.pdf", entry_types::PDF, Some("2024-05-01")),
+            entry("/pictures/gannet.md", doc, Some("2019-12-12")),
+            entry("/home.md", doc, Some("2024-01-01")),
+        ];
+
+        let archive = build_archive(&entries);
+        let sections: Vec<(&str, u32)> = archive
+            .iter()
+            .map(|s| (s.section.as_str(), s.count))
+            .collect();
+        assert_eq!(sections, vec![("/blog", 4), ("/pictures", 1)]);
+
+        let blog = &archive[0];
+        let years: Vec<(i32, u32)> = blog.years.iter().map(|y| (y.year, y.count)).collect();
+        assert_eq!(years, vec![(2024, 3), (2023, 1)]);
+        let may = &blog.years[0].months[0];
+        assert_eq!((may.month, may.count), (5, 2));
+        assert_eq!(may.entries, vec!["/blog/c.md", "/blog/b.md"]);
+        assert_eq!(blog.years[0].months[1].month, 1);
+    }
+}
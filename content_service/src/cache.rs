@@ -0,0 +1,498 @@
+//! Responses kept between visits, so a repeat visit renders from what was
+//! fetched last time, even offline. Copies younger than `max_age` are used
+//! as they are, older ones are served while the client asks the server
+//! whether they changed (`If-None-Match` with the response's `ETag`). The
+//! least recently used copies go once they add up to `max_bytes`. Which
+//! copy was used last is tracked in memory, and only written back to the
+//! store along with the next copy kept, or on `flush`. A response too large
+//! for the store on its own is only kept in memory, for the rest of the
+//! visit.
+//!
+//! The browser build keeps them in `localStorage`, native builds in memory.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+// Prefix of every key written to the store
+const KEY_PREFIX: &str = "olifm-cache:";
+// Key of the recency list, least recently used first
+const INDEX_KEY: &str = "olifm-cache-index";
+
+/// Somewhere to keep strings by key. Writes may fail when the store is
+/// full, the cache then makes room and tries again.
+pub trait CacheStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// `false` when the value couldn't be kept
+    fn set(&self, key: &str, value: &str) -> bool;
+
+    fn remove(&self, key: &str);
+}
+
+/// Lost when the process ends, for native builds and tests
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    values: Mutex<HashMap<String, String>>,
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<String> {
+        self.values.lock().ok()?.get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) -> bool {
+        match self.values.lock() {
+            Ok(mut values) => {
+                values.insert(key.to_string(), value.to_string());
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        if let Ok(mut values) = self.values.lock() {
+            values.remove(key);
+        }
+    }
+}
+
+/// The browser's `localStorage`. Where it's unavailable, e.g. with storage
+/// blocked, nothing is kept and every read misses.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalStorageStore;
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorageStore {
+    fn storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok().flatten()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl CacheStore for LocalStorageStore {
+    fn get(&self, key: &str) -> Option<String> {
+        Self::storage()?.get_item(key).ok().flatten()
+    }
+
+    fn set(&self, key: &str, value: &str) -> bool {
+        Self::storage().is_some_and(|storage| storage.set_item(key, value).is_ok())
+    }
+
+    fn remove(&self, key: &str) {
+        if let Some(storage) = Self::storage() {
+            let _ = storage.remove_item(key);
+        }
+    }
+}
+
+/// A response body as it was cached
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CachedResponse {
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// Unix seconds of the last time the server sent or confirmed it
+    pub fetched_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct Slot {
+    url: String,
+    size: usize,
+}
+
+/// The recency list, read from the store the first time it's needed
+#[derive(Debug, Default)]
+struct Recency {
+    slots: Option<Vec<Slot>>,
+    // Reordered since it was last written back
+    dirty: bool,
+}
+
+#[derive(Clone)]
+pub struct Cache {
+    store: Arc<dyn CacheStore>,
+    max_bytes: usize,
+    max_age: Duration,
+    recency: Arc<Mutex<Recency>>,
+    // Responses over `max_bytes`, which only last as long as the page
+    oversize: Arc<Mutex<HashMap<String, CachedResponse>>>,
+    // URLs being revalidated in the background, so each is asked once
+    revalidating: Arc<Mutex<HashSet<String>>>,
+}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("max_bytes", &self.max_bytes)
+            .field("max_age", &self.max_age)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Cache {
+    /// `localStorage` in the browser, memory elsewhere
+    fn default() -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let store = LocalStorageStore;
+        #[cfg(not(target_arch = "wasm32"))]
+        let store = MemoryStore::default();
+        Self::new(store)
+    }
+}
+
+impl Cache {
+    /// Up to 4 MB, under `localStorage`'s usual 5 MB, fresh for 5 minutes
+    pub fn new(store: impl CacheStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            max_bytes: 4 * 1024 * 1024,
+            max_age: Duration::from_secs(5 * 60),
+            recency: Arc::default(),
+            oversize: Arc::default(),
+            revalidating: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub fn memory() -> Self {
+        Self::new(MemoryStore::default())
+    }
+
+    /// Drop the least recently used copies beyond `max_bytes` of stored
+    /// responses
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Use copies without revalidating for `max_age` after they're fetched
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Whether something fetched at `fetched_at` can be used as it is at
+    /// `now`, both in Unix seconds
+    pub fn is_fresh(&self, fetched_at: i64, now: i64) -> bool {
+        now.saturating_sub(fetched_at) < self.max_age.as_secs() as i64
+    }
+
+    /// The cached copy of `url`, now the most recently used
+    pub fn get(&self, url: &str) -> Option<CachedResponse> {
+        if let Some(cached) = lock(&self.oversize).get(url) {
+            return Some(cached.clone());
+        }
+        let value = self.store.get(&key(url));
+        let cached = value
+            .as_deref()
+            .and_then(|value| serde_json::from_str::<CachedResponse>(value).ok());
+        let mut recency = self.recency();
+        let slots = self.slots(&mut recency);
+        let listed = slots.len();
+        slots.retain(|slot| slot.url != url);
+        match value.filter(|_| cached.is_some()) {
+            Some(value) => slots.push(Slot {
+                url: url.to_string(),
+                size: value.len(),
+            }),
+            None if slots.len() == listed => return None,
+            // Written by an older build or cleared behind our back
+            None => self.store.remove(&key(url)),
+        }
+        recency.dirty = true;
+        cached
+    }
+
+    /// Keep `response` for `url`, making room for it. `false` when it
+    /// could only be kept in memory, e.g. on its own it's larger than
+    /// `max_bytes`.
+    pub fn put(&self, url: &str, response: &CachedResponse) -> bool {
+        let mut recency = self.recency();
+        let slots = self.slots(&mut recency);
+        slots.retain(|slot| slot.url != url);
+        lock(&self.oversize).remove(url);
+        let Ok(value) = serde_json::to_string(response) else {
+            return false;
+        };
+        let size = value.len();
+        if size > self.max_bytes {
+            warn(&format!(
+                "Caching {} for this visit only: {} bytes is over the cache's {}",
+                url, size, self.max_bytes
+            ));
+            lock(&self.oversize).insert(url.to_string(), response.clone());
+            self.store.remove(&key(url));
+            self.save_slots(&mut recency);
+            return false;
+        }
+
+        let mut total: usize = slots.iter().map(|slot| slot.size).sum();
+        while total + size > self.max_bytes && !slots.is_empty() {
+            total -= self.evict(slots);
+        }
+        // The store can be full before the cap is, e.g. shared with the
+        // rest of the site
+        let mut kept = true;
+        while !self.store.set(&key(url), &value) {
+            if slots.is_empty() {
+                warn(&format!(
+                    "Caching {} for this visit only: the store is full",
+                    url
+                ));
+                lock(&self.oversize).insert(url.to_string(), response.clone());
+                kept = false;
+                break;
+            }
+            self.evict(slots);
+        }
+        if kept {
+            slots.push(Slot {
+                url: url.to_string(),
+                size,
+            });
+        }
+        self.save_slots(&mut recency);
+        kept
+    }
+
+    /// Write the recency list back to the store if reads have reordered it
+    pub fn flush(&self) {
+        let mut recency = self.recency();
+        if recency.dirty {
+            self.save_slots(&mut recency);
+        }
+    }
+
+    /// Claim the background revalidation of `url`, `false` if one is
+    /// already under way
+    pub fn start_revalidating(&self, url: &str) -> bool {
+        self.revalidating
+            .lock()
+            .is_ok_and(|mut urls| urls.insert(url.to_string()))
+    }
+
+    pub fn done_revalidating(&self, url: &str) {
+        if let Ok(mut urls) = self.revalidating.lock() {
+            urls.remove(url);
+        }
+    }
+
+    /// Drop the least recently used copy, returning its size
+    fn evict(&self, slots: &mut Vec<Slot>) -> usize {
+        let slot = slots.remove(0);
+        self.store.remove(&key(&slot.url));
+        slot.size
+    }
+
+    fn recency(&self) -> MutexGuard<'_, Recency> {
+        lock(&self.recency)
+    }
+
+    /// The recency list, read from the store on first use
+    fn slots<'a>(&self, recency: &'a mut Recency) -> &'a mut Vec<Slot> {
+        recency.slots.get_or_insert_with(|| {
+            self.store
+                .get(INDEX_KEY)
+                .and_then(|value| serde_json::from_str(&value).ok())
+                .unwrap_or_default()
+        })
+    }
+
+    fn save_slots(&self, recency: &mut Recency) {
+        let Some(slots) = &recency.slots else {
+            return;
+        };
+        if let Ok(value) = serde_json::to_string(slots) {
+            recency.dirty = !self.store.set(INDEX_KEY, &value);
+        }
+    }
+}
+
+fn lock<V>(mutex: &Mutex<V>) -> MutexGuard<'_, V> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn key(url: &str) -> String {
+    format!("{}{}", KEY_PREFIX, url)
+}
+
+fn warn(message: &str) {
+    #[cfg(target_arch = "wasm32")]
+    crate::utils::console_warn(message);
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("WARNING: {}", message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::models::{Audio, Img, JsonEntry, SiteIndex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn response(body: &str) -> CachedResponse {
+        CachedResponse {
+            body: body.to_string(),
+            etag: Some("\"v1\"".to_string()),
+            fetched_at: 1_000,
+        }
+    }
+
+    /// A `MemoryStore` that outlives the caches over it, like
+    /// `localStorage` between visits, and counts index writes
+    #[derive(Clone, Default)]
+    struct SharedStore {
+        values: Arc<MemoryStore>,
+        index_writes: Arc<AtomicUsize>,
+    }
+
+    impl CacheStore for SharedStore {
+        fn get(&self, key: &str) -> Option<String> {
+            self.values.get(key)
+        }
+
+        fn set(&self, key: &str, value: &str) -> bool {
+            if key == INDEX_KEY {
+                self.index_writes.fetch_add(1, Ordering::SeqCst);
+            }
+            self.values.set(key, value)
+        }
+
+        fn remove(&self, key: &str) {
+            self.values.remove(key)
+        }
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let size = serde_json::to_string(&response("aaaa")).unwrap().len();
+        let cache = Cache::memory().with_max_bytes(2 * size + 1);
+        cache.put("/a", &response("aaaa"));
+        cache.put("/b", &response("bbbb"));
+        // Reading `/a` makes `/b` the oldest
+        assert_eq!(cache.get("/a"), Some(response("aaaa")));
+        cache.put("/c", &response("cccc"));
+
+        assert_eq!(cache.get("/b"), None);
+        assert!(cache.get("/a").is_some() && cache.get("/c").is_some());
+    }
+
+    #[test]
+    fn reads_reorder_in_memory_until_flushed() {
+        let store = SharedStore::default();
+        let cache = Cache::new(store.clone());
+        cache.put("/a", &response("aaaa"));
+        cache.put("/b", &response("bbbb"));
+        let writes = store.index_writes.load(Ordering::SeqCst);
+
+        for _ in 0..10 {
+            assert!(cache.get("/a").is_some());
+        }
+        assert_eq!(store.index_writes.load(Ordering::SeqCst), writes);
+        cache.flush();
+        cache.flush();
+        assert_eq!(store.index_writes.load(Ordering::SeqCst), writes + 1);
+
+        // The next visit sees `/a` as the most recently used
+        let next_visit = Cache::new(store.clone());
+        let size = serde_json::to_string(&response("cccc")).unwrap().len();
+        let next_visit = next_visit.with_max_bytes(2 * size + 1);
+        next_visit.put("/c", &response("cccc"));
+        assert!(next_visit.get("/b").is_none());
+        assert!(next_visit.get("/a").is_some());
+    }
+
+    /// The index of a site about this size: photos with palettes, hashes
+    /// and placeholders, and tracks with their peaks
+    fn realistic_index(photos: usize, tracks: usize) -> String {
+        let photo = |i: usize| JsonEntry {
+            path: format!("/pictures/photo-{}.md", i),
+            entry_type: "document".to_string(),
+            size: 420,
+            name: format!("Photo {}", i),
+            date: Some("2024-05-01".to_string()),
+            images: vec![Img {
+                blurhash: "LKO2?U%2Tw=w]~RBVZRi};RPxuwH".to_string(),
+                aspect_ratio: "4/3".to_string(),
+                name: format!("photo-{}.jpg", i),
+                path: format!("/pictures/photo-{}.jpg", i),
+                palette: vec!["#3a4f63".to_string(); 5],
+                // A 32px placeholder PNG comes to about 1.5kB as base64
+                placeholder: Some(format!("data:image/png;base64,{}", "iVBORw0K".repeat(190))),
+                phash: Some("c3c3e1e1f0f0f8f8".to_string()),
+                animation: None,
+            }],
+            metadata: [
+                ("tags", r#"Sequence [String("bird"), String("perth")]"#),
+                ("medium", "Nikon Coolpix P900"),
+            ]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+            ..Default::default()
+        };
+        let track = |i: usize| JsonEntry {
+            path: format!("/sounds/track-{}.md", i),
+            entry_type: "document".to_string(),
+            name: format!("Track {}", i),
+            audio: vec![Audio {
+                name: format!("track-{}.mp3", i),
+                path: format!("/sounds/track-{}.mp3", i),
+                duration_ms: Some(215_000),
+                peaks: vec![187; 200],
+            }],
+            ..Default::default()
+        };
+        let index = SiteIndex {
+            schema: None,
+            schema_version: crate::schema::SCHEMA_VERSION,
+            generated_at: None,
+            site: Default::default(),
+            archive: Vec::new(),
+            entries: (0..photos)
+                .map(photo)
+                .chain((0..tracks).map(track))
+                .collect(),
+        };
+        serde_json::to_string(&index).unwrap()
+    }
+
+    #[test]
+    fn a_realistic_index_is_kept_between_visits() {
+        let index = realistic_index(1_000, 150);
+        assert!(index.len() > 1_500_000, "{} bytes", index.len());
+
+        let store = SharedStore::default();
+        let url = "/directory_structure.json";
+        assert!(Cache::new(store.clone()).put(url, &response(&index)));
+        // Offline on the next visit, it's still there to render from
+        let next_visit = Cache::new(store.clone());
+        assert_eq!(next_visit.get(url).map(|r| r.body.len()), Some(index.len()));
+    }
+
+    #[test]
+    fn oversize_responses_are_kept_for_the_visit_only() {
+        let index = realistic_index(100, 10);
+        let store = SharedStore::default();
+        let cache = Cache::new(store.clone()).with_max_bytes(index.len() / 2);
+        let url = "/directory_structure.json";
+
+        assert!(!cache.put(url, &response(&index)));
+        assert_eq!(cache.get(url), Some(response(&index)));
+        assert_eq!(Cache::new(store).get(url), None);
+    }
+
+    #[test]
+    fn copies_go_stale_after_max_age() {
+        let cache = Cache::memory().with_max_age(Duration::from_secs(60));
+        assert!(cache.is_fresh(1_000, 1_059));
+        assert!(!cache.is_fresh(1_000, 1_060));
+        let always_stale = Cache::memory().with_max_age(Duration::ZERO);
+        assert!(!always_stale.is_fresh(1_000, 1_000));
+    }
+}
@@ -0,0 +1,198 @@
+//! Giving up on work nobody wants any more, e.g. the render of a page the
+//! reader has already navigated away from. Work run under a `CancelToken`
+//! is dropped at its next `.await` once the token is cancelled, and a
+//! `fetch` it was waiting on is aborted with it.
+//!
+//! ```ignore
+//! let token = CancelToken::new();
+//! spawn_cancellable(&token, async move {
+//!     let document = client.get_parsed_document(&url).await;
+//!     // Only reached while `token` hasn't been cancelled
+//!     show(document);
+//! });
+//! token.cancel();
+//! ```
+
+use crate::error::ContentServiceError;
+use futures::future::{self, Either};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use wasm_bindgen_futures::spawn_local;
+
+/// Cancels everything run under it, and every clone of it, at once
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    // Whoever is waiting in `cancelled()`, one slot each until they're
+    // dropped
+    wakers: Mutex<HashMap<u64, Waker>>,
+    next_waiter: AtomicU64,
+}
+
+impl Inner {
+    fn wakers(&self) -> MutexGuard<'_, HashMap<u64, Waker>> {
+        self.wakers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel whatever is running under the token, now and from now on
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        let wakers = std::mem::take(&mut *self.inner.wakers());
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Finishes once the token is cancelled
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+            waiter: None,
+        }
+    }
+
+    /// `future`'s output, or `ContentServiceError::Cancelled` if the token
+    /// is cancelled first. `future` is then dropped without being polled
+    /// again.
+    pub async fn run<F: Future>(&self, future: F) -> Result<F::Output, ContentServiceError> {
+        if self.is_cancelled() {
+            return Err(ContentServiceError::Cancelled);
+        }
+        // The token goes first, so work woken by the same event that
+        // cancelled it doesn't get one more step
+        match future::select(self.cancelled(), pin!(future)).await {
+            Either::Left(_) => Err(ContentServiceError::Cancelled),
+            Either::Right((output, _)) => Ok(output),
+        }
+    }
+}
+
+/// See `CancelToken::cancelled`
+#[derive(Debug)]
+pub struct Cancelled {
+    token: CancelToken,
+    // Its slot in the token's wakers, from the first time it's polled
+    waiter: Option<u64>,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let inner = Arc::clone(&self.token.inner);
+        let waiter = *self
+            .waiter
+            .get_or_insert_with(|| inner.next_waiter.fetch_add(1, Ordering::Relaxed));
+        let mut wakers = inner.wakers();
+        // Replaced rather than added to when polled from somewhere else
+        if !wakers
+            .get(&waiter)
+            .is_some_and(|waker| waker.will_wake(cx.waker()))
+        {
+            wakers.insert(waiter, cx.waker().clone());
+        }
+        drop(wakers);
+        // `cancel` may have run between the check and the waker going in
+        if self.token.is_cancelled() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Cancelled {
+    fn drop(&mut self) {
+        if let Some(waiter) = self.waiter {
+            self.token.inner.wakers().remove(&waiter);
+        }
+    }
+}
+
+/// Run `future` in the background until it's done or `token` is cancelled
+pub fn spawn_cancellable<F>(token: &CancelToken, future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    let token = token.clone();
+    spawn_local(async move {
+        let _ = token.run(future).await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn cancelling_drops_the_work_at_its_next_await() {
+        let token = CancelToken::new();
+        let steps = Cell::new(0);
+        let work = async {
+            steps.set(1);
+            token.cancel();
+            // Woken straight away by `cancel`, but the token is looked at
+            // first
+            futures::pending!();
+            steps.set(2);
+        };
+
+        let result = pollster::block_on(token.run(work));
+        assert!(matches!(result, Err(ContentServiceError::Cancelled)));
+        assert_eq!(steps.get(), 1);
+
+        // Too late to start anything else
+        let later = pollster::block_on(token.run(async { steps.set(3) }));
+        assert!(later.is_err());
+        assert_eq!(steps.get(), 1);
+    }
+
+    #[test]
+    fn uncancelled_work_runs_to_completion() {
+        let token = CancelToken::new();
+        let other = token.clone();
+        assert_eq!(pollster::block_on(other.run(async { 7 })).unwrap(), 7);
+        assert!(!token.is_cancelled());
+    }
+
+    #[test]
+    fn finished_work_leaves_no_wakers_behind() {
+        let token = CancelToken::new();
+        for _ in 0..3 {
+            // Polled twice, then dropped unfinished
+            let work = future::pending::<()>();
+            let waker = futures::task::noop_waker();
+            let mut run = pin!(token.run(work));
+            let mut cx = Context::from_waker(&waker);
+            assert!(run.as_mut().poll(&mut cx).is_pending());
+            assert!(run.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(token.inner.wakers().len(), 1);
+        }
+        assert!(token.inner.wakers().is_empty());
+        assert_eq!(pollster::block_on(token.run(async { 1 })).unwrap(), 1);
+        assert!(token.inner.wakers().is_empty());
+    }
+}
@@ -1,152 +1,314 @@
+use crate::archive::build_archive;
+use crate::cache::{Cache, CachedResponse};
+use crate::cancel::{spawn_cancellable, CancelToken};
+use crate::document::Document;
 use crate::error::ContentServiceError;
-use crate::models::JsonEntry;
-use gloo_net::http::Request;
-use std::collections::HashMap;
+use crate::models::{ArchiveSection, JsonEntry, SiteInfo};
+use crate::paging::ContentPage;
+use crate::query::ContentQuery;
+use crate::retry::{parse_retry_after, RetryPolicy};
+use crate::schema::parse_site_index;
+use crate::section::Section;
+use crate::transport::{FetchTransport, Response, Transport};
+use chrono::Utc;
+use futures::lock::Mutex as AsyncMutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen_futures::spawn_local;
 
-// User-Agent string for API requests
-const USER_AGENT: &str = "olifm-rust/1.0";
-
-// The in-memory map exposed publicly
+// Fetches go through `T`, the browser's `fetch` unless another transport is
+// given, and are kept in `cache`. Clones share the parsed index and the
+// requests under way, so one client can serve a whole page's worth of
+// concurrent calls through `&self`.
 #[derive(Debug, Clone)]
-pub struct ContentServiceClient {
+pub struct ContentServiceClient<T = FetchTransport> {
     base_url: String,
-    pub files: Vec<JsonEntry>,
-    pub documents: HashMap<String, String>,
+    transport: T,
+    retry: RetryPolicy,
+    cache: Cache,
+    index: Arc<Mutex<Index>>,
+    in_flight: Arc<Mutex<HashMap<String, InFlight>>>,
 }
 
-impl ContentServiceClient {
-    pub fn directory_structure_url(&self) -> String {
-        format!("{}/directory_structure.json", self.base_url)
+/// The parsed `directory_structure.json`
+#[derive(Debug, Default)]
+struct Index {
+    files: Vec<JsonEntry>,
+    site: SiteInfo,
+    archive: Vec<ArchiveSection>,
+    // When `files` was fetched, Unix seconds
+    fetched_at: Option<i64>,
+}
+
+/// The answer to a request under way, filled in by whoever made it. The
+/// lock is theirs until then.
+type InFlight = Arc<AsyncMutex<Option<Result<CachedResponse, ContentServiceError>>>>;
+
+/// Nothing panics while these are held, but a poisoned lock shouldn't take
+/// the site down with it
+fn locked<V>(mutex: &Mutex<V>) -> MutexGuard<'_, V> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Default for ContentServiceClient {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
+impl ContentServiceClient {
     pub fn new() -> Self {
-        Self {
-            base_url: "https://oli.fm".to_string(),
-            files: Vec::new(),
-            documents: HashMap::new(),
-        }
+        Self::with_base_url("https://oli.fm")
     }
 
     pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self::with_transport(base_url, FetchTransport)
+    }
+}
+
+impl<T: Transport> ContentServiceClient<T> {
+    /// Fetch through `transport`, e.g. an `FsTransport` over a helper output
+    /// directory with an empty `base_url`
+    pub fn with_transport(base_url: impl Into<String>, transport: T) -> Self {
         Self {
             base_url: base_url.into(),
-            files: Vec::new(),
-            documents: HashMap::new(),
+            transport,
+            retry: RetryPolicy::default(),
+            cache: Cache::default(),
+            index: Arc::default(),
+            in_flight: Arc::default(),
         }
     }
 
+    /// Retry failed GETs per `retry` rather than the default policy
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Keep responses in `cache` rather than the default, `localStorage` in
+    /// the browser and memory elsewhere. Clients given clones of one cache
+    /// share it.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Save the cache's recency order, which reads only change in memory.
+    /// Worth calling before the page goes away.
+    pub fn flush_cache(&self) {
+        self.cache.flush();
+    }
+
+    pub fn directory_structure_url(&self) -> String {
+        format!("{}/directory_structure.json", self.base_url)
+    }
+
     /// Get the base URL being used
     pub fn base_url(&self) -> &str {
         &self.base_url
     }
 
-    /// Internal: fetch and parse the directory structure from the static JSON
-    async fn fetch_directory_structure(&mut self) -> Result<Vec<JsonEntry>, ContentServiceError> {
-        if self.files.is_empty() {
-            let url = self.directory_structure_url();
-            let resp = Request::get(&url)
-                .header("User-Agent", USER_AGENT)
-                .send()
-                .await?;
-
-            if !resp.ok() {
-                return Err(ContentServiceError::NetworkError(format!(
-                    "HTTP error: {}",
-                    resp.status()
-                )));
-            }
-
-            let text = resp.text().await.map_err(|e| {
-                ContentServiceError::NetworkError(format!("Failed to read response text: {:?}", e))
-            })?;
+    /// `url` from the cache while it's fresh. A stale copy is served as it
+    /// is while the server is asked whether it changed, in the background
+    /// in the browser. Offline, the stale copy is all there is.
+    async fn fetch_cached(&self, url: &str) -> Result<CachedResponse, ContentServiceError> {
+        let Some(stale) = self.cache.get(url) else {
+            return revalidate(&self.transport, &self.retry, &self.cache, url, None).await;
+        };
+        let now = Utc::now().timestamp();
+        if self.cache.is_fresh(stale.fetched_at, now) {
+            return Ok(stale);
+        }
 
-            let items: Vec<JsonEntry> =
-                serde_json::from_str(&text).map_err(ContentServiceError::ParseError)?;
-            self.files = items.clone();
-            Ok(items)
-        } else {
-            Ok(self.files.clone())
+        #[cfg(target_arch = "wasm32")]
+        {
+            if self.cache.start_revalidating(url) {
+                let (transport, retry, cache) =
+                    (self.transport.clone(), self.retry, self.cache.clone());
+                let (url, cached) = (url.to_string(), stale.clone());
+                spawn_local(async move {
+                    // On failure the stale copy stays for next time
+                    let _ = revalidate(&transport, &retry, &cache, &url, Some(&cached)).await;
+                    cache.done_revalidating(&url);
+                });
+            }
+            Ok(stale)
+        }
+        // Native transports block anyway, so the answer is waited for
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let fresh = revalidate(&self.transport, &self.retry, &self.cache, url, Some(&stale));
+            match fresh.await {
+                Err(e) if e.is_transient() => Ok(stale),
+                result => result,
+            }
         }
     }
 
-    pub async fn get_content(
-        &mut self,
-        path: String,
-        filter: Option<String>,
-    ) -> Result<Vec<JsonEntry>, ContentServiceError> {
-        // Fetch directory structure JSON
-        let items = self.fetch_directory_structure().await?;
-
-        // Filter items
-        let mut filtered_items: Vec<JsonEntry> = items
-            .into_iter()
-            .filter(|item| {
-                // Check path prefix
-                if !item.path.starts_with(&path) {
-                    return false;
-                }
-
-                // Check optional type filter
-                if let Some(ref f) = filter {
-                    if item.entry_type != *f {
-                        return false;
+    /// `fetch_cached`, except that asking for a URL already being fetched
+    /// waits for that answer rather than asking again
+    async fn fetch_shared(&self, url: &str) -> Result<CachedResponse, ContentServiceError> {
+        loop {
+            let (slot, claimed) = {
+                let mut in_flight = locked(&self.in_flight);
+                match in_flight.get(url) {
+                    Some(slot) => (slot.clone(), None),
+                    None => {
+                        let slot = InFlight::default();
+                        let claimed = slot.try_lock_owned();
+                        in_flight.insert(url.to_string(), slot.clone());
+                        (slot, claimed)
                     }
                 }
-                true
-            })
-            .collect();
-
-        // Sort by date (newest first)
-        filtered_items.sort_by(|a, b| {
-            match (&a.date, &b.date) {
-                (Some(date_a), Some(date_b)) => {
-                    // Sort in descending order (newest first)
-                    date_b.cmp(date_a)
-                }
-                (Some(_), None) => {
-                    // Items with dates come before items without dates
-                    std::cmp::Ordering::Less
-                }
-                (None, Some(_)) => {
-                    // Items without dates come after items with dates
-                    std::cmp::Ordering::Greater
-                }
-                (None, None) => {
-                    // If both have no date, sort by name
-                    a.name.cmp(&b.name)
-                }
+            };
+
+            if let Some(mut answer) = claimed {
+                let result = self.fetch_cached(url).await;
+                *answer = Some(match &result {
+                    Ok(response) => Ok(response.clone()),
+                    Err(e) => Err(e.duplicate()),
+                });
+                locked(&self.in_flight).remove(url);
+                return result;
             }
-        });
 
-        Ok(filtered_items)
-    }
-
-    pub async fn get_tags(&mut self, path: String) -> Result<Vec<String>, ContentServiceError> {
-        let json_content = self.get_content(path, None).await?;
-
-        let mut tags: Vec<String> = Vec::new();
-        for entry in json_content {
-            if let Some(doc_tags) = entry.metadata.get("tags") {
-                use regex::Regex;
-                let re = Regex::new(r#"String\("([^"]*)"\)"#).unwrap();
-                let tag_str: Vec<&str> = re
-                    .captures_iter(doc_tags)
-                    .map(|cap| cap.get(1).unwrap().as_str())
-                    .collect();
-                for tag in tag_str {
-                    let to_push = tag.to_string();
-                    if tags.contains(&to_push) == false {
-                        tags.push(to_push);
+            let answer = slot.lock().await;
+            match &*answer {
+                Some(Ok(response)) => return Ok(response.clone()),
+                Some(Err(e)) => return Err(e.duplicate()),
+                // Whoever was fetching it gave up, e.g. their future was
+                // dropped, so the next one round asks instead
+                None => {
+                    let mut in_flight = locked(&self.in_flight);
+                    if in_flight.get(url).is_some_and(|s| Arc::ptr_eq(s, &slot)) {
+                        in_flight.remove(url);
                     }
                 }
             }
         }
+    }
+
+    /// Run `read` on the index, fetching and parsing the directory structure
+    /// first when there's none or it's gone stale
+    async fn with_index<R>(
+        &self,
+        read: impl FnOnce(&Index) -> R,
+    ) -> Result<R, ContentServiceError> {
+        let now = Utc::now().timestamp();
+        {
+            let index = locked(&self.index);
+            if index
+                .fetched_at
+                .is_some_and(|fetched_at| self.cache.is_fresh(fetched_at, now))
+            {
+                return Ok(read(&index));
+            }
+        }
 
-        Ok(tags)
+        let response = self.fetch_shared(&self.directory_structure_url()).await?;
+        let mut index = locked(&self.index);
+        // A stale copy being revalidated is parsed once, then again when
+        // the revalidated one turns up
+        if index.fetched_at != Some(response.fetched_at) {
+            // Older bare-array indexes are migrated, newer ones are an error
+            let site_index = parse_site_index(&response.body)?;
+            // Indexes written before the archive existed get one built here
+            index.archive = if site_index.archive.is_empty() {
+                build_archive(&site_index.entries)
+            } else {
+                site_index.archive
+            };
+            index.site = site_index.site;
+            index.files = site_index.entries;
+            index.fetched_at = Some(response.fetched_at);
+        }
+        Ok(read(&index))
     }
 
-    pub async fn get_document(&mut self, path: &str) -> Result<String, ContentServiceError> {
+    /// Run `read` on every entry in the index, borrowing rather than copying
+    /// them
+    pub async fn read_entries<R>(
+        &self,
+        read: impl FnOnce(&[JsonEntry]) -> R,
+    ) -> Result<R, ContentServiceError> {
+        self.with_index(|index| read(&index.files)).await
+    }
+
+    /// The index as last loaded, empty before the first fetch
+    pub fn files(&self) -> Vec<JsonEntry> {
+        locked(&self.index).files.clone()
+    }
+
+    /// Site name and description from the index envelope
+    pub async fn get_site_info(&self) -> Result<SiteInfo, ContentServiceError> {
+        self.with_index(|index| index.site.clone()).await
+    }
+
+    /// Dated documents by section, year and month
+    pub async fn get_archive(&self) -> Result<Vec<ArchiveSection>, ContentServiceError> {
+        self.with_index(|index| index.archive.clone()).await
+    }
+
+    /// How the section at `path`, e.g. "/pictures", lists its entries. A
+    /// section without a readme or frontmatter gets the defaults.
+    pub async fn get_section(&self, path: &str) -> Result<Section, ContentServiceError> {
+        self.with_index(|index| {
+            index
+                .files
+                .iter()
+                .find(|item| item.is_directory() && item.path == path)
+                .map(Section::from_entry)
+                .unwrap_or_default()
+        })
+        .await
+    }
+
+    /// The entries `query` picks out of the index, see `ContentQuery`
+    pub async fn query(&self, query: &ContentQuery) -> Result<ContentPage, ContentServiceError> {
+        self.with_index(|index| query.run(&index.files)).await
+    }
+
+    /// Everything under `path`, optionally of one entry type, pinned then
+    /// newest first
+    pub async fn get_content(
+        &self,
+        path: String,
+        filter: Option<String>,
+    ) -> Result<Vec<JsonEntry>, ContentServiceError> {
+        let mut query = ContentQuery::new(path);
+        if let Some(entry_type) = filter {
+            query = query.entry_type(entry_type);
+        }
+        Ok(self.query(&query).await?.entries)
+    }
+
+    /// `get_content` a page at a time, up to `limit` entries from `offset`
+    pub async fn get_content_page(
+        &self,
+        path: String,
+        filter: Option<String>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<ContentPage, ContentServiceError> {
+        let items = self.get_content(path, filter).await?;
+        Ok(ContentPage::new(items, offset, limit))
+    }
+
+    /// Every tag used under `path`, once each in the order first seen
+    pub async fn get_tags(&self, path: String) -> Result<Vec<String>, ContentServiceError> {
+        let mut seen = HashSet::new();
+        Ok(self
+            .get_content(path, None)
+            .await?
+            .iter()
+            .flat_map(JsonEntry::tags)
+            .filter(|tag| seen.insert(tag.clone()))
+            .collect())
+    }
+
+    pub async fn get_document(&self, path: &str) -> Result<String, ContentServiceError> {
         if path.trim().is_empty() {
             return Err(ContentServiceError::InvalidInput(
                 "Path cannot be empty".to_string(),
@@ -160,39 +322,83 @@ impl ContentServiceClient {
             format!("{}/{}", self.base_url, clean_path)
         };
 
-        if self.documents.contains_key(&document_url) {
-            return Ok(self.documents[&document_url].clone());
-        } else {
-            let resp = Request::get(&document_url)
-                .header("User-Agent", USER_AGENT)
-                .send()
-                .await?;
-
-            if !resp.ok() {
-                return Err(ContentServiceError::NetworkError(format!(
-                    "HTTP error {}: Failed to fetch document from {}",
-                    resp.status(),
-                    document_url
-                )));
-            }
+        Ok(self.fetch_shared(&document_url).await?.body)
+    }
 
-            let markdown_content = resp.text().await.map_err(|e| {
-                ContentServiceError::NetworkError(format!(
-                    "Failed to read document content: {:?}",
-                    e
-                ))
-            })?;
-            self.documents
-                .insert(document_url.clone(), markdown_content.clone());
-            Ok(markdown_content)
+    /// `get_document` read into a `Document`, the way every page renders it
+    pub async fn get_parsed_document(&self, path: &str) -> Result<Document, ContentServiceError> {
+        Ok(Document::parse(&self.get_document(path).await?))
+    }
+}
+
+/// GET `url`, retrying transient failures per `retry`. Anything but a 2xx
+/// or 304 answer is an error carrying the URL and status.
+async fn fetch<T: Transport>(
+    transport: &T,
+    retry: &RetryPolicy,
+    url: &str,
+    headers: &[(&str, &str)],
+) -> Result<Response, ContentServiceError> {
+    let mut attempt = 0;
+    loop {
+        let error = match transport.get(url, headers).await {
+            Ok(resp) if resp.ok() || resp.status == 304 => return Ok(resp),
+            Ok(resp) => {
+                let retry_after = resp
+                    .header("retry-after")
+                    .and_then(|value| parse_retry_after(value, Utc::now()));
+                ContentServiceError::from_status(url, resp.status, retry_after)
+            }
+            Err(e) => e,
+        };
+        match retry.delay(&error, attempt) {
+            Some(delay) => {
+                transport.sleep(delay).await;
+                attempt += 1;
+            }
+            None => return Err(error),
         }
     }
 }
 
-/// Callback-based content service API client for WASM compatibility
+/// Fetch `url` into `cache`, asking only for changes since `stale`
+async fn revalidate<T: Transport>(
+    transport: &T,
+    retry: &RetryPolicy,
+    cache: &Cache,
+    url: &str,
+    stale: Option<&CachedResponse>,
+) -> Result<CachedResponse, ContentServiceError> {
+    let headers = match stale.and_then(|stale| stale.etag.as_deref()) {
+        Some(etag) => vec![("If-None-Match", etag)],
+        None => Vec::new(),
+    };
+    let resp = fetch(transport, retry, url, &headers).await?;
+
+    let fetched_at = Utc::now().timestamp();
+    let fresh = match (resp.status, stale) {
+        (304, Some(stale)) => CachedResponse {
+            fetched_at,
+            ..stale.clone()
+        },
+        // Not asked for, so not something to cache
+        (304, None) => return Err(ContentServiceError::from_status(url, 304, None)),
+        _ => CachedResponse {
+            etag: resp.header("etag").map(str::to_string),
+            body: resp.body,
+            fetched_at,
+        },
+    };
+    cache.put(url, &fresh);
+    Ok(fresh)
+}
+
+/// Callback-based content service API client for WASM compatibility.
+/// Callbacks aren't called once its `CancelToken` is cancelled.
 #[derive(Debug, Clone)]
 pub struct ContentServiceClientCallback {
     inner: ContentServiceClient,
+    cancel: CancelToken,
 }
 
 impl Default for ContentServiceClientCallback {
@@ -206,6 +412,7 @@ impl ContentServiceClientCallback {
     pub fn new() -> Self {
         Self {
             inner: ContentServiceClient::new(),
+            cancel: CancelToken::new(),
         }
     }
 
@@ -213,23 +420,32 @@ impl ContentServiceClientCallback {
     pub fn with_base_url(base_url: impl Into<String>) -> Self {
         Self {
             inner: ContentServiceClient::with_base_url(base_url),
+            cancel: CancelToken::new(),
         }
     }
 
+    /// Drop requests still under way, callbacks and all, when `cancel` is
+    /// cancelled
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
     /// Get the base URL being used
     pub fn base_url(&self) -> &str {
         self.inner.base_url()
     }
 
-    /// Fetch directory content by path with callback (results sorted by date)
+    /// Fetch directory content by path with callback (results sorted pinned
+    /// first, then by date)
     /// Added parameter: filter to apply on the loaded directory structure
     pub fn get_content<F>(&self, path: String, filter: Option<String>, callback: F)
     where
         F: FnOnce(Result<Vec<JsonEntry>, ContentServiceError>) + 'static,
     {
-        let mut inner = self.inner.clone();
+        let inner = self.inner.clone();
 
-        spawn_local(async move {
+        spawn_cancellable(&self.cancel, async move {
             let res = inner.get_content(path, filter).await;
             callback(res);
         });
@@ -239,9 +455,9 @@ impl ContentServiceClientCallback {
     where
         F: FnOnce(Result<Vec<String>, ContentServiceError>) + 'static,
     {
-        let mut inner = self.inner.clone();
+        let inner = self.inner.clone();
 
-        spawn_local(async move {
+        spawn_cancellable(&self.cancel, async move {
             let res = inner.get_tags(path).await;
             callback(res);
         });
@@ -251,9 +467,9 @@ impl ContentServiceClientCallback {
     where
         F: FnOnce(Result<String, ContentServiceError>) + 'static,
     {
-        let mut inner = self.inner.clone();
+        let inner = self.inner.clone();
         let doc_path = path.to_string();
-        spawn_local(async move {
+        spawn_cancellable(&self.cancel, async move {
             let result = inner.get_document(&doc_path).await;
             callback(result);
         });
@@ -263,6 +479,7 @@ impl ContentServiceClientCallback {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::query::sort_pinned_then_newest;
 
     #[test]
     fn test_client_creation() {
@@ -270,9 +487,177 @@ mod tests {
         assert_eq!(client.base_url(), "https://oli.fm");
     }
 
+    #[test]
+    fn test_pinned_entries_sort_first() {
+        let entry = |name: &str, date: Option<&str>, pinned: bool| JsonEntry {
+            path: format!("/blog/{}", name),
+            entry_type: crate::models::entry_types::DOCUMENT.to_string(),
+            name: name.to_string(),
+            date: date.map(str::to_string),
+            pinned,
+            ..Default::default()
+        };
+        let mut entries = vec![
+            entry("new.md", Some("2025-01-01"), false),
+            entry("old-pin.md", Some("2020-01-01"), true),
+            entry("undated.md", None, false),
+            entry("new-pin.md", Some("2024-01-01"), true),
+            entry("old.md", Some("2021-01-01"), false),
+        ];
+        sort_pinned_then_newest(&mut entries);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["new-pin.md", "old-pin.md", "new.md", "old.md", "undated.md"]
+        );
+    }
+
     #[test]
     fn test_client_with_custom_base_url() {
         let client = ContentServiceClient::with_base_url("https://custom.contentservice.com");
         assert_eq!(client.base_url(), "https://custom.contentservice.com");
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_client_reads_an_output_directory() {
+        use crate::transport::FsTransport;
+        use std::fs;
+
+        let root = std::env::temp_dir().join(format!("olifm-client-{}", std::process::id()));
+        fs::create_dir_all(root.join("content/blog")).unwrap();
+        fs::write(
+            root.join("directory_structure.json"),
+            r#"{"schema_version":3,"entries":[
+                {"path":"/blog/old.md","type":"document","size":1,"name":"old.md","date":"2020-01-01",
+                 "metadata":{"tags":"Sequence [String(\"rust\"), String(\"notes\")]"}},
+                {"path":"/blog/new.md","type":"document","size":1,"name":"new.md","date":"2024-01-01",
+                 "metadata":{"tags":"Sequence [String(\"rust\")]"}},
+                {"path":"/blog/cv.pdf","type":"pdf","size":1,"name":"cv.pdf"},
+                {"path":"/about.md","type":"document","size":1,"name":"about.md"}
+            ]}"#,
+        )
+        .unwrap();
+        fs::write(root.join("content/blog/new.md"), "# New").unwrap();
+
+        let client = ContentServiceClient::with_transport("", FsTransport::new(&root));
+        pollster::block_on(async {
+            let blog = client
+                .get_content("/blog".to_string(), Some("document".to_string()))
+                .await
+                .unwrap();
+            let names: Vec<&str> = blog.iter().map(|e| e.name.as_str()).collect();
+            assert_eq!(names, vec!["new.md", "old.md"]);
+
+            let page = client
+                .get_content_page("/".to_string(), None, 1, Some(2))
+                .await
+                .unwrap();
+            assert_eq!((page.entries.len(), page.total), (2, 4));
+
+            let tags = client.get_tags("/blog".to_string()).await.unwrap();
+            assert_eq!(tags, vec!["rust", "notes"]);
+
+            assert_eq!(
+                client.get_document("/content/blog/new.md").await.unwrap(),
+                "# New"
+            );
+            assert!(client.get_document("/content/blog/old.md").await.is_err());
+
+            let document = client
+                .get_parsed_document("/content/blog/new.md")
+                .await
+                .unwrap();
+            assert_eq!(document.outline[0].text, "New");
+        });
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// Answers every URL with its own path after yielding once, so
+    /// concurrent requests overlap, and counts what it was asked
+    #[derive(Clone, Default)]
+    struct SlowTransport {
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Transport for SlowTransport {
+        async fn get(
+            &self,
+            url: &str,
+            _headers: &[(&str, &str)],
+        ) -> Result<Response, ContentServiceError> {
+            locked(&self.requests).push(url.to_string());
+            let mut yielded = false;
+            std::future::poll_fn(|cx| {
+                if yielded {
+                    return std::task::Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                std::task::Poll::Pending
+            })
+            .await;
+            let status = if url.ends_with("missing.md") {
+                404
+            } else {
+                200
+            };
+            Ok(Response {
+                status,
+                headers: Default::default(),
+                body: url.to_string(),
+            })
+        }
+
+        async fn sleep(&self, _duration: std::time::Duration) {}
+    }
+
+    #[test]
+    fn test_concurrent_requests_for_a_url_are_made_once() {
+        let transport = SlowTransport::default();
+        let client = ContentServiceClient::with_transport("", transport.clone());
+
+        // Clones share what's under way
+        let other = client.clone();
+        let (a, b, c, missing, also_missing) = pollster::block_on(async {
+            futures::join!(
+                client.get_document("/a.md"),
+                other.get_document("/a.md"),
+                client.get_document("/b.md"),
+                client.get_document("/missing.md"),
+                other.get_document("/missing.md"),
+            )
+        });
+        assert_eq!(a.unwrap(), "/a.md");
+        assert_eq!(b.unwrap(), "/a.md");
+        assert_eq!(c.unwrap(), "/b.md");
+        for result in [missing, also_missing] {
+            assert!(matches!(result, Err(ContentServiceError::NotFound { .. })));
+        }
+
+        let mut requests = locked(&transport.requests).clone();
+        requests.sort();
+        assert_eq!(requests, vec!["/a.md", "/b.md", "/missing.md"]);
+    }
+
+    #[test]
+    fn test_a_cancelled_request_leaves_others_waiting_on_it_answered() {
+        let transport = SlowTransport::default();
+        let client = ContentServiceClient::with_transport("", transport.clone());
+        let token = CancelToken::new();
+
+        let (cancelled, waiting, _) = pollster::block_on(async {
+            futures::join!(
+                token.run(client.get_document("/a.md")),
+                client.get_document("/a.md"),
+                // By now the first is fetching and the second waiting on it
+                async { token.cancel() },
+            )
+        });
+        assert!(matches!(cancelled, Err(ContentServiceError::Cancelled)));
+        assert_eq!(waiting.unwrap(), "/a.md");
+        // The second asked again once the first gave up
+        assert_eq!(*locked(&transport.requests), vec!["/a.md", "/a.md"]);
+    }
 }
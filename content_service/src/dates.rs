@@ -0,0 +1,41 @@
+//! Dates as they are written in frontmatter and the index
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+/// Formats seen in frontmatter `date` fields, tried in order
+const DATE_FORMATS: [&str; 6] = [
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%d/%m/%Y",
+    "%d-%m-%Y",
+    "%B %d, %Y",
+    "%b %d, %Y",
+];
+
+/// Parse a frontmatter or index date into a calendar day
+pub fn parse_content_date(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.date_naive());
+    }
+    if let Ok(ndt) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Some(ndt.date());
+    }
+    DATE_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(value, fmt).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frontmatter_styles() {
+        let day = NaiveDate::from_ymd_opt(2021, 1, 1);
+        assert_eq!(parse_content_date("2021-01-01"), day);
+        assert_eq!(parse_content_date("01/01/2021"), day);
+        assert_eq!(parse_content_date("2021-01-01T08:00:00Z"), day);
+        assert_eq!(parse_content_date("not a date"), None);
+    }
+}
@@ -0,0 +1,381 @@
+//! A document as the site shows it: the frontmatter read into typed fields,
+//! the markdown body rendered to HTML once, and what pages need around it,
+//! the heading outline, the `![[embeds]]` and `[[wikilinks]]` and a short
+//! excerpt for cards.
+
+use crate::dates::parse_content_date;
+use chrono::NaiveDate;
+use pulldown_cmark::{html, CowStr, Event, Parser, Tag, TagEnd};
+use regex::Regex;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::LazyLock;
+
+// `![[name]]`, e.g. an image or clip published next to the document
+static EMBED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"!\[\[([^\]\n]+)\]\]").unwrap());
+// `[[target]]` or `[[target|label]]`, an embed without the `!`
+static WIKILINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(^|[^!])\[\[([^\]|\n]+)(?:\|([^\]\n]+))?\]\]").unwrap());
+
+/// Longest excerpt taken from the body, in characters
+const EXCERPT_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frontmatter {
+    pub title: Option<String>,
+    /// `None` when missing or in a format `parse_content_date` doesn't know
+    pub date: Option<NaiveDate>,
+    pub description: Option<String>,
+    /// A list or a single tag
+    pub tags: Vec<String>,
+    pub pinned: bool,
+    pub featured: bool,
+    /// Every other field as it was written
+    pub other: BTreeMap<String, Value>,
+}
+
+impl Frontmatter {
+    /// Fields from the YAML between the `---` lines. YAML that doesn't parse
+    /// leaves every field empty rather than failing the page.
+    pub fn parse(yaml: &str) -> Frontmatter {
+        let mut other: BTreeMap<String, Value> = serde_yaml::from_str::<Mapping>(yaml)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.as_str()?.to_string(), value)))
+            .collect();
+        let title = other.remove("title").as_ref().and_then(scalar);
+        let date = other
+            .remove("date")
+            .as_ref()
+            .and_then(scalar)
+            .and_then(|date| parse_content_date(&date));
+        let description = other.remove("description").as_ref().and_then(scalar);
+        let tags = match other.remove("tags") {
+            Some(Value::Sequence(tags)) => tags.iter().filter_map(scalar).collect(),
+            Some(tag) => scalar(&tag).into_iter().collect(),
+            None => Vec::new(),
+        };
+        // A section readme's `pinned` is a list of entries, not a flag
+        let pinned = match other.remove("pinned") {
+            Some(pinned @ Value::Sequence(_)) => {
+                other.insert("pinned".to_string(), pinned);
+                false
+            }
+            pinned => pinned.as_ref().is_some_and(flag),
+        };
+        let featured = other.remove("featured").as_ref().is_some_and(flag);
+
+        Frontmatter {
+            title,
+            date,
+            description,
+            tags,
+            pinned,
+            featured,
+            other,
+        }
+    }
+}
+
+/// A YAML scalar as text, `None` for lists, maps and empty values
+fn scalar(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// `true`, or `yes` as the helper also accepts
+fn flag(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        value => scalar(value).is_some_and(|s| matches!(s.to_lowercase().as_str(), "true" | "yes")),
+    }
+}
+
+/// A heading in the body, with the `id` it was given in the HTML
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiLink {
+    pub target: String,
+    /// The text after `|`, else the target
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub frontmatter: Frontmatter,
+    /// The markdown after the frontmatter
+    pub body: String,
+    /// `body` rendered, with embeds left as written. See `render` to
+    /// swap them for media.
+    pub html: String,
+    pub outline: Vec<Heading>,
+    /// Names inside `![[...]]`, in order
+    pub embeds: Vec<String>,
+    pub links: Vec<WikiLink>,
+    /// The frontmatter's `description`, else the start of the first
+    /// paragraph as plain text
+    pub excerpt: String,
+}
+
+impl Document {
+    pub fn parse(text: &str) -> Document {
+        let (yaml, body) = split_frontmatter(text);
+        let frontmatter = yaml.map(Frontmatter::parse).unwrap_or_default();
+
+        let embeds = EMBED
+            .captures_iter(body)
+            .map(|cap| cap[1].trim().to_string())
+            .collect();
+        let links = WIKILINK
+            .captures_iter(body)
+            .map(|cap| {
+                let target = cap[2].trim().to_string();
+                let label = cap
+                    .get(3)
+                    .map_or_else(|| target.clone(), |label| label.as_str().trim().to_string());
+                WikiLink { target, label }
+            })
+            .collect();
+
+        let mut events: Vec<Event> = Parser::new(body).collect();
+        let outline = outline(&mut events);
+        let excerpt = match &frontmatter.description {
+            Some(description) => description.clone(),
+            None => excerpt(&events),
+        };
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, events.into_iter());
+
+        Document {
+            frontmatter,
+            body: body.to_string(),
+            html: html_output,
+            outline,
+            embeds,
+            links,
+            excerpt,
+        }
+    }
+
+    /// The body as HTML with each `![[name]]` swapped for `embed(name)`,
+    /// those it gives `None` for left as written. The swap happens before
+    /// the markdown is rendered, so the embed's HTML should be one line.
+    pub fn render(&self, embed: impl Fn(&str) -> Option<String>) -> String {
+        let body = EMBED.replace_all(&self.body, |cap: &regex::Captures| {
+            embed(cap[1].trim()).unwrap_or_else(|| cap[0].to_string())
+        });
+        let mut events: Vec<Event> = Parser::new(&body).collect();
+        outline(&mut events);
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, events.into_iter());
+        html_output
+    }
+}
+
+/// The YAML between a leading pair of `---` lines, if there is one, and the
+/// rest. Only whole lines count, so a `---` rule or `a---b` in the body is
+/// left alone.
+pub fn split_frontmatter(text: &str) -> (Option<&str>, &str) {
+    let text = text.trim_start_matches('\u{feff}');
+    let rest = match text.split_once('\n') {
+        Some((first, rest)) if first.trim_end() == "---" => rest,
+        _ => return (None, text),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            let body = &rest[offset + line.len()..];
+            return (Some(&rest[..offset]), body.trim_start_matches(['\r', '\n']));
+        }
+        offset += line.len();
+    }
+    // Never closed, so not frontmatter
+    (None, text)
+}
+
+/// Give every heading an `id` made from its text and list them
+fn outline(events: &mut [Event]) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for start in 0..events.len() {
+        let Event::Start(Tag::Heading { level, id, .. }) = &events[start] else {
+            continue;
+        };
+        let level = *level as u8;
+        let written_id = id.as_ref().map(|id| id.to_string());
+
+        let mut text = String::new();
+        for event in &events[start + 1..] {
+            match event {
+                Event::End(TagEnd::Heading(_)) => break,
+                Event::Text(t) | Event::Code(t) => text.push_str(t),
+                _ => {}
+            }
+        }
+
+        let id = written_id.unwrap_or_else(|| {
+            let slug = slugify(&text);
+            let count = seen.entry(slug.clone()).or_default();
+            *count += 1;
+            match *count {
+                1 => slug,
+                n => format!("{}-{}", slug, n),
+            }
+        });
+        if let Event::Start(Tag::Heading { id: slot, .. }) = &mut events[start] {
+            *slot = Some(CowStr::from(id.clone()));
+        }
+        headings.push(Heading {
+            level,
+            text: text.trim().to_string(),
+            id,
+        });
+    }
+    headings
+}
+
+/// Lowercase words joined by `-`, e.g. "Getting Started!" to
+/// "getting-started"
+fn slugify(text: &str) -> String {
+    let slug = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+/// The first paragraph as plain text, cut at a word near `EXCERPT_LENGTH`
+fn excerpt(events: &[Event]) -> String {
+    let mut text = String::new();
+    let mut in_paragraph = false;
+    for event in events {
+        match event {
+            Event::Start(Tag::Paragraph) => in_paragraph = true,
+            Event::End(TagEnd::Paragraph) if !text.trim().is_empty() => break,
+            Event::End(TagEnd::Paragraph) => in_paragraph = false,
+            Event::Text(t) | Event::Code(t) if in_paragraph => text.push_str(t),
+            Event::SoftBreak | Event::HardBreak if in_paragraph => text.push(' '),
+            _ => {}
+        }
+    }
+    // Embeds are markup, not prose, and links read as their label
+    let text = EMBED.replace_all(text.trim(), "");
+    let text = WIKILINK.replace_all(&text, |cap: &regex::Captures| {
+        let label = cap.get(3).unwrap_or_else(|| cap.get(2).unwrap());
+        format!("{}{}", &cap[1], label.as_str().trim())
+    });
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if text.chars().count() <= EXCERPT_LENGTH {
+        return text;
+    }
+    let cut: String = text.chars().take(EXCERPT_LENGTH).collect();
+    let cut = cut
+        .rsplit_once(' ')
+        .map_or(cut.as_str(), |(words, _)| words);
+    format!("{}…", cut.trim_end_matches([',', '.', ';', ':']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POST: &str = "---
+title: Walking
+date: 2024-03-04
+tags: [walks, perth]
+featured: yes
+medium: Nikon Coolpix P900
+---
+An easy loop around the river, see [[Bridges|the bridges]].
+
+![[heron.jpg]]
+
+## Getting there
+
+---
+
+## Getting there
+Take the `train`.
+";
+
+    #[test]
+    fn reads_frontmatter_into_fields() {
+        let document = Document::parse(POST);
+        let frontmatter = &document.frontmatter;
+        assert_eq!(frontmatter.title.as_deref(), Some("Walking"));
+        assert_eq!(frontmatter.date, NaiveDate::from_ymd_opt(2024, 3, 4));
+        assert_eq!(frontmatter.tags, vec!["walks", "perth"]);
+        assert!(frontmatter.featured && !frontmatter.pinned);
+        assert_eq!(
+            frontmatter.other.get("medium"),
+            Some(&Value::String("Nikon Coolpix P900".to_string()))
+        );
+
+        let readme = Frontmatter::parse("pinned: [a.md, b.md]\ntags: solo");
+        assert!(!readme.pinned);
+        assert!(readme.other.contains_key("pinned"));
+        assert_eq!(readme.tags, vec!["solo"]);
+        assert_eq!(
+            Frontmatter::parse("title: [unclosed"),
+            Frontmatter::default()
+        );
+    }
+
+    #[test]
+    fn keeps_rules_in_the_body() {
+        let (yaml, body) = split_frontmatter(POST);
+        assert!(yaml.unwrap().contains("title: Walking"));
+        assert!(body.starts_with("An easy loop"));
+        assert!(body.contains("\n---\n"));
+        assert!(Document::parse(POST).html.contains("<hr />"));
+
+        let (yaml, body) = split_frontmatter("no frontmatter --- here");
+        assert_eq!((yaml, body), (None, "no frontmatter --- here"));
+        assert_eq!(split_frontmatter("---\nnever closed").0, None);
+    }
+
+    #[test]
+    fn finds_headings_embeds_and_links() {
+        let document = Document::parse(POST);
+        let ids: Vec<&str> = document.outline.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["getting-there", "getting-there-2"]);
+        assert_eq!(document.outline[0].level, 2);
+        assert!(document.html.contains("<h2 id=\"getting-there-2\">"));
+
+        assert_eq!(document.embeds, vec!["heron.jpg"]);
+        assert_eq!(
+            document.links,
+            vec![WikiLink {
+                target: "Bridges".to_string(),
+                label: "the bridges".to_string()
+            }]
+        );
+        assert_eq!(
+            document.excerpt,
+            "An easy loop around the river, see the bridges."
+        );
+
+        let html = document.render(|name| Some(format!("<img src=\"/{}\" />", name)));
+        assert!(html.contains("<img src=\"/heron.jpg\" />"));
+        assert!(!html.contains("![["));
+    }
+}
@@ -1,4 +1,6 @@
 use std::fmt;
+use std::time::Duration;
+
 /// Content service specific errors
 #[derive(Debug)]
 pub enum ContentServiceError {
@@ -6,14 +8,25 @@ pub enum ContentServiceError {
     RequestError(gloo_net::Error),
     /// JSON parsing failed
     ParseError(serde_json::Error),
-    /// Resource not found (404)
-    NotFound,
-    /// Rate limit exceeded
-    RateLimited,
+    /// Resource not found (404 or 410)
+    NotFound { url: String },
+    /// Rate limit exceeded (429), still after any retries. `retry_after` is
+    /// the server's `Retry-After` when it sent one.
+    RateLimited {
+        url: String,
+        retry_after: Option<Duration>,
+    },
+    /// Any other status outside 2xx, still after any retries
+    Http { url: String, status: u16 },
     /// Invalid input provided
     InvalidInput(String),
     /// Network error
     NetworkError(String),
+    /// The site index is in a format this client can't read or migrate.
+    /// `found` is `None` when the shape isn't recognised at all.
+    UnsupportedSchema { found: Option<u32>, supported: u32 },
+    /// Given up on through a `CancelToken`, e.g. after navigating away
+    Cancelled,
 }
 
 impl fmt::Display for ContentServiceError {
@@ -21,16 +34,109 @@ impl fmt::Display for ContentServiceError {
         match self {
             ContentServiceError::RequestError(err) => write!(f, "Request error: {}", err),
             ContentServiceError::ParseError(err) => write!(f, "Parse error: {}", err),
-            ContentServiceError::NotFound => write!(f, "Resource not found"),
-            ContentServiceError::RateLimited => write!(f, "Rate limit exceeded"),
+            ContentServiceError::NotFound { url } => write!(f, "Not found: {}", url),
+            ContentServiceError::RateLimited {
+                url,
+                retry_after: Some(wait),
+            } => write!(
+                f,
+                "Rate limit exceeded for {}, retry after {}s",
+                url,
+                wait.as_secs()
+            ),
+            ContentServiceError::RateLimited { url, .. } => {
+                write!(f, "Rate limit exceeded for {}", url)
+            }
+            ContentServiceError::Http { url, status } => {
+                write!(f, "HTTP error {} from {}", status, url)
+            }
             ContentServiceError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
             ContentServiceError::NetworkError(msg) => write!(f, "Network error: {}", msg),
+            ContentServiceError::UnsupportedSchema {
+                found: Some(version),
+                supported,
+            } => write!(
+                f,
+                "Site index schema version {} is newer than this client supports ({})",
+                version, supported
+            ),
+            ContentServiceError::UnsupportedSchema {
+                found: None,
+                supported,
+            } => write!(
+                f,
+                "Site index format not recognised (this client reads versions up to {})",
+                supported
+            ),
+            ContentServiceError::Cancelled => write!(f, "Request cancelled"),
         }
     }
 }
 
 impl std::error::Error for ContentServiceError {}
 
+impl ContentServiceError {
+    /// The error for a response with a status outside 2xx
+    pub fn from_status(url: &str, status: u16, retry_after: Option<Duration>) -> Self {
+        let url = url.to_string();
+        match status {
+            404 | 410 => ContentServiceError::NotFound { url },
+            429 => ContentServiceError::RateLimited { url, retry_after },
+            status => ContentServiceError::Http { url, status },
+        }
+    }
+
+    /// Whether trying the same GET again could succeed: a dropped
+    /// connection, a rate limit or a server that's briefly unwell
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ContentServiceError::RequestError(_) | ContentServiceError::NetworkError(_) => true,
+            ContentServiceError::RateLimited { .. } => true,
+            ContentServiceError::Http { status, .. } => matches!(status, 408 | 500 | 502..=504),
+            _ => false,
+        }
+    }
+
+    /// A copy for everyone waiting on the same request. The wrapped errors
+    /// can't be cloned, their copies keep the message.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            ContentServiceError::RequestError(err) => {
+                ContentServiceError::RequestError(gloo_net::Error::GlooError(err.to_string()))
+            }
+            ContentServiceError::ParseError(err) => {
+                ContentServiceError::ParseError(serde::de::Error::custom(err))
+            }
+            ContentServiceError::NotFound { url } => {
+                ContentServiceError::NotFound { url: url.clone() }
+            }
+            ContentServiceError::RateLimited { url, retry_after } => {
+                ContentServiceError::RateLimited {
+                    url: url.clone(),
+                    retry_after: *retry_after,
+                }
+            }
+            ContentServiceError::Http { url, status } => ContentServiceError::Http {
+                url: url.clone(),
+                status: *status,
+            },
+            ContentServiceError::InvalidInput(msg) => {
+                ContentServiceError::InvalidInput(msg.clone())
+            }
+            ContentServiceError::NetworkError(msg) => {
+                ContentServiceError::NetworkError(msg.clone())
+            }
+            ContentServiceError::UnsupportedSchema { found, supported } => {
+                ContentServiceError::UnsupportedSchema {
+                    found: *found,
+                    supported: *supported,
+                }
+            }
+            ContentServiceError::Cancelled => ContentServiceError::Cancelled,
+        }
+    }
+}
+
 impl From<gloo_net::Error> for ContentServiceError {
     fn from(error: gloo_net::Error) -> Self {
         ContentServiceError::RequestError(error)
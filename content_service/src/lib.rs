@@ -2,13 +2,41 @@
 //!
 //! This library provides a simple interface to interact with the GitHub API.
 
+pub mod archive;
+pub mod cache;
+pub mod cancel;
 pub mod client;
+pub mod dates;
+pub mod document;
 pub mod error;
 pub mod models;
+pub mod paging;
+pub mod query;
+pub mod retry;
+pub mod schema;
+pub mod section;
+pub mod transport;
 pub mod utils;
 
 // Re-export commonly used types for convenience
+pub use archive::build_archive;
+#[cfg(target_arch = "wasm32")]
+pub use cache::LocalStorageStore;
+pub use cache::{Cache, CacheStore, CachedResponse, MemoryStore};
+pub use cancel::{spawn_cancellable, CancelToken};
 pub use client::{ContentServiceClient, ContentServiceClientCallback};
+pub use document::{Document, Frontmatter, Heading, WikiLink};
 pub use error::ContentServiceError;
-pub use models::{Img, JsonEntry};
+pub use models::{
+    Animation, ArchiveMonth, ArchiveSection, ArchiveYear, Audio, Change, Img, JsonEntry, Similar,
+    SiteIndex, SiteInfo, Video,
+};
+pub use paging::ContentPage;
+pub use query::{ContentQuery, Predicate};
+pub use retry::RetryPolicy;
+pub use schema::{parse_site_index, SCHEMA_VERSION};
+pub use section::{Layout, Section};
+pub use transport::{FetchTransport, Transport};
+#[cfg(not(target_arch = "wasm32"))]
+pub use transport::{FsTransport, HttpTransport};
 pub use utils::console_log;
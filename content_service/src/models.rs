@@ -1,7 +1,78 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+// Sequences in frontmatter are stored as their debug form, e.g.
+// `Sequence [String("Bird"), String("Perth")]`
+static SEQUENCE_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"String\("([^"]*)"\)"#).unwrap());
+
+/// Top-level shape of `directory_structure.json` from schema version 2 on.
+/// Older files are a bare array of entries, see `schema::parse_site_index`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SiteIndex {
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>, // URL of the published JSON Schema
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<String>, // RFC 3339
+    #[serde(default)]
+    pub site: SiteInfo,
+    pub entries: Vec<JsonEntry>,
+    /// Dated documents of each section by year and month, see `archive`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archive: Vec<ArchiveSection>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SiteInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// One section's archive, newest year first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveSection {
+    pub section: String, // e.g. "/blog"
+    pub count: u32,
+    pub years: Vec<ArchiveYear>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveYear {
+    pub year: i32,
+    pub count: u32,
+    /// Newest month first
+    pub months: Vec<ArchiveMonth>,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMonth {
+    pub month: u32, // 1 to 12
+    pub count: u32,
+    /// Entry paths, newest first
+    pub entries: Vec<String>,
+}
+
+/// Values of `JsonEntry::entry_type`
+pub mod entry_types {
+    /// Markdown or plain text, rendered as a page
+    pub const DOCUMENT: &str = "document";
+    pub const AUDIO: &str = "audio";
+    pub const VIDEO: &str = "video";
+    pub const PDF: &str = "pdf";
+    /// Anything else published alongside the content, e.g. a zip
+    pub const OTHER: &str = "other";
+    /// A folder, with `children` counting what is directly inside it
+    pub const DIRECTORY: &str = "directory";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
 pub struct JsonEntry {
     pub path: String,
     #[serde(rename = "type")]
@@ -12,8 +83,67 @@ pub struct JsonEntry {
     pub date: Option<String>, // e.g., "2025-09-09T08:00:00Z"
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub images: Vec<Img>,
-    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
-    pub metadata: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audio: Vec<Audio>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub videos: Vec<Video>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>, // sorted so the index serialises stably
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub similar: Vec<Similar>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub children: BTreeMap<String, u32>, // direct children by entry type, directories only
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pinned: bool, // listed before everything else in its section
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub featured: bool, // shown in the home page's hero strip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>, // YYYY-MM-DD of the first commit with the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>, // YYYY-MM-DD of the latest commit with the file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<Change>, // newest first, only when the helper is asked for them
+}
+
+/// A commit that touched an entry's file
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub date: String, // YYYY-MM-DD
+    pub summary: String,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl JsonEntry {
+    pub fn is_directory(&self) -> bool {
+        self.entry_type == entry_types::DIRECTORY
+    }
+
+    /// Tags from the `tags` frontmatter field
+    pub fn tags(&self) -> Vec<String> {
+        self.list("tags")
+    }
+
+    /// Items of a frontmatter sequence, or a plain value as a single item
+    pub fn list(&self, key: &str) -> Vec<String> {
+        match self.metadata.get(key) {
+            Some(raw) if raw.starts_with("Sequence") => SEQUENCE_ITEM
+                .captures_iter(raw)
+                .map(|cap| cap[1].to_string())
+                .collect(),
+            Some(raw) if !raw.trim().is_empty() => vec![raw.trim().to_string()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Another entry recommended alongside this one, best match first
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Similar {
+    pub path: String,
+    pub score: u8, // 0-100
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
@@ -22,4 +152,53 @@ pub struct Img {
     pub aspect_ratio: String,
     pub name: String,
     pub path: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub palette: Vec<String>, // "#rrggbb", dominant colour first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>, // data URI, decode `blurhash` when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phash: Option<String>, // 64-bit perceptual hash as hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation: Option<Animation>, // set for animated gif, webp and png
+}
+
+/// Frames and renditions of an animated image
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    pub frames: u32,
+    pub duration_ms: u64, // one loop
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poster: Option<String>, // first frame, same path scheme as `path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcoded: Option<String>, // mp4 or animated webp, same path scheme
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Audio {
+    pub name: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peaks: Vec<u8>, // waveform bars scaled 0-255
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Video {
+    pub name: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poster: Option<String>, // extracted frame, same path scheme as `path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blurhash: Option<String>, // of the poster frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>, // data URI of the blurred poster
 }
@@ -0,0 +1,101 @@
+//! Listings a page at a time. A section's `page_size` is the limit, the
+//! site keeps the page in the route, e.g. `#/pictures?page=2`.
+
+use crate::models::JsonEntry;
+
+/// A run of entries from a longer listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentPage {
+    pub entries: Vec<JsonEntry>,
+    /// Position of the first entry in the whole listing
+    pub offset: usize,
+    /// Entries per page, everything on one page when `None`
+    pub limit: Option<usize>,
+    /// Length of the whole listing
+    pub total: usize,
+}
+
+impl ContentPage {
+    /// Up to `limit` of `entries` starting at `offset`
+    pub fn new(entries: Vec<JsonEntry>, offset: usize, limit: Option<usize>) -> ContentPage {
+        let total = entries.len();
+        let entries = entries
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+        ContentPage {
+            entries,
+            offset,
+            limit,
+            total,
+        }
+    }
+
+    /// Where the following page starts, `None` on the last page
+    pub fn next_offset(&self) -> Option<usize> {
+        let next = self.offset + self.entries.len();
+        (self.limit.is_some() && next < self.total).then_some(next)
+    }
+
+    /// 1-based number of this page
+    pub fn page(&self) -> usize {
+        match self.limit {
+            Some(limit) => self.offset / limit + 1,
+            None => 1,
+        }
+    }
+
+    /// How many pages the listing takes, at least one
+    pub fn pages(&self) -> usize {
+        match self.limit {
+            Some(limit) => self.total.div_ceil(limit).max(1),
+            None => 1,
+        }
+    }
+}
+
+/// Offset of the 1-based `page`, page 0 is taken as the first
+pub fn page_offset(page: usize, limit: Option<usize>) -> usize {
+    limit.map_or(0, |limit| page.saturating_sub(1) * limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::entry_types;
+
+    fn entries(count: usize) -> Vec<JsonEntry> {
+        (0..count)
+            .map(|i| JsonEntry {
+                path: format!("/blog/{}.md", i),
+                entry_type: entry_types::DOCUMENT.to_string(),
+                name: format!("{}.md", i),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn slices_pages_and_counts_them() {
+        let limit = Some(4);
+        let second = ContentPage::new(entries(10), page_offset(2, limit), limit);
+        assert_eq!(second.entries[0].path, "/blog/4.md");
+        assert_eq!(second.entries.len(), 4);
+        assert_eq!((second.page(), second.pages()), (2, 3));
+        assert_eq!(second.next_offset(), Some(8));
+
+        let last = ContentPage::new(entries(10), page_offset(3, limit), limit);
+        assert_eq!(last.entries.len(), 2);
+        assert_eq!(last.next_offset(), None);
+
+        let past_the_end = ContentPage::new(entries(10), page_offset(9, limit), limit);
+        assert!(past_the_end.entries.is_empty());
+        assert_eq!(past_the_end.next_offset(), None);
+
+        let everything = ContentPage::new(entries(10), page_offset(2, None), None);
+        assert_eq!(everything.entries.len(), 10);
+        assert_eq!((everything.page(), everything.pages()), (1, 1));
+        assert_eq!(page_offset(0, limit), 0);
+    }
+}
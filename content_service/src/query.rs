@@ -0,0 +1,365 @@
+//! Picking entries out of the index. A `ContentQuery` is built up a filter
+//! at a time and run against the entries the client has loaded:
+//!
+//! ```ignore
+//! let photos = ContentQuery::new("/pictures")
+//!     .entry_type(entry_types::DOCUMENT)
+//!     .all_tags(["bird"])
+//!     .field("medium", Predicate::Equals("Nikon Coolpix P900".to_string()))
+//!     .sort_by("date", true)
+//!     .limit(24);
+//! let page = client.query(&photos).await?;
+//! ```
+
+use crate::dates::parse_content_date;
+use crate::models::JsonEntry;
+use crate::paging::ContentPage;
+use crate::section::Section;
+use chrono::NaiveDate;
+use std::cmp::Ordering;
+
+/// A test on one frontmatter field. Sequence fields match when any of
+/// their items does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    /// The field is set, whatever its value
+    Exists,
+    Equals(String),
+    NotEquals(String),
+    /// Substring, ignoring case
+    Contains(String),
+}
+
+impl Predicate {
+    fn matches(&self, entry: &JsonEntry, key: &str) -> bool {
+        let values = entry.list(key);
+        match self {
+            Predicate::Exists => entry.metadata.contains_key(key),
+            Predicate::Equals(wanted) => values.iter().any(|value| value == wanted),
+            Predicate::NotEquals(unwanted) => values.iter().all(|value| value != unwanted),
+            Predicate::Contains(part) => {
+                let part = part.to_lowercase();
+                values
+                    .iter()
+                    .any(|value| value.to_lowercase().contains(&part))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentQuery {
+    path: String,
+    entry_type: Option<String>,
+    any_tags: Vec<String>,
+    all_tags: Vec<String>,
+    no_tags: Vec<String>,
+    excluded: Vec<String>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    fields: Vec<(String, Predicate)>,
+    order: Option<Section>,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl ContentQuery {
+    /// Every entry whose path starts with `path`, pinned then newest first
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Only entries of this `entry_types` value
+    pub fn entry_type(mut self, entry_type: impl Into<String>) -> Self {
+        self.entry_type = Some(entry_type.into());
+        self
+    }
+
+    /// Entries with at least one of `tags`. No tags leaves the filter off.
+    pub fn any_tags<S: Into<String>>(mut self, tags: impl IntoIterator<Item = S>) -> Self {
+        self.any_tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Entries with every one of `tags`
+    pub fn all_tags<S: Into<String>>(mut self, tags: impl IntoIterator<Item = S>) -> Self {
+        self.all_tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Entries with none of `tags`
+    pub fn without_tags<S: Into<String>>(mut self, tags: impl IntoIterator<Item = S>) -> Self {
+        self.no_tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Leave out the entry at `path`, ignoring case, e.g. a section's
+    /// readme shown above its listing
+    pub fn exclude(mut self, path: impl Into<String>) -> Self {
+        self.excluded.push(path.into());
+        self
+    }
+
+    /// Entries dated on or after `day`. Undated entries are left out.
+    pub fn since(mut self, day: NaiveDate) -> Self {
+        self.since = Some(day);
+        self
+    }
+
+    /// Entries dated on or before `day`. Undated entries are left out.
+    pub fn until(mut self, day: NaiveDate) -> Self {
+        self.until = Some(day);
+        self
+    }
+
+    /// Entries whose frontmatter `key` passes `predicate`
+    pub fn field(mut self, key: impl Into<String>, predicate: Predicate) -> Self {
+        self.fields.push((key.into(), predicate));
+        self
+    }
+
+    /// Sort on `date`, `name`, `path` or a frontmatter field, the way a
+    /// section's `sort` does. Entries pinned in their frontmatter still
+    /// come first.
+    pub fn sort_by(self, key: impl Into<String>, descending: bool) -> Self {
+        self.arranged_like(&Section {
+            sort: key.into(),
+            descending,
+            ..Section::default()
+        })
+    }
+
+    /// Sort and pin the way `section` lists its entries
+    pub fn arranged_like(mut self, section: &Section) -> Self {
+        self.order = Some(section.clone());
+        self
+    }
+
+    /// Skip the first `offset` matches
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// At most `limit` matches. `None` for all of them.
+    pub fn limit(mut self, limit: impl Into<Option<usize>>) -> Self {
+        self.limit = limit.into();
+        self
+    }
+
+    pub fn matches(&self, entry: &JsonEntry) -> bool {
+        if !entry.path.starts_with(&self.path)
+            || self
+                .excluded
+                .iter()
+                .any(|path| path.eq_ignore_ascii_case(&entry.path))
+        {
+            return false;
+        }
+        if self
+            .entry_type
+            .as_ref()
+            .is_some_and(|entry_type| entry.entry_type != *entry_type)
+        {
+            return false;
+        }
+
+        if !(self.any_tags.is_empty() && self.all_tags.is_empty() && self.no_tags.is_empty()) {
+            let tags = entry.tags();
+            let has = |tag: &String| tags.contains(tag);
+            if !self.any_tags.is_empty() && !self.any_tags.iter().any(has) {
+                return false;
+            }
+            if !self.all_tags.iter().all(has) || self.no_tags.iter().any(has) {
+                return false;
+            }
+        }
+
+        if self.since.is_some() || self.until.is_some() {
+            let Some(day) = entry.date.as_deref().and_then(parse_content_date) else {
+                return false;
+            };
+            if self.since.is_some_and(|since| day < since)
+                || self.until.is_some_and(|until| day > until)
+            {
+                return false;
+            }
+        }
+
+        self.fields
+            .iter()
+            .all(|(key, predicate)| predicate.matches(entry, key))
+    }
+
+    /// The page of `entries` this query asks for, `total` counting every
+    /// match
+    pub fn run(&self, entries: &[JsonEntry]) -> ContentPage {
+        let mut matched: Vec<JsonEntry> = entries
+            .iter()
+            .filter(|entry| self.matches(entry))
+            .cloned()
+            .collect();
+        match &self.order {
+            Some(section) => section.arrange(&mut matched),
+            None => sort_pinned_then_newest(&mut matched),
+        }
+        ContentPage::new(matched, self.offset, self.limit)
+    }
+}
+
+/// The order without a `sort_by`: pinned entries first, then newest first,
+/// with undated entries last by name
+pub(crate) fn sort_pinned_then_newest(entries: &mut [JsonEntry]) {
+    entries.sort_by(|a, b| {
+        // Pinned entries first, each group newest first
+        b.pinned.cmp(&a.pinned).then_with(|| {
+            match (&a.date, &b.date) {
+                (Some(date_a), Some(date_b)) => {
+                    // Sort in descending order (newest first)
+                    date_b.cmp(date_a)
+                }
+                (Some(_), None) => {
+                    // Items with dates come before items without dates
+                    Ordering::Less
+                }
+                (None, Some(_)) => {
+                    // Items without dates come after items with dates
+                    Ordering::Greater
+                }
+                (None, None) => {
+                    // If both have no date, sort by name
+                    a.name.cmp(&b.name)
+                }
+            }
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::entry_types;
+
+    fn entry(path: &str, date: Option<&str>, metadata: &[(&str, &str)]) -> JsonEntry {
+        JsonEntry {
+            path: path.to_string(),
+            entry_type: entry_types::DOCUMENT.to_string(),
+            name: path.rsplit('/').next().unwrap().to_string(),
+            date: date.map(str::to_string),
+            metadata: metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn paths(page: &ContentPage) -> Vec<&str> {
+        page.entries.iter().map(|e| e.path.as_str()).collect()
+    }
+
+    #[test]
+    fn filters_on_tags_dates_and_fields() {
+        let entries = vec![
+            entry(
+                "/pictures/heron.md",
+                Some("2024-05-01"),
+                &[
+                    ("tags", r#"Sequence [String("bird"), String("perth")]"#),
+                    ("medium", "Nikon Coolpix P900"),
+                ],
+            ),
+            entry(
+                "/pictures/gull.md",
+                Some("2023-02-01"),
+                &[("tags", r#"Sequence [String("bird")]"#), ("medium", "Film")],
+            ),
+            entry(
+                "/pictures/city.md",
+                None,
+                &[("tags", r#"Sequence [String("perth")]"#)],
+            ),
+            entry("/blog/post.md", Some("2024-06-01"), &[]),
+        ];
+        let run = |query: ContentQuery| {
+            let page = query.run(&entries);
+            paths(&page)
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+        };
+        let pictures = || ContentQuery::new("/pictures");
+
+        assert_eq!(
+            run(pictures()),
+            vec![
+                "/pictures/heron.md",
+                "/pictures/gull.md",
+                "/pictures/city.md"
+            ]
+        );
+        assert_eq!(
+            run(pictures().all_tags(["bird", "perth"])),
+            vec!["/pictures/heron.md"]
+        );
+        assert_eq!(
+            run(pictures().any_tags(["perth", "none"])),
+            vec!["/pictures/heron.md", "/pictures/city.md"]
+        );
+        assert_eq!(
+            run(pictures().without_tags(["perth"])),
+            vec!["/pictures/gull.md"]
+        );
+        assert_eq!(
+            run(pictures().exclude("/Pictures/Heron.md")),
+            vec!["/pictures/gull.md", "/pictures/city.md"]
+        );
+
+        let day = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert_eq!(
+            run(ContentQuery::new("/").since(day("2024-01-01"))),
+            vec!["/blog/post.md", "/pictures/heron.md"]
+        );
+        assert_eq!(
+            run(pictures().until(day("2023-12-31"))),
+            vec!["/pictures/gull.md"]
+        );
+
+        let medium = |predicate| pictures().field("medium", predicate);
+        assert_eq!(
+            run(medium(Predicate::Equals("Nikon Coolpix P900".to_string()))),
+            vec!["/pictures/heron.md"]
+        );
+        assert_eq!(
+            run(medium(Predicate::Contains("film".to_string()))),
+            vec!["/pictures/gull.md"]
+        );
+        assert_eq!(
+            run(medium(Predicate::NotEquals("Film".to_string()))),
+            vec!["/pictures/heron.md", "/pictures/city.md"]
+        );
+        assert_eq!(run(medium(Predicate::Exists)).len(), 2);
+    }
+
+    #[test]
+    fn sorts_then_pages() {
+        let entries = vec![
+            entry("/blog/b.md", Some("2024-01-01"), &[]),
+            entry("/blog/c.md", Some("2022-01-01"), &[]),
+            entry("/blog/a.md", Some("2023-01-01"), &[]),
+        ];
+
+        let by_name = ContentQuery::new("/blog").sort_by("name", false);
+        assert_eq!(
+            paths(&by_name.run(&entries)),
+            vec!["/blog/a.md", "/blog/b.md", "/blog/c.md"]
+        );
+
+        let page = ContentQuery::new("/blog").offset(1).limit(1).run(&entries);
+        assert_eq!(paths(&page), vec!["/blog/a.md"]);
+        assert_eq!((page.total, page.page(), page.pages()), (3, 2, 3));
+    }
+}
@@ -0,0 +1,143 @@
+//! When the client tries a GET again. Only transient failures are retried:
+//! dropped connections, rate limits and 5xx answers. Waits grow
+//! exponentially with jitter so a burst of clients doesn't come back in step,
+//! and a server's `Retry-After` wins over the computed wait.
+
+use crate::error::ContentServiceError;
+use chrono::{DateTime, Utc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::hash_map::RandomState;
+#[cfg(not(target_arch = "wasm32"))]
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Further attempts after the first, 0 to never retry
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each one after
+    pub base_delay: Duration,
+    /// Longest wait between attempts. A `Retry-After` beyond it isn't
+    /// waited out, the rate limit is returned instead.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Fail on the first error
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// How long to wait before retrying after `error` on `attempt`, counted
+    /// from 0. `None` when the error is final or the retries are used up.
+    pub fn delay(&self, error: &ContentServiceError, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_retries || !error.is_transient() {
+            return None;
+        }
+        match error {
+            ContentServiceError::RateLimited {
+                retry_after: Some(wait),
+                ..
+            } => (*wait <= self.max_delay).then_some(*wait),
+            _ => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// `base_delay * 2^attempt` up to `max_delay`, of which a random half
+    /// is waited
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        ceiling / 2 + ceiling.mul_f64(jitter() / 2.0)
+    }
+}
+
+/// A `Retry-After` value, either seconds or an HTTP date
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    // A date already past means go ahead now
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+/// Uniform in [0, 1). `RandomState` has no entropy to seed it in the browser,
+/// every tab would wait alike, so `Math.random` is used there.
+#[cfg(target_arch = "wasm32")]
+fn jitter() -> f64 {
+    js_sys::Math::random()
+}
+
+/// Uniform-ish in [0, 1). Every `RandomState` is seeded differently, which
+/// is plenty for spreading retries.
+#[cfg(not(target_arch = "wasm32"))]
+fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_transient_errors_with_growing_waits() {
+        let policy = RetryPolicy::default();
+        let unwell = ContentServiceError::from_status("/x", 503, None);
+        let missing = ContentServiceError::from_status("/x", 404, None);
+
+        for attempt in 0..3 {
+            let wait = policy.delay(&unwell, attempt).unwrap();
+            let ceiling = policy.base_delay * 2u32.pow(attempt);
+            assert!(wait >= ceiling / 2 && wait <= ceiling, "{:?}", wait);
+        }
+        assert_eq!(policy.delay(&unwell, 3), None);
+        assert_eq!(policy.delay(&missing, 0), None);
+        assert!(policy.backoff(30) <= policy.max_delay);
+
+        let limited = |seconds| ContentServiceError::RateLimited {
+            url: "/x".to_string(),
+            retry_after: Some(Duration::from_secs(seconds)),
+        };
+        assert_eq!(policy.delay(&limited(2), 0), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(&limited(60), 0), None);
+        assert_eq!(RetryPolicy::none().delay(&unwell, 0), None);
+    }
+
+    #[test]
+    fn reads_retry_after_seconds_and_dates() {
+        let now = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Mon, 01 Jan 2024 00:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 31 Dec 2023 23:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
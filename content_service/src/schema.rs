@@ -0,0 +1,303 @@
+//! Versions of `directory_structure.json` and migration to the current one.
+//!
+//! - 0: a bare array of `{path, type, size}` with `"./web/content/..."` paths
+//!   and `"directory"` entries, as in the old root `directory_structure.json`
+//! - 1: a bare array of `JsonEntry`
+//! - 2: the `SiteIndex` envelope
+//! - 3: entries typed as document, audio, video, pdf or other rather than
+//!   `"file"`, plus `"directory"` entries with child counts
+
+use crate::error::ContentServiceError;
+use crate::models::{entry_types, JsonEntry, SiteIndex, SiteInfo};
+use serde_json::Value;
+
+/// Schema version written by the helper and read by this client
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Where the JSON Schema for the current version is published, relative to
+/// the site root
+pub const SCHEMA_PATH: &str = "/schema/directory_structure.schema.json";
+
+/// Parse a site index of any known version, migrating older ones
+pub fn parse_site_index(text: &str) -> Result<SiteIndex, ContentServiceError> {
+    let value: Value = serde_json::from_str(text)?;
+    match &value {
+        Value::Object(object) => match object.get("schema_version").and_then(Value::as_u64) {
+            Some(version) if version == SCHEMA_VERSION as u64 => Ok(serde_json::from_value(value)?),
+            Some(2) => {
+                let mut index: SiteIndex = serde_json::from_value(value)?;
+                index.schema_version = SCHEMA_VERSION;
+                index.entries.iter_mut().for_each(migrate_file_type);
+                Ok(index)
+            }
+            Some(version) => Err(ContentServiceError::UnsupportedSchema {
+                found: Some(version.min(u32::MAX as u64) as u32),
+                supported: SCHEMA_VERSION,
+            }),
+            None => Err(ContentServiceError::UnsupportedSchema {
+                found: None,
+                supported: SCHEMA_VERSION,
+            }),
+        },
+        Value::Array(items) if items.iter().all(is_v0_entry) => Ok(envelope(
+            items.iter().filter_map(migrate_v0_entry).collect(),
+        )),
+        Value::Array(_) => {
+            let mut entries: Vec<JsonEntry> = serde_json::from_value(value)?;
+            entries.iter_mut().for_each(migrate_file_type);
+            Ok(envelope(entries))
+        }
+        _ => Err(ContentServiceError::UnsupportedSchema {
+            found: None,
+            supported: SCHEMA_VERSION,
+        }),
+    }
+}
+
+fn envelope(entries: Vec<JsonEntry>) -> SiteIndex {
+    SiteIndex {
+        schema: None,
+        schema_version: SCHEMA_VERSION,
+        generated_at: None,
+        site: SiteInfo::default(),
+        entries,
+        archive: Vec::new(),
+    }
+}
+
+/// Before version 3 every entry was a `"file"`. Only documents were listed
+/// in practice, anything else was a stray file.
+fn migrate_file_type(entry: &mut JsonEntry) {
+    if entry.entry_type != "file" {
+        return;
+    }
+    let extension = entry
+        .path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase());
+    entry.entry_type = match extension.as_deref() {
+        Some("md" | "markdown" | "txt") => entry_types::DOCUMENT,
+        Some("pdf") => entry_types::PDF,
+        _ => entry_types::OTHER,
+    }
+    .to_string();
+}
+
+/// Version 0 entries carry nothing beyond path, type and size
+fn is_v0_entry(item: &Value) -> bool {
+    item.as_object()
+        .is_some_and(|object| object.contains_key("path") && !object.contains_key("name"))
+}
+
+/// Version 0 listed every file and directory, only markdown documents were
+/// pages. Paths were relative to where the helper ran, e.g.
+/// `./web/content/blog/post.md`, and become `/blog/post.md`.
+fn migrate_v0_entry(item: &Value) -> Option<JsonEntry> {
+    if item.get("type")?.as_str()? != "file" {
+        return None;
+    }
+    let raw_path = item.get("path")?.as_str()?;
+    if !raw_path.to_lowercase().ends_with(".md") {
+        return None;
+    }
+
+    let path = match raw_path.find("/content/") {
+        Some(index) => raw_path[index + "/content".len()..].to_string(),
+        None => format!(
+            "/{}",
+            raw_path.trim_start_matches("./").trim_start_matches('/')
+        ),
+    };
+    let name = path.rsplit('/').next().unwrap_or("").to_string();
+
+    Some(JsonEntry {
+        path,
+        entry_type: entry_types::DOCUMENT.to_string(),
+        size: item.get("size").and_then(Value::as_u64).unwrap_or(0),
+        name,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::build_archive;
+    use crate::models::{Animation, Audio, Change, Img, Similar, Video};
+    use std::collections::BTreeMap;
+
+    const PUBLISHED_SCHEMA: &str = include_str!("../../web/schema/directory_structure.schema.json");
+
+    /// Every key `value` serialises must be declared at the matching place in
+    /// the schema, so a new model field can't ship without a schema update
+    fn assert_declared(value: &Value, schema: &Value, root: &Value, at: &str) {
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => root
+                .pointer(reference.trim_start_matches('#'))
+                .unwrap_or_else(|| panic!("{} refers to missing {}", at, reference)),
+            None => schema,
+        };
+        let schema = match schema.get("oneOf").and_then(Value::as_array) {
+            Some(options) => options
+                .iter()
+                .find(|option| option.get("$ref").is_some())
+                .unwrap(),
+            None => schema,
+        };
+        match value {
+            Value::Object(object) if schema.get("properties").is_some() => {
+                for (key, child) in object {
+                    let property = schema["properties"]
+                        .get(key)
+                        .unwrap_or_else(|| panic!("{}.{} is not in the schema", at, key));
+                    assert_declared(child, property, root, &format!("{}.{}", at, key));
+                }
+            }
+            Value::Array(items) => {
+                if let Some(item_schema) = schema.get("items") {
+                    for item in items {
+                        assert_declared(item, item_schema, root, &format!("{}[]", at));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn published_schema_matches_models() {
+        let schema: Value = serde_json::from_str(PUBLISHED_SCHEMA).unwrap();
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+
+        let text = |s: &str| Some(s.to_string());
+        let entry = JsonEntry {
+            path: "/blog/post.md".to_string(),
+            entry_type: entry_types::DOCUMENT.to_string(),
+            size: 1,
+            name: "Post".to_string(),
+            date: text("2024-01-02"),
+            images: vec![Img {
+                blurhash: "L00000".to_string(),
+                aspect_ratio: "4/3".to_string(),
+                name: "a.gif".to_string(),
+                path: "/blog/a.gif".to_string(),
+                palette: vec!["#112233".to_string()],
+                placeholder: text("data:image/png;base64,"),
+                phash: text("00ff00ff00ff00ff"),
+                animation: Some(Animation {
+                    frames: 2,
+                    duration_ms: 100,
+                    poster: text("/blog/a.poster.png"),
+                    transcoded: text("/blog/a.anim.mp4"),
+                }),
+            }],
+            audio: vec![Audio {
+                name: "a.mp3".to_string(),
+                path: "/blog/a.mp3".to_string(),
+                duration_ms: Some(1),
+                peaks: vec![1],
+            }],
+            videos: vec![Video {
+                name: "a.mp4".to_string(),
+                path: "/blog/a.mp4".to_string(),
+                width: Some(1),
+                height: Some(1),
+                duration_ms: Some(1),
+                aspect_ratio: text("1/1"),
+                poster: text("/blog/a.poster.jpg"),
+                blurhash: text("L00000"),
+                placeholder: text("data:image/png;base64,"),
+            }],
+            metadata: BTreeMap::from([("name".to_string(), "Post".to_string())]),
+            similar: vec![Similar {
+                path: "/blog/other.md".to_string(),
+                score: 50,
+            }],
+            children: BTreeMap::new(),
+            pinned: true,
+            featured: true,
+            created: text("2024-01-02"),
+            updated: text("2024-03-04"),
+            changes: vec![Change {
+                date: "2024-03-04".to_string(),
+                summary: "Fix typo".to_string(),
+            }],
+        };
+        let directory = JsonEntry {
+            path: "/blog".to_string(),
+            entry_type: entry_types::DIRECTORY.to_string(),
+            size: 1,
+            name: "Blog".to_string(),
+            date: text("2024-01-02"),
+            children: BTreeMap::from([(entry_types::DOCUMENT.to_string(), 1)]),
+            ..Default::default()
+        };
+        let entries = vec![directory, entry];
+        let index = SiteIndex {
+            schema: text(SCHEMA_PATH),
+            schema_version: SCHEMA_VERSION,
+            generated_at: text("2024-01-02T00:00:00Z"),
+            site: SiteInfo {
+                name: text("oli.fm"),
+                base_url: text("https://oli.fm"),
+                description: text("Blog"),
+            },
+            archive: build_archive(&entries),
+            entries,
+        };
+
+        let value = serde_json::to_value(&index).unwrap();
+        assert_declared(&value, &schema, &schema, "index");
+    }
+
+    #[test]
+    fn migrates_bare_arrays() {
+        let v0 = r#"[
+            {"path":"./web/content","type":"directory","size":65},
+            {"path":"./web/content/blog/post.md","type":"file","size":171},
+            {"path":"./web/content/blog/assets/shot.jpg","type":"file","size":9}
+        ]"#;
+        let index = parse_site_index(v0).unwrap();
+        assert_eq!(index.schema_version, SCHEMA_VERSION);
+        assert_eq!(index.entries.len(), 1);
+        assert_eq!(index.entries[0].path, "/blog/post.md");
+        assert_eq!(index.entries[0].name, "post.md");
+        assert_eq!(index.entries[0].entry_type, entry_types::DOCUMENT);
+
+        let v1 = r#"[
+            {"path":"/readme.md","type":"file","size":3,"name":"Home"},
+            {"path":"/notes/.DS_Store","type":"file","size":6,"name":".DS_Store"}
+        ]"#;
+        let index = parse_site_index(v1).unwrap();
+        assert_eq!(index.entries[0].name, "Home");
+        assert_eq!(index.entries[0].entry_type, entry_types::DOCUMENT);
+        assert_eq!(index.entries[1].entry_type, entry_types::OTHER);
+
+        let v2 = r#"{"schema_version":2,"entries":[{"path":"/cv.pdf","type":"file","size":3,"name":"cv.pdf"}]}"#;
+        let index = parse_site_index(v2).unwrap();
+        assert_eq!(index.schema_version, SCHEMA_VERSION);
+        assert_eq!(index.entries[0].entry_type, entry_types::PDF);
+
+        let roundtrip = serde_json::to_string(&index).unwrap();
+        assert_eq!(parse_site_index(&roundtrip).unwrap(), index);
+    }
+
+    #[test]
+    fn rejects_newer_and_unknown_formats() {
+        let newer = r#"{"schema_version": 99, "entries": []}"#;
+        assert!(matches!(
+            parse_site_index(newer),
+            Err(ContentServiceError::UnsupportedSchema {
+                found: Some(99),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_site_index(r#""just a string""#),
+            Err(ContentServiceError::UnsupportedSchema { found: None, .. })
+        ));
+    }
+}
@@ -0,0 +1,270 @@
+//! How a section lists its entries, declared in the frontmatter of the
+//! section's `readme.md`. The helper copies that frontmatter onto the
+//! section's directory entry, so a new section needs no code changes:
+//!
+//! ```yaml
+//! ---
+//! title: Pictures
+//! layout: photo       # card, photo, music or timeline
+//! sort: date          # date, name, path or any frontmatter field
+//! order: desc         # asc or desc
+//! page_size: 24
+//! scroll: infinite    # or pages, only with a page_size
+//! pinned: [25-08-15 Puffy.md]
+//! ---
+//! ```
+
+use crate::dates::parse_content_date;
+use crate::models::JsonEntry;
+use std::cmp::Ordering;
+
+/// How the entries of a section are drawn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    #[default]
+    Card,
+    Photo,
+    Music,
+    /// Grouped under the year they are dated
+    Timeline,
+}
+
+impl Layout {
+    pub fn parse(value: &str) -> Option<Layout> {
+        match value.trim().to_lowercase().as_str() {
+            "card" => Some(Layout::Card),
+            "photo" => Some(Layout::Photo),
+            "music" => Some(Layout::Music),
+            "timeline" => Some(Layout::Timeline),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// Shown above the readme, the readme alone when `None`
+    pub title: Option<String>,
+    pub layout: Layout,
+    /// `date`, `name`, `path` or a frontmatter field
+    pub sort: String,
+    pub descending: bool,
+    /// Entries per page, all of them when `None`
+    pub page_size: Option<usize>,
+    /// Load the next page as the end of the listing comes into view rather
+    /// than linking to it
+    pub infinite_scroll: bool,
+    /// File names or paths listed first, in this order
+    pub pinned: Vec<String>,
+}
+
+impl Default for Section {
+    fn default() -> Self {
+        Self {
+            title: None,
+            layout: Layout::Card,
+            sort: "date".to_string(),
+            descending: true,
+            page_size: None,
+            infinite_scroll: false,
+            pinned: Vec::new(),
+        }
+    }
+}
+
+impl Section {
+    /// Settings from a directory entry's frontmatter. Values that don't parse
+    /// keep their defaults.
+    pub fn from_entry(entry: &JsonEntry) -> Section {
+        let mut section = Section::default();
+        let metadata = &entry.metadata;
+
+        section.title = metadata.get("title").cloned();
+        if let Some(layout) = metadata.get("layout").and_then(|l| Layout::parse(l)) {
+            section.layout = layout;
+        }
+        if let Some(sort) = metadata.get("sort") {
+            section.sort = sort.trim().to_string();
+            // Dates read newest first, everything else alphabetically
+            section.descending = section.sort == "date";
+        }
+        match metadata
+            .get("order")
+            .map(|o| o.trim().to_lowercase())
+            .as_deref()
+        {
+            Some("asc") => section.descending = false,
+            Some("desc") => section.descending = true,
+            _ => {}
+        }
+        section.page_size = metadata
+            .get("page_size")
+            .and_then(|size| size.trim().parse().ok())
+            .filter(|&size| size > 0);
+        section.infinite_scroll = metadata
+            .get("scroll")
+            .is_some_and(|scroll| scroll.trim().eq_ignore_ascii_case("infinite"));
+        section.pinned = entry.list("pinned");
+
+        section
+    }
+
+    /// Place among the pinned entries: those listed in the readme in that
+    /// order, then any with `pinned: true` in their own frontmatter
+    fn pin_rank(&self, entry: &JsonEntry) -> Option<usize> {
+        let name = entry.path.rsplit('/').next().unwrap_or("");
+        let listed = self.pinned.iter().position(|pin| {
+            let pin = pin.trim_start_matches('/');
+            pin.eq_ignore_ascii_case(name)
+                || pin.eq_ignore_ascii_case(entry.path.trim_start_matches('/'))
+        });
+        listed.or(entry.pinned.then_some(self.pinned.len()))
+    }
+
+    /// Sort `entries` by the section's field, pinned entries first. Entries
+    /// without a value for the field go last either way, ties go by name.
+    pub fn arrange(&self, entries: &mut [JsonEntry]) {
+        entries.sort_by(|a, b| {
+            let pinned = match (self.pin_rank(a), self.pin_rank(b)) {
+                (Some(pa), Some(pb)) => pa.cmp(&pb),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+            pinned
+                .then_with(|| self.compare(a, b))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+    }
+
+    fn compare(&self, a: &JsonEntry, b: &JsonEntry) -> Ordering {
+        let ordering = match self.sort.as_str() {
+            "date" => {
+                let day = |entry: &JsonEntry| entry.date.as_deref().and_then(parse_content_date);
+                present_first(day(a), day(b))
+            }
+            "name" => Some(a.name.to_lowercase().cmp(&b.name.to_lowercase())),
+            "path" => Some(a.path.cmp(&b.path)),
+            field => present_first(a.metadata.get(field), b.metadata.get(field)),
+        };
+        match ordering {
+            Some(ordering) if self.descending => ordering.reverse(),
+            Some(ordering) => ordering,
+            // Missing values stay last rather than flipping with the order
+            None => match (self.has_value(a), self.has_value(b)) {
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                _ => Ordering::Equal,
+            },
+        }
+    }
+
+    fn has_value(&self, entry: &JsonEntry) -> bool {
+        match self.sort.as_str() {
+            "date" => entry.date.as_deref().and_then(parse_content_date).is_some(),
+            "name" | "path" => true,
+            field => entry.metadata.contains_key(field),
+        }
+    }
+}
+
+/// Comparison of two values when both are there, `None` otherwise
+fn present_first<T: Ord>(a: Option<T>, b: Option<T>) -> Option<Ordering> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::entry_types;
+
+    fn entry(path: &str, date: Option<&str>, metadata: &[(&str, &str)]) -> JsonEntry {
+        JsonEntry {
+            path: path.to_string(),
+            entry_type: entry_types::DOCUMENT.to_string(),
+            name: path.rsplit('/').next().unwrap().to_string(),
+            date: date.map(str::to_string),
+            metadata: metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn paths(entries: &[JsonEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.path.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_readme_frontmatter_and_arranges() {
+        let readme = entry(
+            "/notes",
+            None,
+            &[
+                ("title", "Notes"),
+                ("layout", "Timeline"),
+                ("page_size", "10"),
+                ("scroll", "infinite"),
+                (
+                    "pinned",
+                    r#"Sequence [String("c.md"), String("/notes/b.md")]"#,
+                ),
+            ],
+        );
+        let section = Section::from_entry(&readme);
+        assert_eq!(section.title.as_deref(), Some("Notes"));
+        assert_eq!(section.layout, Layout::Timeline);
+        assert_eq!(section.page_size, Some(10));
+        assert!(section.infinite_scroll);
+
+        let mut entries = vec![
+            entry("/notes/a.md", Some("2024-01-01"), &[("rank", "2")]),
+            entry("/notes/b.md", Some("2021-01-01"), &[]),
+            entry("/notes/c.md", None, &[("rank", "1")]),
+            entry("/notes/d.md", Some("05/06/2024"), &[]),
+            entry("/notes/e.md", None, &[]),
+            entry("/notes/f.md", Some("2020-01-01"), &[]),
+        ];
+        entries[5].pinned = true;
+        section.arrange(&mut entries);
+        assert_eq!(
+            paths(&entries),
+            vec![
+                "/notes/c.md",
+                "/notes/b.md",
+                "/notes/f.md",
+                "/notes/d.md",
+                "/notes/a.md",
+                "/notes/e.md"
+            ]
+        );
+
+        let by_rank = Section {
+            sort: "rank".to_string(),
+            descending: false,
+            ..Section::default()
+        };
+        by_rank.arrange(&mut entries);
+        assert_eq!(
+            paths(&entries),
+            vec![
+                "/notes/f.md",
+                "/notes/c.md",
+                "/notes/a.md",
+                "/notes/b.md",
+                "/notes/d.md",
+                "/notes/e.md"
+            ]
+        );
+
+        // Nothing declared keeps the old newest-first cards
+        assert_eq!(
+            Section::from_entry(&entry("/blog", None, &[])),
+            Section::default()
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! How `ContentServiceClient` fetches the index and documents. The browser
+//! build uses `fetch`; natively the client can read a helper output
+//! directory straight from disk or fetch over HTTP, so the CLI tools and
+//! `cargo test` get the same filtering and sorting as the site.
+
+use crate::error::ContentServiceError;
+use gloo_net::http::Request;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// User-Agent string for API requests
+pub const USER_AGENT: &str = "olifm-rust/1.0";
+
+/// What came back for a GET, whatever the status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    /// Header names are lowercase
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+}
+
+/// Fetches a URL with extra request headers, e.g. `If-None-Match`. An HTTP
+/// error status is still a `Response`, `Err` is for when nothing came back
+/// at all.
+#[allow(async_fn_in_trait)] // the browser's futures aren't Send either way
+pub trait Transport: Clone + 'static {
+    async fn get(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<Response, ContentServiceError>;
+
+    /// Wait between retries without blocking the runtime the transport uses
+    async fn sleep(&self, duration: Duration);
+}
+
+/// The browser's `fetch`. A request whose future is dropped, e.g. by a
+/// `CancelToken`, is aborted rather than left to finish unread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FetchTransport;
+
+/// Aborts its request when dropped before `done`
+struct AbortOnDrop {
+    controller: Option<web_sys::AbortController>,
+}
+
+impl AbortOnDrop {
+    fn new() -> Self {
+        Self {
+            controller: web_sys::AbortController::new().ok(),
+        }
+    }
+
+    fn signal(&self) -> Option<web_sys::AbortSignal> {
+        self.controller
+            .as_ref()
+            .map(|controller| controller.signal())
+    }
+
+    fn done(mut self) {
+        self.controller = None;
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(controller) = &self.controller {
+            controller.abort();
+        }
+    }
+}
+
+impl Transport for FetchTransport {
+    async fn get(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<Response, ContentServiceError> {
+        let abort = AbortOnDrop::new();
+        let signal = abort.signal();
+        let mut request = Request::get(url)
+            .header("User-Agent", USER_AGENT)
+            .abort_signal(signal.as_ref());
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let resp = request.send().await?;
+        let headers = resp
+            .headers()
+            .entries()
+            .map(|(name, value)| (name.to_lowercase(), value))
+            .collect();
+        let body = resp.text().await.map_err(|e| {
+            ContentServiceError::NetworkError(format!("Failed to read response text: {:?}", e))
+        })?;
+        abort.done();
+        Ok(Response {
+            status: resp.status(),
+            headers,
+            body,
+        })
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let millis = duration.as_millis().min(u32::MAX as u128) as u32;
+        gloo_timers::future::TimeoutFuture::new(millis).await;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::{FsTransport, HttpTransport};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::{Response, Transport, USER_AGENT};
+    use crate::error::ContentServiceError;
+    use std::collections::BTreeMap;
+    use std::io::ErrorKind;
+    use std::path::{Component, Path, PathBuf};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Serves a helper output directory from disk, as if it were the site.
+    /// The URL's path is taken relative to `root`, so a client with an empty
+    /// base URL reads `root/directory_structure.json` and
+    /// `root/content/...`. Missing files are a 404.
+    #[derive(Debug, Clone)]
+    pub struct FsTransport {
+        root: PathBuf,
+    }
+
+    impl FsTransport {
+        pub fn new(root: impl Into<PathBuf>) -> Self {
+            Self { root: root.into() }
+        }
+
+        pub fn root(&self) -> &Path {
+            &self.root
+        }
+
+        /// File under `root` for `url`, `None` if it would escape it
+        fn resolve(&self, url: &str) -> Option<PathBuf> {
+            // Drop any scheme and host, then the query and fragment
+            let path = match url.split_once("://") {
+                Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+                None => url,
+            };
+            let path = path.split(['?', '#']).next().unwrap_or("");
+            let relative = PathBuf::from(percent_decode(path.trim_start_matches('/')));
+            relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+                .then(|| self.root.join(relative))
+        }
+    }
+
+    impl Transport for FsTransport {
+        async fn get(
+            &self,
+            url: &str,
+            _headers: &[(&str, &str)],
+        ) -> Result<Response, ContentServiceError> {
+            let status = |status: u16, body: String| Response {
+                status,
+                headers: BTreeMap::new(),
+                body,
+            };
+            let Some(path) = self.resolve(url) else {
+                return Ok(status(404, String::new()));
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(body) => Ok(status(200, body)),
+                Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::IsADirectory) => {
+                    Ok(status(404, String::new()))
+                }
+                Err(e) => Err(ContentServiceError::NetworkError(format!(
+                    "Failed to read '{}': {}",
+                    path.display(),
+                    e
+                ))),
+            }
+        }
+
+        async fn sleep(&self, duration: Duration) {
+            std::thread::sleep(duration);
+        }
+    }
+
+    /// Blocking HTTP for native tools, the request finishes before the
+    /// future is first polled so any executor will do
+    #[derive(Debug, Clone)]
+    pub struct HttpTransport {
+        agent: Arc<ureq::Agent>,
+    }
+
+    impl Default for HttpTransport {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl HttpTransport {
+        pub fn new() -> Self {
+            Self::with_agent(ureq::AgentBuilder::new().user_agent(USER_AGENT).build())
+        }
+
+        /// Give up on a request that takes longer than `timeout` in all
+        pub fn with_timeout(timeout: Duration) -> Self {
+            Self::with_agent(
+                ureq::AgentBuilder::new()
+                    .user_agent(USER_AGENT)
+                    .timeout(timeout)
+                    .build(),
+            )
+        }
+
+        /// Use a configured agent, e.g. with a proxy
+        pub fn with_agent(agent: ureq::Agent) -> Self {
+            Self {
+                agent: Arc::new(agent),
+            }
+        }
+    }
+
+    impl Transport for HttpTransport {
+        async fn get(
+            &self,
+            url: &str,
+            headers: &[(&str, &str)],
+        ) -> Result<Response, ContentServiceError> {
+            let mut request = self.agent.get(url);
+            for (name, value) in headers {
+                request = request.set(name, value);
+            }
+            let resp = match request.call() {
+                Ok(resp) => resp,
+                // Error statuses are answers too, the client maps them
+                Err(ureq::Error::Status(_, resp)) => resp,
+                Err(e) => return Err(ContentServiceError::NetworkError(e.to_string())),
+            };
+            let status = resp.status();
+            let headers = resp
+                .headers_names()
+                .into_iter()
+                .filter_map(|name| {
+                    let value = resp.header(&name)?.to_string();
+                    Some((name.to_lowercase(), value))
+                })
+                .collect();
+            let body = resp.into_string().map_err(|e| {
+                ContentServiceError::NetworkError(format!("Failed to read response text: {}", e))
+            })?;
+            Ok(Response {
+                status,
+                headers,
+                body,
+            })
+        }
+
+        async fn sleep(&self, duration: Duration) {
+            std::thread::sleep(duration);
+        }
+    }
+
+    /// Undo `%XX` escapes, e.g. the `%20` in a document's URL
+    fn percent_decode(value: &str) -> String {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (bytes[i], hex) {
+                (b'%', Some(byte)) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                (byte, _) => {
+                    decoded.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&decoded).into_owned()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::fs;
+
+        #[test]
+        fn reads_files_under_the_root_only() {
+            let root = std::env::temp_dir().join(format!("olifm-transport-{}", std::process::id()));
+            fs::create_dir_all(root.join("content/blog")).unwrap();
+            fs::write(root.join("content/blog/Hello World.md"), "hello").unwrap();
+            let transport = FsTransport::new(&root);
+
+            let get = |url: &str| pollster::block_on(transport.get(url, &[])).unwrap();
+            let found = get("/content/blog/Hello%20World.md");
+            assert!(found.ok());
+            assert_eq!(found.body, "hello");
+            assert_eq!(
+                get("https://oli.fm/content/blog/Hello%20World.md?v=2").body,
+                "hello"
+            );
+            assert_eq!(get("/content/blog/missing.md").status, 404);
+            assert_eq!(get("/content/blog").status, 404);
+            assert_eq!(get("/content/../../etc/passwd").status, 404);
+
+            fs::remove_dir_all(&root).unwrap();
+        }
+    }
+}
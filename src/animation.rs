@@ -0,0 +1,260 @@
+use content_service::models::Animation;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Frames, RgbaImage};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Rendition produced for animated images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscodeFormat {
+    Mp4,
+    WebP,
+}
+
+impl TranscodeFormat {
+    pub fn parse(value: &str) -> Option<TranscodeFormat> {
+        match value.to_lowercase().as_str() {
+            "mp4" => Some(TranscodeFormat::Mp4),
+            "webp" => Some(TranscodeFormat::WebP),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscodeFormat::Mp4 => "mp4",
+            TranscodeFormat::WebP => "webp",
+        }
+    }
+}
+
+/// Frame count, total duration and first frame of an animated image, or
+/// `None` for stills and formats that can't animate
+pub struct AnimationInfo {
+    pub frames: u32,
+    pub duration_ms: u64,
+    pub first_frame: RgbaImage,
+}
+
+fn open(path: &Path) -> Option<BufReader<File>> {
+    File::open(path).ok().map(BufReader::new)
+}
+
+fn frames_of(path: &Path) -> Option<Frames<'static>> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "gif" => Some(GifDecoder::new(open(path)?).ok()?.into_frames()),
+        "webp" => {
+            let decoder = WebPDecoder::new(open(path)?).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            Some(decoder.into_frames())
+        }
+        "png" => {
+            let decoder = PngDecoder::new(open(path)?).ok()?;
+            if !decoder.is_apng().ok()? {
+                return None;
+            }
+            Some(decoder.apng().ok()?.into_frames())
+        }
+        _ => None,
+    }
+}
+
+pub fn detect_animation(path: &Path) -> Option<AnimationInfo> {
+    let mut frames = 0u32;
+    let mut duration_ms = 0f64;
+    let mut first_frame = None;
+
+    for frame in frames_of(path)? {
+        let frame = frame.ok()?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        if denom > 0 {
+            duration_ms += numer as f64 / denom as f64;
+        }
+        frames += 1;
+        if first_frame.is_none() {
+            first_frame = Some(frame.into_buffer());
+        }
+    }
+
+    if frames < 2 {
+        return None;
+    }
+    Some(AnimationInfo {
+        frames,
+        duration_ms: duration_ms.round() as u64,
+        first_frame: first_frame?,
+    })
+}
+
+/// Output named after `path`, `logo.gif` -> `logo.<suffix>`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("animation");
+    path.with_file_name(format!("{}.{}", stem, suffix))
+}
+
+/// Whether `output` exists and is at least as new as `source`
+fn is_fresh(source: &Path, output: &Path) -> bool {
+    match (fs::metadata(source), fs::metadata(output)) {
+        (Ok(src), Ok(out)) => match (src.modified(), out.modified()) {
+            (Ok(src_time), Ok(out_time)) => out_time >= src_time,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether `output` is missing or older than `source`, creating its directory
+/// when it is
+fn needs_writing(source: &Path, output: &Path) -> bool {
+    if is_fresh(source, output) {
+        return false;
+    }
+    if let Some(parent) = output.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    true
+}
+
+/// Write the first frame as `<stem>.poster.png` beside `output`, where the
+/// source sits in the tree being written to
+pub fn write_poster(path: &Path, output: &Path, info: &AnimationInfo) -> Option<PathBuf> {
+    let poster = sibling(output, "poster.png");
+    if needs_writing(path, &poster) {
+        info.first_frame.save(&poster).ok()?;
+    }
+    Some(poster)
+}
+
+/// Whether `path` is a poster or rendition written by the helper, these are
+/// attached to their source rather than indexed on their own
+pub fn is_derived_file(path: &Path) -> bool {
+    source_stem(path).is_some()
+}
+
+/// Stem of the file a poster or rendition was made from, `clip.poster.jpg`
+/// -> `clip`
+pub fn source_stem(path: &Path) -> Option<&str> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.strip_suffix(".poster")
+        .or_else(|| stem.strip_suffix(".anim"))
+}
+
+/// Transcode with ffmpeg into `<stem>.anim.mp4` or `<stem>.anim.webp` beside
+/// `output`, returning the new file when it was written
+pub fn transcode(path: &Path, output: &Path, format: TranscodeFormat) -> Option<PathBuf> {
+    let output = sibling(output, &format!("anim.{}", format.extension()));
+    if !needs_writing(path, &output) {
+        return Some(output);
+    }
+
+    let mut command = Command::new("ffmpeg");
+    command.args(["-v", "error", "-y", "-i"]).arg(path);
+    match format {
+        TranscodeFormat::Mp4 => {
+            // yuv420p needs even dimensions, and faststart lets playback
+            // begin before the whole file arrives
+            command.args([
+                "-movflags",
+                "faststart",
+                "-pix_fmt",
+                "yuv420p",
+                "-vf",
+                "scale=trunc(iw/2)*2:trunc(ih/2)*2",
+                "-an",
+            ]);
+        }
+        TranscodeFormat::WebP => {
+            command.args(["-c:v", "libwebp_anim", "-loop", "0", "-q:v", "75"]);
+        }
+    }
+    let status = command.arg(&output).stdout(Stdio::null()).status().ok()?;
+    if status.success() {
+        Some(output)
+    } else {
+        let _ = fs::remove_file(&output);
+        None
+    }
+}
+
+/// Detect, poster and optionally transcode one image. Derived files are named
+/// after `output`, where the image sits in the tree being written to. Paths in
+/// the result are built by `to_index_path` so callers decide what they're
+/// relative to.
+pub fn process_animation(
+    path: &Path,
+    output: &Path,
+    format: Option<TranscodeFormat>,
+    to_index_path: impl Fn(&Path) -> String,
+) -> Option<Animation> {
+    let info = detect_animation(path)?;
+
+    let poster = write_poster(path, output, &info).map(|p| to_index_path(&p));
+    if poster.is_none() {
+        println!("Could not write poster for {}", path.display());
+    }
+
+    let transcoded = format.and_then(|format| {
+        let output = transcode(path, output, format);
+        if output.is_none() {
+            println!(
+                "Could not transcode {} to {}, is ffmpeg installed?",
+                path.display(),
+                format.extension()
+            );
+        }
+        output.map(|p| to_index_path(&p))
+    });
+
+    Some(Animation {
+        frames: info.frames,
+        duration_ms: info.duration_ms,
+        poster,
+        transcoded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, Rgba};
+
+    #[test]
+    fn counts_frames_and_duration_of_gif() {
+        let dir = std::env::temp_dir().join(format!("olifm-anim-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("blink.gif");
+        {
+            let mut encoder = GifEncoder::new(File::create(&path).unwrap());
+            for colour in [[255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 255]] {
+                let buffer = RgbaImage::from_pixel(4, 4, Rgba(colour));
+                let delay = Delay::from_numer_denom_ms(100, 1);
+                encoder
+                    .encode_frame(Frame::from_parts(buffer, 0, 0, delay))
+                    .unwrap();
+            }
+        }
+
+        let info = detect_animation(&path).expect("gif should be animated");
+        assert_eq!(info.frames, 3);
+        assert_eq!(info.duration_ms, 300);
+        assert_eq!(info.first_frame.get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+        let output = dir.join("out/pictures/blink.gif");
+        let poster = write_poster(&path, &output, &info).unwrap();
+        assert_eq!(poster, dir.join("out/pictures/blink.poster.png"));
+        assert!(poster.exists());
+        assert!(!dir.join("blink.poster.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
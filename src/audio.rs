@@ -0,0 +1,176 @@
+use content_service::models::Audio;
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::ignore::content_files;
+use crate::to_forward_slashes;
+
+/// Number of bars stored for each track's waveform
+const PEAK_COUNT: usize = 200;
+
+/// Frames folded into one intermediate peak while decoding, keeps memory flat
+/// for long recordings
+const BLOCK_FRAMES: usize = 1024;
+
+pub fn is_audio_file(path: &Path) -> bool {
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        matches!(
+            ext.to_lowercase().as_str(),
+            "mp3" | "wav" | "flac" | "ogg" | "m4a" | "aac"
+        )
+    } else {
+        false
+    }
+}
+
+/// Decode the whole track and return `PEAK_COUNT` peaks scaled to 0-255 along
+/// with its duration in milliseconds
+fn decode_peaks_and_duration(path: &Path) -> Option<(Vec<u8>, u64)> {
+    let file = File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+    let mut format = probed.format;
+    let track = format.default_track()?;
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .ok()?;
+
+    let mut blocks: Vec<f32> = Vec::new();
+    let mut block_peak = 0.0f32;
+    let mut block_len = 0usize;
+    let mut total_frames = 0u64;
+
+    while let Ok(packet) = format.next_packet() {
+        if packet.track_id() != track_id {
+            continue;
+        }
+        // Skip over corrupt packets rather than dropping the whole track
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(_) => continue,
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+
+        for frame in buf.samples().chunks(channels) {
+            let peak = frame.iter().fold(0.0f32, |acc, s| acc.max(s.abs()));
+            block_peak = block_peak.max(peak);
+            block_len += 1;
+            total_frames += 1;
+            if block_len == BLOCK_FRAMES {
+                blocks.push(block_peak);
+                block_peak = 0.0;
+                block_len = 0;
+            }
+        }
+    }
+    if block_len > 0 {
+        blocks.push(block_peak);
+    }
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let duration_ms = total_frames * 1000 / sample_rate as u64;
+    Some((downsample_peaks(&blocks, PEAK_COUNT), duration_ms))
+}
+
+/// Reduce `blocks` to `count` bars by taking the loudest block in each bucket,
+/// normalised so the loudest bar is 255
+fn downsample_peaks(blocks: &[f32], count: usize) -> Vec<u8> {
+    let count = count.min(blocks.len());
+    let loudest = blocks.iter().fold(0.0f32, |acc, p| acc.max(*p));
+    if count == 0 || loudest <= 0.0 {
+        return vec![0; count];
+    }
+
+    (0..count)
+        .map(|i| {
+            let start = i * blocks.len() / count;
+            let end = ((i + 1) * blocks.len() / count).max(start + 1);
+            let peak = blocks[start..end].iter().fold(0.0f32, |acc, p| acc.max(*p));
+            (peak / loudest * 255.0).round() as u8
+        })
+        .collect()
+}
+
+pub fn build_audio_structure(base: &Path) -> std::io::Result<Vec<Audio>> {
+    let mut tracks: Vec<Audio> = Vec::new();
+
+    if base.is_dir() {
+        for path in content_files(base)? {
+            if is_audio_file(&path) {
+                let relative_path = path.strip_prefix(base).unwrap_or(&path);
+                let path_str = format!("/{}", to_forward_slashes(relative_path));
+                let name = path
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("")
+                    .to_string();
+
+                // Tracks we can't decode are still playable, just without a waveform
+                let (peaks, duration_ms) = match decode_peaks_and_duration(&path) {
+                    Some((peaks, duration_ms)) => (peaks, Some(duration_ms)),
+                    None => {
+                        println!("Could not decode audio: {}", path_str);
+                        (Vec::new(), None)
+                    }
+                };
+
+                println!("Audio added: {}", path_str);
+                tracks.push(Audio {
+                    name,
+                    path: path_str,
+                    duration_ms,
+                    peaks,
+                });
+            }
+        }
+    }
+
+    Ok(tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsample_keeps_loudest_block_per_bucket() {
+        let blocks = [0.1, 0.5, 0.2, 0.25, 0.0, 0.0];
+        assert_eq!(downsample_peaks(&blocks, 3), vec![255, 128, 0]);
+    }
+
+    #[test]
+    fn downsample_never_returns_more_bars_than_blocks() {
+        assert_eq!(downsample_peaks(&[1.0, 0.5], 200), vec![255, 128]);
+    }
+
+    #[test]
+    fn silent_track_has_flat_waveform() {
+        assert_eq!(downsample_peaks(&[0.0; 4], 2), vec![0, 0]);
+    }
+}
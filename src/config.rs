@@ -0,0 +1,233 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::animation::TranscodeFormat;
+
+/// Config file picked up from the working directory when `--config` isn't given
+pub const DEFAULT_CONFIG_FILE: &str = "olifm.yaml";
+
+/// Helper settings, every field has a default so the file is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub images: ImageConfig,
+    pub similar: SimilarConfig,
+    pub privacy: PrivacyConfig,
+    pub site: SiteConfig,
+    pub history: HistoryConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageConfig {
+    /// Fixed blurhash components as "XxY", e.g. "4x3". When unset they are
+    /// chosen per image from its aspect ratio.
+    pub blurhash_components: Option<String>,
+    /// Components along the longer side when choosing from the aspect ratio
+    pub blurhash_max_components: u32,
+    /// Longer side in pixels of the precomputed placeholder
+    pub placeholder_size: u32,
+    /// Perceptual hash distance (out of 64 bits) at or below which two images
+    /// are reported as near duplicates
+    pub duplicate_distance: u32,
+    /// Rendition written beside animated images, "mp4" or "webp". Needs
+    /// ffmpeg, when unset animations only get a static poster.
+    pub transcode_animated: Option<String>,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            blurhash_components: None,
+            blurhash_max_components: 4,
+            placeholder_size: 32,
+            duplicate_distance: 6,
+            transcode_animated: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimilarConfig {
+    /// Sections whose photo entries get "similar photos" recommendations
+    pub sections: Vec<String>,
+    /// Recommendations kept per entry
+    pub max: usize,
+    /// Score from 0 to 1 a recommendation needs to be kept
+    pub min_score: f32,
+}
+
+impl Default for SimilarConfig {
+    fn default() -> Self {
+        Self {
+            sections: vec!["pictures".to_string()],
+            max: 6,
+            min_score: 0.3,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrivacyConfig {
+    /// Drop GPS EXIF tags and XMP packets with a location from published images
+    pub strip_gps: bool,
+    /// Further EXIF tags to drop by name, e.g. "BodySerialNumber"
+    pub strip_fields: Vec<String>,
+    /// Where `publish` records what it removed. Keep it outside the published
+    /// directory, it holds the data that was stripped.
+    pub report: PathBuf,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            strip_gps: true,
+            strip_fields: [
+                "MakerNote",
+                "BodySerialNumber",
+                "LensSerialNumber",
+                "CameraOwnerName",
+                "ImageUniqueID",
+            ]
+            .map(String::from)
+            .to_vec(),
+            report: PathBuf::from("privacy-report.json"),
+        }
+    }
+}
+
+/// Written into the index envelope for the site to show
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    pub name: Option<String>,
+    pub base_url: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            name: Some("oli.fm".to_string()),
+            base_url: Some("https://oli.fm".to_string()),
+            description: None,
+        }
+    }
+}
+
+/// Created and updated dates read from the content's git history
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Read `git log` when the content directory is in a repository
+    pub enabled: bool,
+    /// Latest commits kept per entry as its change log, 0 for none
+    pub changelog: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            changelog: 0,
+        }
+    }
+}
+
+impl ImageConfig {
+    /// The override from `blurhash_components`, if one is set
+    pub fn fixed_components(&self) -> Option<(u32, u32)> {
+        self.blurhash_components
+            .as_deref()
+            .and_then(parse_components)
+    }
+
+    /// The format from `transcode_animated`, if one is set
+    pub fn transcode_format(&self) -> Option<TranscodeFormat> {
+        self.transcode_animated
+            .as_deref()
+            .and_then(TranscodeFormat::parse)
+    }
+}
+
+/// Parse "XxY" where both counts are within blurhash's 1-9 range
+fn parse_components(value: &str) -> Option<(u32, u32)> {
+    let (x, y) = value.trim().split_once(['x', 'X'])?;
+    let x: u32 = x.trim().parse().ok()?;
+    let y: u32 = y.trim().parse().ok()?;
+    if (1..=9).contains(&x) && (1..=9).contains(&y) {
+        Some((x, y))
+    } else {
+        None
+    }
+}
+
+impl Config {
+    /// Load `path`, or the default file if present, falling back to defaults
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => Path::new(DEFAULT_CONFIG_FILE),
+            None => return Ok(Config::default()),
+        };
+
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config '{}': {}", path.display(), e))?;
+        let config: Config = serde_yaml::from_str(&text)
+            .map_err(|e| format!("failed to parse config '{}': {}", path.display(), e))?;
+        config.validate()?;
+
+        println!("Loaded config from '{}'", path.display());
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let images = &self.images;
+        if let Some(components) = &images.blurhash_components
+            && images.fixed_components().is_none()
+        {
+            return Err(format!(
+                "images.blurhash_components '{}' should look like \"4x3\" with counts from 1 to 9",
+                components
+            ));
+        }
+        if !(1..=9).contains(&images.blurhash_max_components) {
+            return Err("images.blurhash_max_components should be from 1 to 9".to_string());
+        }
+        if images.duplicate_distance > 64 {
+            return Err("images.duplicate_distance should be from 0 to 64".to_string());
+        }
+        if let Some(format) = &images.transcode_animated
+            && images.transcode_format().is_none()
+        {
+            return Err(format!(
+                "images.transcode_animated '{}' should be \"mp4\" or \"webp\"",
+                format
+            ));
+        }
+        if images.placeholder_size == 0 {
+            return Err("images.placeholder_size should be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.similar.min_score) {
+            return Err("similar.min_score should be from 0 to 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_parse_and_range_check() {
+        assert_eq!(parse_components("4x3"), Some((4, 3)));
+        assert_eq!(parse_components(" 3 X 5 "), Some((3, 5)));
+        assert_eq!(parse_components("0x3"), None);
+        assert_eq!(parse_components("10x3"), None);
+        assert_eq!(parse_components("four"), None);
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, NaiveDate};
+use content_service::JsonEntry;
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Shared with the site, which sorts and files entries by the same dates
+pub use content_service::dates::parse_content_date;
+
+/// A `YYYY-MM-DD` or `YYYYMMDD` day anywhere in a file name
+static FILE_NAME_DATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\D)(\d{4})-?(\d{2})-?(\d{2})(?:\D|$)").unwrap());
+
+/// Date written into a file name, e.g. `2024-07-05-trip.md` or a camera's
+/// `PXL_20240705_215141089.jpg`. Used when there is no frontmatter date so the
+/// index doesn't depend on filesystem timestamps.
+pub fn date_from_file_name(name: &str) -> Option<NaiveDate> {
+    let captures = FILE_NAME_DATE.captures(name)?;
+    let part = |i: usize| captures.get(i)?.as_str().parse::<u32>().ok();
+    NaiveDate::from_ymd_opt(part(1)? as i32, part(2)?, part(3)?)
+}
+
+/// `generated_at` for the index: `SOURCE_DATE_EPOCH` when the build sets it,
+/// otherwise the newest entry date or edit, so rebuilding unchanged content
+/// gives the same bytes
+pub fn index_timestamp(entries: &[JsonEntry]) -> Option<String> {
+    if let Some(epoch) = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.trim().parse::<i64>().ok())
+    {
+        return DateTime::from_timestamp(epoch, 0)
+            .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string());
+    }
+    entries
+        .iter()
+        .flat_map(|entry| [entry.date.as_deref(), entry.updated.as_deref()])
+        .filter_map(|date| date.and_then(parse_content_date))
+        .max()
+        .map(|day| format!("{}T00:00:00Z", day.format("%Y-%m-%d")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_dates_from_file_names() {
+        let day = NaiveDate::from_ymd_opt(2024, 7, 5);
+        assert_eq!(date_from_file_name("2024-07-05-trip.md"), day);
+        assert_eq!(date_from_file_name("PXL_20240705_215141089.jpg"), day);
+        assert_eq!(date_from_file_name("2024-2-Employment.md"), None);
+        assert_eq!(date_from_file_name("readme.md"), None);
+    }
+}
@@ -0,0 +1,219 @@
+use content_service::models::Img;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::phash;
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct DupeMember {
+    pub path: String,
+    /// Hash distance from the first member, 0 for byte-identical copies
+    pub distance: u32,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DupeReport {
+    pub max_distance: u32,
+    /// Byte-identical files
+    pub exact: Vec<Vec<DupeMember>>,
+    /// Different files whose perceptual hashes are within `max_distance`
+    pub near: Vec<Vec<DupeMember>>,
+}
+
+impl DupeReport {
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.near.is_empty()
+    }
+
+    pub fn print(&self) {
+        if self.is_empty() {
+            println!("No duplicate images found.");
+            return;
+        }
+
+        println!("Exact duplicates ({} clusters):", self.exact.len());
+        for cluster in &self.exact {
+            print_cluster(cluster);
+        }
+        println!(
+            "Near duplicates ({} clusters, max distance {}):",
+            self.near.len(),
+            self.max_distance
+        );
+        for cluster in &self.near {
+            print_cluster(cluster);
+        }
+    }
+}
+
+fn print_cluster(cluster: &[DupeMember]) {
+    for (i, member) in cluster.iter().enumerate() {
+        let bullet = if i == 0 { "  - " } else { "    " };
+        if i == 0 {
+            println!("{}{}", bullet, member.path);
+        } else {
+            println!("{}{} (distance {})", bullet, member.path, member.distance);
+        }
+    }
+}
+
+fn file_digest(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let digest = Sha256::digest(&bytes);
+    Some(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Group images into byte-identical clusters, then cluster the distinct files
+/// by perceptual hash. `base` is the content directory the image paths are
+/// relative to.
+pub fn find_duplicates(base: &Path, images: &[Img], max_distance: u32) -> DupeReport {
+    // One group per distinct file, keyed by digest so the order is stable
+    let mut by_digest: BTreeMap<String, Vec<&Img>> = BTreeMap::new();
+    for img in images {
+        let full_path = base.join(img.path.trim_start_matches('/'));
+        if let Some(digest) = file_digest(&full_path) {
+            by_digest.entry(digest).or_default().push(img);
+        }
+    }
+
+    let mut report = DupeReport {
+        max_distance,
+        ..DupeReport::default()
+    };
+
+    let mut groups: Vec<Vec<&Img>> = by_digest.into_values().collect();
+    for group in &mut groups {
+        group.sort_by(|a, b| a.path.cmp(&b.path));
+        if group.len() > 1 {
+            report.exact.push(
+                group
+                    .iter()
+                    .map(|img| DupeMember {
+                        path: img.path.clone(),
+                        distance: 0,
+                    })
+                    .collect(),
+            );
+        }
+    }
+    report.exact.sort_by(|a, b| a[0].path.cmp(&b[0].path));
+
+    // Union-find over the distinct files
+    let hashes: Vec<Option<u64>> = groups
+        .iter()
+        .map(|group| group[0].phash.as_deref().and_then(phash::from_hex))
+        .collect();
+    let mut parent: Vec<usize> = (0..groups.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for a in 0..groups.len() {
+        for b in (a + 1)..groups.len() {
+            if let (Some(ha), Some(hb)) = (hashes[a], hashes[b])
+                && phash::distance(ha, hb) <= max_distance
+            {
+                let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+                parent[ra] = rb;
+            }
+        }
+    }
+
+    let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..groups.len() {
+        let r = root(&mut parent, i);
+        clusters.entry(r).or_default().push(i);
+    }
+
+    for members in clusters.into_values().filter(|m| m.len() > 1) {
+        let mut files: Vec<(&Img, Option<u64>)> = members
+            .iter()
+            .flat_map(|&i| {
+                let hash = hashes[i];
+                groups[i].iter().map(move |img| (*img, hash))
+            })
+            .collect();
+        files.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+
+        let first = files[0].1;
+        report.near.push(
+            files
+                .iter()
+                .map(|(img, hash)| DupeMember {
+                    path: img.path.clone(),
+                    distance: match (first, hash) {
+                        (Some(a), Some(b)) => phash::distance(a, *b),
+                        _ => 0,
+                    },
+                })
+                .collect(),
+        );
+    }
+    report.near.sort_by(|a, b| a[0].path.cmp(&b[0].path));
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn img(name: &str, phash: u64) -> Img {
+        Img {
+            blurhash: String::new(),
+            aspect_ratio: "1/1".to_string(),
+            name: name.to_string(),
+            path: format!("/{}", name),
+            palette: Vec::new(),
+            placeholder: None,
+            phash: Some(phash::to_hex(phash)),
+            animation: None,
+        }
+    }
+
+    #[test]
+    fn clusters_exact_and_near_copies() {
+        let dir = std::env::temp_dir().join(format!("olifm-dupes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.jpg"), b"one").unwrap();
+        fs::write(dir.join("a-copy.jpg"), b"one").unwrap();
+        fs::write(dir.join("b.jpg"), b"two").unwrap();
+        fs::write(dir.join("b-edit.jpg"), b"three").unwrap();
+        fs::write(dir.join("c.jpg"), b"four").unwrap();
+
+        let images = vec![
+            img("a.jpg", 0xff00),
+            img("a-copy.jpg", 0xff00),
+            img("b.jpg", 0x0f0f_0000_0000_0000),
+            img("b-edit.jpg", 0x0f0f_0000_0000_0003),
+            img("c.jpg", 0xffff_ffff_0000_0000),
+        ];
+        let report = find_duplicates(&dir, &images, 4);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.exact.len(), 1);
+        assert_eq!(report.exact[0][0].path, "/a-copy.jpg");
+        assert_eq!(report.exact[0][1].path, "/a.jpg");
+
+        assert_eq!(report.near.len(), 1);
+        assert_eq!(
+            report.near[0],
+            vec![
+                DupeMember {
+                    path: "/b-edit.jpg".to_string(),
+                    distance: 0
+                },
+                DupeMember {
+                    path: "/b.jpg".to_string(),
+                    distance: 2
+                },
+            ]
+        );
+    }
+}
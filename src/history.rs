@@ -0,0 +1,153 @@
+use content_service::models::Change;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Separates commits in the `git log` output, it can't appear in a subject
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+/// When a file was first and last committed, from the content's git history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHistory {
+    /// YYYY-MM-DD author date of the first commit
+    pub created: String,
+    /// YYYY-MM-DD author date of the latest commit
+    pub updated: String,
+    /// Every commit with the file, newest first
+    pub changes: Vec<Change>,
+}
+
+/// History of every committed file under `dir`, keyed by its path relative
+/// to `dir` in the index's "/dir/file" form. Read from the local repository
+/// with one `git log`, so it works offline and doesn't depend on checkout
+/// times. Empty when `dir` isn't in a repository or git isn't installed.
+pub fn load_history(dir: &Path) -> HashMap<String, FileHistory> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--relative",
+            "--name-only",
+            "--no-renames",
+            "--format=%x1e%as%x09%s",
+            "--",
+            ".",
+        ])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_log(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => HashMap::new(),
+    }
+}
+
+/// Parse `git log` records of a "date<TAB>subject" line followed by the
+/// files the commit touched, newest commit first
+fn parse_log(log: &str) -> HashMap<String, FileHistory> {
+    let mut history: HashMap<String, FileHistory> = HashMap::new();
+
+    for record in log.split(RECORD_SEPARATOR) {
+        let mut lines = record.lines();
+        let Some((date, summary)) = lines.next().and_then(|line| line.split_once('\t')) else {
+            continue;
+        };
+        let change = Change {
+            date: date.to_string(),
+            summary: summary.trim().to_string(),
+        };
+
+        for file in lines.map(str::trim).filter(|line| !line.is_empty()) {
+            let path = format!("/{}", file);
+            match history.get_mut(&path) {
+                // Older commits come later, each one moves `created` back
+                Some(file_history) => {
+                    file_history.created = change.date.clone();
+                    file_history.changes.push(change.clone());
+                }
+                None => {
+                    history.insert(
+                        path,
+                        FileHistory {
+                            created: change.date.clone(),
+                            updated: change.date.clone(),
+                            changes: vec![change.clone()],
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn git(dir: &Path, args: &[&str], date: &str) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args([
+                "-c",
+                "user.name=olifm",
+                "-c",
+                "user.email=olifm@example.com",
+            ])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn reads_first_and_last_commit_per_file() {
+        let repo = std::env::temp_dir().join(format!("olifm-history-{}", std::process::id()));
+        let content = repo.join("content");
+        fs::create_dir_all(content.join("blog")).unwrap();
+        git(&repo, &["init", "-q"], "2024-01-01T10:00:00Z");
+
+        fs::write(content.join("blog/post.md"), "first").unwrap();
+        fs::write(content.join("readme.md"), "home").unwrap();
+        git(&repo, &["add", "-A"], "2024-01-01T10:00:00Z");
+        git(
+            &repo,
+            &["commit", "-q", "-m", "Add post"],
+            "2024-01-01T10:00:00Z",
+        );
+
+        fs::write(content.join("blog/post.md"), "second").unwrap();
+        git(
+            &repo,
+            &["commit", "-q", "-am", "Fix typo"],
+            "2024-03-04T10:00:00Z",
+        );
+        fs::write(content.join("draft.md"), "uncommitted").unwrap();
+
+        let history = load_history(&content);
+        let post = &history["/blog/post.md"];
+        assert_eq!(post.created, "2024-01-01");
+        assert_eq!(post.updated, "2024-03-04");
+        let summaries: Vec<&str> = post.changes.iter().map(|c| c.summary.as_str()).collect();
+        assert_eq!(summaries, vec!["Fix typo", "Add post"]);
+        assert_eq!(history["/readme.md"].updated, "2024-01-01");
+        assert!(!history.contains_key("/draft.md"));
+
+        // Outside any repository there's simply no history
+        assert!(load_history(&std::env::temp_dir().join("olifm-no-such-dir")).is_empty());
+
+        fs::remove_dir_all(&repo).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Ignore file read from the root of the content directory
+pub const IGNORE_FILE: &str = ".olifmignore";
+
+/// Which content files are left out of the index and the published site.
+/// Hidden names such as `.obsidian`, `.trash` and `.DS_Store` always are,
+/// `.olifmignore` adds gitignore-style patterns:
+///
+/// - `drafts/` matches a directory named `drafts` anywhere
+/// - `*.tmp` matches any file or directory name
+/// - `/notes/private.md` with a slash is matched against the whole path
+/// - `#` starts a comment
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    glob: String,
+    /// Match the path from the content root rather than any single name
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRules {
+    /// Rules from `base/.olifmignore`, just the hidden-name rule when missing
+    pub fn load(base: &Path) -> IgnoreRules {
+        match fs::read_to_string(base.join(IGNORE_FILE)) {
+            Ok(text) => IgnoreRules::parse(&text),
+            Err(_) => IgnoreRules::default(),
+        }
+    }
+
+    pub fn parse(text: &str) -> IgnoreRules {
+        let patterns = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let dir_only = line.ends_with('/');
+                let line = line.trim_end_matches('/');
+                Pattern {
+                    anchored: line.contains('/'),
+                    glob: line.trim_start_matches('/').to_string(),
+                    dir_only,
+                }
+            })
+            .collect();
+        IgnoreRules { patterns }
+    }
+
+    /// Whether `relative`, a path under the content root, is left out. Only
+    /// its own name is checked, walks don't descend into ignored directories.
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let Some(name) = relative.file_name().and_then(|s| s.to_str()) else {
+            return false;
+        };
+        if name.starts_with('.') {
+            return true;
+        }
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        self.patterns.iter().any(|pattern| {
+            (is_dir || !pattern.dir_only)
+                && if pattern.anchored {
+                    glob_match(&pattern.glob, &relative)
+                } else {
+                    glob_match(&pattern.glob, name)
+                }
+        })
+    }
+}
+
+/// `*` matches any run of characters except `/`, `?` any single one
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*`, as (pattern index, text index)
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == text[t] || (c == '?' && text[t] != '/') => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) if text[star_t] != '/' => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                _ => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Every file under `base` that isn't ignored, sorted so walks are the same
+/// on every machine
+pub fn content_files(base: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let rules = IgnoreRules::load(base);
+    let mut files = Vec::new();
+    let mut dirs = vec![base.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry_res in fs::read_dir(&dir)? {
+            let entry = entry_res?;
+            let meta = entry.metadata()?;
+            let path = entry.path();
+            let relative = path.strip_prefix(base).unwrap_or(&path);
+            if rules.is_ignored(relative, meta.is_dir()) {
+                continue;
+            }
+
+            if meta.is_dir() {
+                dirs.push(path);
+            } else if meta.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_names_and_patterns() {
+        let rules = IgnoreRules::parse("# drafts\ndrafts/\n*.tmp\n/notes/private*.md\n");
+        let ignored = |path: &str, is_dir: bool| rules.is_ignored(Path::new(path), is_dir);
+
+        assert!(ignored(".obsidian", true));
+        assert!(ignored("blog/.DS_Store", false));
+        assert!(ignored("blog/drafts", true));
+        assert!(!ignored("blog/drafts", false));
+        assert!(ignored("blog/scratch.tmp", false));
+        assert!(ignored("notes/private-1.md", false));
+        assert!(!ignored("blog/notes/private-1.md", false));
+        assert!(!ignored("blog/post.md", false));
+    }
+}
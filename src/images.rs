@@ -0,0 +1,219 @@
+use base64::engine::{Engine, general_purpose};
+use blurhash::encode;
+use content_service::models::Img;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader, RgbaImage};
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::animation::{is_derived_file, process_animation};
+use crate::config::ImageConfig;
+use crate::ignore::content_files;
+use crate::palette::extract_palette;
+use crate::phash;
+use crate::to_forward_slashes;
+
+/// Images are shrunk to fit this box before blurhash encoding, the hash only
+/// keeps a handful of components so the full resolution buys nothing
+const BLURHASH_SAMPLE_SIZE: u32 = 128;
+
+pub fn is_image_file(path: &Path) -> bool {
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        matches!(
+            ext.to_lowercase().as_str(),
+            "png" | "jpg" | "jpeg" | "webp" | "bmp" | "tiff" | "gif"
+        )
+    } else {
+        false
+    }
+}
+
+pub struct ImageSummary {
+    pub blurhash: String,
+    pub aspect_ratio: String,
+    pub palette: Vec<String>,
+    pub placeholder: Option<String>,
+    pub phash: u64,
+}
+
+/// Blurhash components for an image, the longer side gets `max` and the
+/// shorter side scales with the aspect ratio
+pub fn components_for(width: u32, height: u32, max: u32) -> (u32, u32) {
+    if width == 0 || height == 0 {
+        return (max, max);
+    }
+    let scaled = |short: u32, long: u32| {
+        ((max as f64 * short as f64 / long as f64).round() as u32).clamp(1, max)
+    };
+    if width >= height {
+        (max, scaled(height, width))
+    } else {
+        (scaled(width, height), max)
+    }
+}
+
+/// Size of the placeholder, keeping the image's aspect ratio within `size`
+fn placeholder_dimensions(width: u32, height: u32, size: u32) -> (u32, u32) {
+    if width >= height {
+        let h = (size as f64 * height as f64 / width as f64).round() as u32;
+        (size, h.max(1))
+    } else {
+        let w = (size as f64 * width as f64 / height as f64).round() as u32;
+        (w.max(1), size)
+    }
+}
+
+/// Render the blurhash as a small PNG data URI so the client can show it
+/// straight away instead of decoding the hash itself
+pub fn placeholder_data_uri(blurhash: &str, width: u32, height: u32, size: u32) -> Option<String> {
+    let (w, h) = placeholder_dimensions(width, height, size);
+    let pixels = blurhash::decode(blurhash, w, h, 1.0).ok()?;
+    let image = RgbaImage::from_raw(w, h, pixels)?;
+
+    let mut png = Vec::new();
+    DynamicImage::ImageRgba8(image)
+        .to_rgb8()
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .ok()?;
+    Some(format!(
+        "data:image/png;base64,{}",
+        general_purpose::STANDARD.encode(png)
+    ))
+}
+
+/// Decode an image once and derive everything the index stores about it
+pub fn summarise_image(path: &Path, config: &ImageConfig) -> Option<ImageSummary> {
+    let reader = ImageReader::open(path).ok()?;
+    let img = reader.decode().ok()?;
+    let (width, height) = img.dimensions();
+    if height == 0 {
+        return None;
+    }
+
+    let (x, y) = config
+        .fixed_components()
+        .unwrap_or_else(|| components_for(width, height, config.blurhash_max_components));
+    let sample = if width > BLURHASH_SAMPLE_SIZE || height > BLURHASH_SAMPLE_SIZE {
+        img.thumbnail(BLURHASH_SAMPLE_SIZE, BLURHASH_SAMPLE_SIZE)
+    } else {
+        img.clone()
+    };
+    let rgba = sample.to_rgba8();
+    let blurhash = encode(x, y, rgba.width(), rgba.height(), rgba.as_raw()).ok()?;
+
+    let aspect_ratio = format!("{}/{}", width, height);
+    let palette = extract_palette(&img);
+    let placeholder = placeholder_data_uri(&blurhash, width, height, config.placeholder_size);
+    let phash = phash::perceptual_hash(&sample);
+    Some(ImageSummary {
+        blurhash,
+        aspect_ratio,
+        palette,
+        placeholder,
+        phash,
+    })
+}
+
+/// Summarise every image under `base`. Posters and renditions of animated
+/// images go under `derived_dir` and are indexed relative to it, with `None`
+/// animations aren't looked for at all.
+pub fn build_img_structure(
+    base: &Path,
+    derived_dir: Option<&Path>,
+    config: &ImageConfig,
+) -> std::io::Result<Vec<Img>> {
+    let mut images: Vec<Img> = Vec::new();
+
+    if base.is_dir() {
+        for path in content_files(base)? {
+            if is_image_file(&path) && !is_derived_file(&path) {
+                let relative_path = path.strip_prefix(base).unwrap_or(&path);
+                let path_str = format!("/{}", to_forward_slashes(relative_path));
+                let name = path
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("")
+                    .to_string();
+
+                // Get blurhash, aspect ratio, palette and placeholder
+                if let Some(summary) = summarise_image(&path, config) {
+                    let animation = derived_dir.and_then(|derived_dir| {
+                        let output = derived_dir.join(relative_path);
+                        process_animation(&path, &output, config.transcode_format(), |derived| {
+                            let relative = derived.strip_prefix(derived_dir).unwrap_or(derived);
+                            format!("/{}", to_forward_slashes(relative))
+                        })
+                    });
+                    if let Some(animation) = &animation {
+                        println!(
+                            "Animation found: {} ({} frames, {}ms)",
+                            path_str, animation.frames, animation.duration_ms
+                        );
+                    }
+                    println!("Image added: {}", path_str);
+                    images.push(Img {
+                        blurhash: summary.blurhash,
+                        aspect_ratio: summary.aspect_ratio,
+                        name,
+                        path: path_str,
+                        palette: summary.palette,
+                        placeholder: summary.placeholder,
+                        phash: Some(phash::to_hex(summary.phash)),
+                        animation,
+                    });
+                }
+                // Skip images that couldn't be processed
+            }
+        }
+    } else if base.is_file() && is_image_file(base) {
+        // Handle single image file case
+        let url = format!("{}", base.display());
+        let path_str = format!("/{}", to_forward_slashes(url));
+
+        let name = base
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_string();
+
+        if let Some(summary) = summarise_image(base, config) {
+            let animation = derived_dir.and_then(|derived_dir| {
+                let output = derived_dir.join(base.file_name()?);
+                process_animation(base, &output, config.transcode_format(), |derived| {
+                    format!("/{}", to_forward_slashes(derived))
+                })
+            });
+            images.push(Img {
+                blurhash: summary.blurhash,
+                aspect_ratio: summary.aspect_ratio,
+                name,
+                path: path_str,
+                palette: summary.palette,
+                placeholder: summary.placeholder,
+                phash: Some(phash::to_hex(summary.phash)),
+                animation,
+            });
+        }
+    }
+
+    Ok(images)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_follow_orientation() {
+        assert_eq!(components_for(4000, 3000, 4), (4, 3));
+        assert_eq!(components_for(3000, 4000, 4), (3, 4));
+        assert_eq!(components_for(1000, 1000, 4), (4, 4));
+        assert_eq!(components_for(4000, 500, 4), (4, 1));
+    }
+
+    #[test]
+    fn placeholder_keeps_aspect_ratio() {
+        assert_eq!(placeholder_dimensions(4080, 3072, 32), (32, 24));
+        assert_eq!(placeholder_dimensions(3072, 4080, 32), (24, 32));
+        assert_eq!(placeholder_dimensions(10000, 10, 32), (32, 1));
+    }
+}
@@ -1,222 +1,391 @@
-use blurhash::encode;
-use chrono::{DateTime, Utc};
-use content_service::models::{Img, JsonEntry};
-use image::{GenericImageView, ImageReader};
+mod animation;
+mod audio;
+mod config;
+mod dates;
+mod dupes;
+mod history;
+mod ignore;
+mod images;
+mod palette;
+mod phash;
+mod privacy;
+mod publish;
+mod similar;
+mod video;
+
+use animation::is_derived_file;
+use audio::{build_audio_structure, is_audio_file};
+use config::Config;
+use content_service::models::{Audio, Img, JsonEntry, SiteIndex, SiteInfo, Video, entry_types};
+use content_service::{SCHEMA_VERSION, build_archive};
+use dates::{date_from_file_name, index_timestamp, parse_content_date};
+use dupes::find_duplicates;
+use history::{FileHistory, load_history};
+use images::{build_img_structure, is_image_file};
+use publish::{PUBLISHED_CONTENT_DIR, sync_content};
 use regex::Regex;
 use serde_yaml::Value as YamlValue;
-use std::collections::HashMap;
+use similar::attach_similar;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
-
-fn is_image_file(path: &Path) -> bool {
-    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-        matches!(
-            ext.to_lowercase().as_str(),
-            "png" | "jpg" | "jpeg" | "webp" | "bmp" | "tiff" | "gif"
-        )
-    } else {
-        false
-    }
-}
+use video::{build_video_structure, is_video_file};
 
-fn encode_blurhash_and_aspect(path: &Path) -> Option<(String, String)> {
-    let reader = ImageReader::open(path).ok()?;
-    let img = reader.decode().ok()?;
-    let (width, height) = img.dimensions();
-    let rgba = img.to_rgba8();
-    let blurhash = encode(4, 3, width, height, rgba.as_raw()).ok()?;
-    if height == 0 {
+/// Type of a file's entry in the index, `None` for images, which are
+/// indexed as embeds of the documents that reference them, and for posters
+/// and renditions made from other files
+fn entry_type_for(path: &Path) -> Option<&'static str> {
+    if is_image_file(path) || is_derived_file(path) {
         return None;
     }
-    let aspect_ratio = format!("{}/{}", width, height);
-    Some((blurhash, aspect_ratio))
+    if is_audio_file(path) {
+        return Some(entry_types::AUDIO);
+    }
+    if is_video_file(path) {
+        return Some(entry_types::VIDEO);
+    }
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    Some(match extension.as_deref() {
+        Some("md" | "markdown" | "txt") => entry_types::DOCUMENT,
+        Some("pdf") => entry_types::PDF,
+        _ => entry_types::OTHER,
+    })
 }
 
-fn build_directory_structure(base: &Path) -> std::io::Result<Vec<JsonEntry>> {
-    let mut entries: Vec<JsonEntry> = Vec::new();
-    let images: Vec<Img> = build_img_structure(base).expect("Could not encode images");
+/// Whether a frontmatter field is set to true. A section readme's `pinned`
+/// is a list of entries instead, which doesn't count.
+fn frontmatter_flag(metadata: &BTreeMap<String, String>, key: &str) -> bool {
+    metadata
+        .get(key)
+        .is_some_and(|value| matches!(value.trim().to_lowercase().as_str(), "true" | "yes"))
+}
 
-    if base.is_dir() {
-        let mut dirs = vec![base.to_path_buf()];
-        while let Some(dir) = dirs.pop() {
-            for entry_res in fs::read_dir(&dir)? {
-                let entry = entry_res?;
-                let meta = entry.metadata()?;
-                let path = entry.path();
-
-                if meta.is_dir() {
-                    dirs.push(path);
-                } else if meta.is_file() && !is_image_file(&path) {
-                    let relative_path = path.strip_prefix(base).unwrap_or(&path);
-                    let path_str = format!("/{}", to_forward_slashes(relative_path));
-                    let mut name = path
-                        .file_name()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or("")
-                        .to_string();
-
-                    let file_images = find_images(path.to_str().unwrap_or(""), images.clone());
-
-                    let metadata = extract_frontmatter(path.to_str().unwrap_or(""));
-                    if metadata.contains_key("name") {
-                        name = metadata["name"].clone();
-                    }
-                    let date = if let Some(metadata_date) = metadata.get("date") {
-                        Some(metadata_date.clone())
-                    } else {
-                        meta.modified()
-                            .ok()
-                            .and_then(|time| system_time_to_iso8601(time))
-                    };
-
-                    println!("Added {}", path_str);
-                    entries.push(JsonEntry {
-                        path: path_str,
-                        entry_type: "file".to_string(),
-                        size: meta.len(),
-                        name,
-                        date,
-                        images: file_images,
-                        metadata,
-                    });
-                }
+/// Index entry for one file. Documents carry their frontmatter and embeds,
+/// audio and video files carry their own track or clip.
+fn file_entry(
+    path: &Path,
+    path_str: String,
+    entry_type: &str,
+    images: &[Img],
+    tracks: &[Audio],
+    videos: &[Video],
+) -> std::io::Result<JsonEntry> {
+    let size = fs::metadata(path)?.len();
+    let mut name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    let mut entry = JsonEntry {
+        path: path_str,
+        entry_type: entry_type.to_string(),
+        size,
+        ..Default::default()
+    };
+
+    match entry_type {
+        entry_types::DOCUMENT => {
+            let path_str = path.to_str().unwrap_or("");
+            entry.images = find_images(path_str, images.to_vec());
+            entry.audio = find_audio(path_str, tracks);
+            entry.videos = find_videos(path_str, videos);
+            entry.metadata = extract_frontmatter(path_str);
+            if let Some(metadata_name) = entry.metadata.get("name") {
+                name = metadata_name.clone();
             }
+            entry.pinned = frontmatter_flag(&entry.metadata, "pinned");
+            entry.featured = frontmatter_flag(&entry.metadata, "featured");
         }
-    } else if base.is_file() && !is_image_file(base) {
-        let meta = fs::metadata(base)?;
-        let path_str = format!("./{}", base.display());
-        let mut name = base
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_string();
-
-        let file_images = find_images(base.to_str().unwrap_or(""), images.clone());
-        let metadata = extract_frontmatter(base.to_str().unwrap_or(""));
-        if metadata.contains_key("name") {
-            name = metadata["name"].clone();
+        entry_types::AUDIO => {
+            entry.audio = tracks
+                .iter()
+                .filter(|t| t.path == entry.path)
+                .cloned()
+                .collect();
         }
-        let date = if let Some(metadata_date) = metadata.get("date") {
-            Some(metadata_date.clone())
-        } else {
-            meta.modified()
-                .ok()
-                .and_then(|time| system_time_to_iso8601(time))
-        };
+        entry_types::VIDEO => {
+            entry.videos = videos
+                .iter()
+                .filter(|v| v.path == entry.path)
+                .cloned()
+                .collect();
+        }
+        _ => {}
+    }
+    entry.name = name;
+    entry.date = entry_date(&entry.metadata, path);
+
+    println!("Added {} ({})", entry.path, entry_type);
+    Ok(entry)
+}
+
+/// The `readme.md` directly inside the directory at `dir_path`
+fn find_readme<'a>(files: &'a [JsonEntry], dir_path: &str) -> Option<&'a JsonEntry> {
+    files.iter().find(|entry| {
+        entry.path.rsplit_once('/').is_some_and(|(parent, name)| {
+            parent == dir_path && name.eq_ignore_ascii_case("readme.md")
+        })
+    })
+}
 
-        println!("Added {}", path_str);
-        entries.push(JsonEntry {
-            path: path_str,
-            entry_type: "file".to_string(),
-            size: meta.len(),
-            name,
-            date,
-            images: file_images,
-            metadata,
-        });
+/// Title of a directory: the readme's `title` or `name` frontmatter, else its
+/// first `# ` heading, else the directory's own name
+fn readme_title(base: &Path, readme: Option<&JsonEntry>, dir_path: &str) -> String {
+    if let Some(readme) = readme {
+        if let Some(title) = readme.metadata.get("title").or(readme.metadata.get("name")) {
+            return title.clone();
+        }
+        let heading = fs::read_to_string(base.join(readme.path.trim_start_matches('/')))
+            .ok()
+            .and_then(|text| {
+                text.lines()
+                    .find_map(|line| line.strip_prefix("# ").map(|h| h.trim().to_string()))
+            });
+        if let Some(heading) = heading {
+            return heading;
+        }
     }
+    dir_path.rsplit('/').next().unwrap_or("").to_string()
+}
 
-    Ok(entries)
+/// An entry for every directory holding indexed files, with its readme title,
+/// total size, the newest date inside it and counts of its direct children.
+/// The readme's frontmatter is carried over as the section's settings.
+fn directory_entries(base: &Path, files: &[JsonEntry]) -> Vec<JsonEntry> {
+    let parent_of = |path: &str| path.rsplit_once('/').map(|(parent, _)| parent.to_string());
+
+    let mut dirs: BTreeSet<String> = BTreeSet::new();
+    for file in files {
+        let mut current = parent_of(&file.path);
+        while let Some(dir) = current.filter(|dir| !dir.is_empty()) {
+            current = parent_of(&dir);
+            dirs.insert(dir);
+        }
+    }
+
+    dirs.iter()
+        .map(|dir_path| {
+            let prefix = format!("{}/", dir_path);
+            let inside: Vec<&JsonEntry> = files
+                .iter()
+                .filter(|file| file.path.starts_with(&prefix))
+                .collect();
+
+            let mut children: BTreeMap<String, u32> = BTreeMap::new();
+            for file in &inside {
+                if parent_of(&file.path).as_deref() == Some(dir_path) {
+                    *children.entry(file.entry_type.clone()).or_default() += 1;
+                }
+            }
+            for dir in &dirs {
+                if parent_of(dir).as_deref() == Some(dir_path) {
+                    *children
+                        .entry(entry_types::DIRECTORY.to_string())
+                        .or_default() += 1;
+                }
+            }
+
+            let readme = find_readme(files, dir_path);
+            JsonEntry {
+                path: dir_path.clone(),
+                entry_type: entry_types::DIRECTORY.to_string(),
+                size: inside.iter().map(|file| file.size).sum(),
+                name: readme_title(base, readme, dir_path),
+                date: inside
+                    .iter()
+                    .filter_map(|file| file.date.as_deref().and_then(parse_content_date))
+                    .max()
+                    .map(|day| day.format("%Y-%m-%d").to_string()),
+                metadata: readme.map(|r| r.metadata.clone()).unwrap_or_default(),
+                children,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Fill in when each entry was first and last committed. Files without a
+/// frontmatter or file name date are dated by their first commit.
+fn apply_history(
+    entries: &mut [JsonEntry],
+    history: &HashMap<String, FileHistory>,
+    changelog: usize,
+) {
+    for entry in entries.iter_mut() {
+        if let Some(file_history) = history.get(&entry.path) {
+            entry.created = Some(file_history.created.clone());
+            entry.updated = Some(file_history.updated.clone());
+            entry.changes = file_history
+                .changes
+                .iter()
+                .take(changelog)
+                .cloned()
+                .collect();
+            if entry.date.is_none() {
+                entry.date = entry.created.clone();
+            }
+        }
+    }
 }
 
-fn build_img_structure(base: &Path) -> std::io::Result<Vec<Img>> {
-    let mut images: Vec<Img> = Vec::new();
+/// Index `base`. Git history is read from `history_dir`, which is `base`
+/// unless it is a published copy of a checked out content directory. Posters
+/// and renditions are written under `derived_dir`.
+fn build_directory_structure(
+    base: &Path,
+    history_dir: &Path,
+    derived_dir: &Path,
+    config: &Config,
+) -> std::io::Result<Vec<JsonEntry>> {
+    let mut entries: Vec<JsonEntry> = Vec::new();
+    let mut images: Vec<Img> = build_img_structure(base, Some(derived_dir), &config.images)
+        .expect("Could not encode images");
+    let mut tracks: Vec<Audio> = build_audio_structure(base).expect("Could not decode audio");
+    let mut videos: Vec<Video> =
+        build_video_structure(base, derived_dir, &config.images).expect("Could not probe videos");
+    // Walks follow `read_dir` order, which differs between machines. Sorting
+    // also settles which file wins when two embeds share a name.
+    images.sort_by(|a, b| a.path.cmp(&b.path));
+    tracks.sort_by(|a, b| a.path.cmp(&b.path));
+    videos.sort_by(|a, b| a.path.cmp(&b.path));
 
     if base.is_dir() {
-        let mut dirs = vec![base.to_path_buf()];
-        while let Some(dir) = dirs.pop() {
-            for entry_res in fs::read_dir(&dir)? {
-                let entry = entry_res?;
-                let meta = entry.metadata()?;
-                let path = entry.path();
-
-                if meta.is_dir() {
-                    dirs.push(path);
-                } else if meta.is_file() && is_image_file(&path) {
-                    let relative_path = path.strip_prefix(base).unwrap_or(&path);
-                    let path_str = format!("/{}", to_forward_slashes(relative_path));
-                    let name = path
-                        .file_name()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or("")
-                        .to_string();
-
-                    // Get blurhash and aspect ratio
-                    if let Some((blurhash, aspect_ratio)) = encode_blurhash_and_aspect(&path) {
-                        println!("Image added: {}", path_str);
-                        images.push(Img {
-                            blurhash,
-                            aspect_ratio,
-                            name,
-                            path: path_str,
-                        });
-                    }
-                    // Skip images that couldn't be processed
-                }
+        for path in ignore::content_files(base)? {
+            if let Some(entry_type) = entry_type_for(&path) {
+                let relative_path = path.strip_prefix(base).unwrap_or(&path);
+                let path_str = format!("/{}", to_forward_slashes(relative_path));
+                entries.push(file_entry(
+                    &path, path_str, entry_type, &images, &tracks, &videos,
+                )?);
             }
         }
-    } else if base.is_file() && is_image_file(base) {
-        // Handle single image file case
-        let url = format!("{}", base.display());
-        let path_str = format!("/{}", to_forward_slashes(url));
-
-        let name = base
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_string();
-
-        if let Some((blurhash, aspect_ratio)) = encode_blurhash_and_aspect(base) {
-            images.push(Img {
-                blurhash,
-                aspect_ratio,
-                name,
-                path: path_str,
-            });
+        if config.history.enabled {
+            apply_history(
+                &mut entries,
+                &load_history(history_dir),
+                config.history.changelog,
+            );
         }
+        entries.extend(directory_entries(base, &entries));
+    } else if base.is_file()
+        && let Some(entry_type) = entry_type_for(base)
+    {
+        let path_str = format!("./{}", base.display());
+        entries.push(file_entry(
+            base, path_str, entry_type, &images, &tracks, &videos,
+        )?);
     }
 
-    Ok(images)
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    attach_similar(&mut entries, &config.similar);
+
+    Ok(entries)
 }
 
-fn find_images(path: &str, img_store: Vec<Img>) -> Vec<Img> {
-    let mut images: Vec<Img> = Vec::new();
+fn build_site_index(
+    base: &Path,
+    history_dir: &Path,
+    derived_dir: &Path,
+    config: &Config,
+) -> std::io::Result<SiteIndex> {
+    let entries = build_directory_structure(base, history_dir, derived_dir, config)?;
+    Ok(SiteIndex {
+        schema: Some(content_service::schema::SCHEMA_PATH.to_string()),
+        schema_version: SCHEMA_VERSION,
+        generated_at: index_timestamp(&entries),
+        site: SiteInfo {
+            name: config.site.name.clone(),
+            base_url: config.site.base_url.clone(),
+            description: config.site.description.clone(),
+        },
+        archive: build_archive(&entries),
+        entries,
+    })
+}
 
+/// The frontmatter date, else one written into the file name. Filesystem
+/// timestamps change on checkout so they are never used, the first commit
+/// is the fallback when the content is in git.
+fn entry_date(metadata: &BTreeMap<String, String>, path: &Path) -> Option<String> {
+    if let Some(metadata_date) = metadata.get("date") {
+        return Some(metadata_date.clone());
+    }
+    path.file_name()
+        .and_then(|s| s.to_str())
+        .and_then(date_from_file_name)
+        .map(|day| day.format("%Y-%m-%d").to_string())
+}
+
+fn find_images(path: &str, img_store: Vec<Img>) -> Vec<Img> {
     // Create a lookup map by image name for O(1) access
     let img_map: HashMap<String, &Img> = img_store
         .iter()
         .map(|img| (img.name.clone(), img))
         .collect();
 
+    find_embed_names(path)
+        .iter()
+        .filter_map(|name| img_map.get(name).map(|img| (*img).clone()))
+        .collect()
+}
+
+fn find_audio(path: &str, audio_store: &[Audio]) -> Vec<Audio> {
+    let audio_map: HashMap<&str, &Audio> = audio_store
+        .iter()
+        .map(|track| (track.name.as_str(), track))
+        .collect();
+
+    find_embed_names(path)
+        .iter()
+        .filter_map(|name| audio_map.get(name.as_str()).map(|track| (*track).clone()))
+        .collect()
+}
+
+fn find_videos(path: &str, video_store: &[Video]) -> Vec<Video> {
+    let video_map: HashMap<&str, &Video> = video_store
+        .iter()
+        .map(|video| (video.name.as_str(), video))
+        .collect();
+
+    find_embed_names(path)
+        .iter()
+        .filter_map(|name| video_map.get(name.as_str()).map(|video| (*video).clone()))
+        .collect()
+}
+
+/// Unique `![[filename]]` embeds in a document, in the order they first appear
+fn find_embed_names(path: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+
     // Read the file content
     if let Ok(content) = fs::read_to_string(path) {
         // Regex to match ![[filename]] pattern
         let re = Regex::new(r"!\[\[([^\]]+)\]\]").unwrap();
 
-        // Find all matches and collect unique image names
+        // Find all matches and collect unique embed names
         let mut found_names: std::collections::HashSet<String> = std::collections::HashSet::new();
 
         for capture in re.captures_iter(&content) {
             if let Some(filename) = capture.get(1) {
                 let filename_str = filename.as_str().to_string();
                 if found_names.insert(filename_str.clone()) {
-                    // Only process if we haven't seen this filename before
-                    if let Some(img) = img_map.get(&filename_str) {
-                        images.push((*img).clone());
-                    }
+                    names.push(filename_str);
                 }
             }
         }
     }
 
-    images
+    names
 }
 
-fn extract_frontmatter(file_path: &str) -> HashMap<String, String> {
-    let mut metadata = HashMap::new();
+fn extract_frontmatter(file_path: &str) -> BTreeMap<String, String> {
+    let mut metadata = BTreeMap::new();
 
     let content = match fs::read_to_string(file_path) {
         Ok(content) => content,
@@ -236,45 +405,45 @@ fn extract_frontmatter(file_path: &str) -> HashMap<String, String> {
         }
     };
 
-    if let Some(captures) = re.captures(&content) {
-        if let Some(yaml_content) = captures.get(1) {
-            let yaml_str = yaml_content.as_str().trim();
-            match serde_yaml::from_str::<YamlValue>(yaml_str) {
-                Ok(yaml_value) => {
-                    if let YamlValue::Mapping(map) = yaml_value {
-                        for (key, value) in map {
-                            if let YamlValue::String(k) = key {
-                                let value_str = match value {
-                                    YamlValue::String(s) => s,
-                                    YamlValue::Number(n) => n.to_string(),
-                                    YamlValue::Bool(b) => b.to_string(),
-                                    YamlValue::Null => "null".to_string(),
-                                    _ => format!("{:?}", value),
-                                };
-                                println!("Adding metadata: {} = {}", k, value_str);
-                                metadata.insert(k, value_str);
-                            }
+    if let Some(captures) = re.captures(&content)
+        && let Some(yaml_content) = captures.get(1)
+    {
+        let yaml_str = yaml_content.as_str().trim();
+        match serde_yaml::from_str::<YamlValue>(yaml_str) {
+            Ok(yaml_value) => {
+                if let YamlValue::Mapping(map) = yaml_value {
+                    for (key, value) in map {
+                        if let YamlValue::String(k) = key {
+                            let value_str = match value {
+                                YamlValue::String(s) => s,
+                                YamlValue::Number(n) => n.to_string(),
+                                YamlValue::Bool(b) => b.to_string(),
+                                YamlValue::Null => "null".to_string(),
+                                _ => format!("{:?}", value),
+                            };
+                            println!("Adding metadata: {} = {}", k, value_str);
+                            metadata.insert(k, value_str);
                         }
                     }
                 }
-                Err(e) => {
-                    println!("Failed to parse YAML in {}: {}", file_path, e);
-                    for line in yaml_str.lines() {
-                        let line = line.trim();
-                        if line.is_empty() || line.starts_with('#') {
-                            continue;
-                        }
+            }
+            Err(e) => {
+                println!("Failed to parse YAML in {}: {}", file_path, e);
+                for line in yaml_str.lines() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
 
-                        if let Some((key, value)) = line.split_once(':') {
-                            let key = key.trim().to_string();
-                            let value = value
-                                .trim()
-                                .trim_matches('"')
-                                .trim_matches('\'')
-                                .to_string();
-                            println!("Fallback parsing: {} = {}", key, value);
-                            metadata.insert(key, value);
-                        }
+                    if let Some((key, value)) = line.split_once(':') {
+                        let key = key.trim().to_string();
+                        let value = value
+                            .trim()
+                            .trim_matches('"')
+                            .trim_matches('\'')
+                            .to_string();
+                        println!("Fallback parsing: {} = {}", key, value);
+                        metadata.insert(key, value);
                     }
                 }
             }
@@ -285,60 +454,162 @@ fn extract_frontmatter(file_path: &str) -> HashMap<String, String> {
 
 fn print_usage(program_name: &str) {
     eprintln!(
-        "Usage: {} --content <content_dir> --out <output_dir>",
+        "Usage: {} [build] --content <content_dir> --out <output_dir> [--config <file>]",
         program_name
     );
+    eprintln!(
+        "       {} dupes --content <content_dir> [--config <file>]",
+        program_name
+    );
+    eprintln!(
+        "       {} check --content <content_dir> [--config <file>] [--fail-on-dupes] [--published <dir>]",
+        program_name
+    );
+    eprintln!(
+        "       {} publish --content <content_dir> --out <output_dir> [--config <file>] [--dry-run]",
+        program_name
+    );
+    eprintln!(
+        "       {} transcode --input <file> [--input <file> ...] [--format mp4|webp]",
+        program_name
+    );
+    eprintln!(
+        "  --config defaults to '{}' in the working directory when present",
+        config::DEFAULT_CONFIG_FILE
+    );
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    /// Write the site index
+    Build,
+    /// Report duplicate images
+    Dupes,
+    /// Validate the content, exiting non-zero on errors
+    Check,
+    /// Sync the content for serving with private metadata stripped, then index it
+    Publish,
+    /// Poster and rendition for animated files outside the content, e.g. the logo
+    Transcode,
+}
+
+struct Args {
+    command: Command,
+    content_dir: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    config_path: Option<PathBuf>,
+    fail_on_dupes: bool,
+    published_dir: Option<PathBuf>,
+    dry_run: bool,
+    inputs: Vec<PathBuf>,
+    format: Option<String>,
+}
 
-    let mut content_dir: Option<PathBuf> = None;
-    let mut output_dir: Option<PathBuf> = None;
+fn parse_args(args: &[String]) -> Option<Args> {
+    let mut parsed = Args {
+        command: Command::Build,
+        content_dir: None,
+        output_dir: None,
+        config_path: None,
+        fail_on_dupes: false,
+        published_dir: None,
+        dry_run: false,
+        inputs: Vec::new(),
+        format: None,
+    };
 
     let mut i = 1;
+    // An optional leading subcommand, plain flags keep meaning `build`
+    if let Some(first) = args.get(1) {
+        let command = match first.as_str() {
+            "build" => Some(Command::Build),
+            "dupes" => Some(Command::Dupes),
+            "check" => Some(Command::Check),
+            "publish" => Some(Command::Publish),
+            "transcode" => Some(Command::Transcode),
+            _ => None,
+        };
+        if let Some(command) = command {
+            parsed.command = command;
+            i = 2;
+        }
+    }
+
     while i < args.len() {
         match args[i].as_str() {
-            "--content" => {
-                if i + 1 < args.len() {
-                    content_dir = Some(PathBuf::from(&args[i + 1]));
-                    i += 2;
-                } else {
-                    print_usage(&args[0]);
-                    return;
-                }
+            "--content" if i + 1 < args.len() => {
+                parsed.content_dir = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
             }
-            "--out" => {
-                if i + 1 < args.len() {
-                    output_dir = Some(PathBuf::from(&args[i + 1]));
-                    i += 2;
-                } else {
-                    print_usage(&args[0]);
-                    return;
-                }
+            "--out" if i + 1 < args.len() => {
+                parsed.output_dir = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
             }
-            _ => {
-                print_usage(&args[0]);
-                return;
+            "--config" if i + 1 < args.len() => {
+                parsed.config_path = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
+            }
+            "--fail-on-dupes" if parsed.command == Command::Check => {
+                parsed.fail_on_dupes = true;
+                i += 1;
+            }
+            "--published" if parsed.command == Command::Check && i + 1 < args.len() => {
+                parsed.published_dir = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
             }
+            "--dry-run" if parsed.command == Command::Publish => {
+                parsed.dry_run = true;
+                i += 1;
+            }
+            "--input" if parsed.command == Command::Transcode && i + 1 < args.len() => {
+                parsed.inputs.push(PathBuf::from(&args[i + 1]));
+                i += 2;
+            }
+            "--format" if parsed.command == Command::Transcode && i + 1 < args.len() => {
+                parsed.format = Some(args[i + 1].clone());
+                i += 2;
+            }
+            _ => return None,
         }
     }
 
-    let content_dir = match content_dir {
+    Some(parsed)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let Some(args_parsed) = parse_args(&args) else {
+        print_usage(&args[0]);
+        std::process::exit(1);
+    };
+
+    if args_parsed.command == Command::Transcode {
+        if args_parsed.inputs.is_empty() {
+            eprintln!("Error: --input argument is required.");
+            print_usage(&args[0]);
+            std::process::exit(1);
+        }
+        if !run_transcode(&args_parsed.inputs, args_parsed.format.as_deref()) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let content_dir = match args_parsed.content_dir {
         Some(p) => p,
         None => {
             eprintln!("Error: --content argument is required.");
             print_usage(&args[0]);
-            return;
+            std::process::exit(1);
         }
     };
 
-    let output_dir = match output_dir {
-        Some(p) => p,
-        None => {
-            eprintln!("Error: --out argument is required.");
-            print_usage(&args[0]);
-            return;
+    let config = match Config::load(args_parsed.config_path.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
         }
     };
 
@@ -347,57 +618,374 @@ fn main() {
             "Error: content directory '{}' does not exist.",
             content_dir.display()
         );
-        return;
+        std::process::exit(1);
+    }
+
+    // Scripts run under `set -e` stop here rather than deploy what's left
+    let result = match args_parsed.command {
+        Command::Build => match args_parsed.output_dir {
+            Some(output_dir) => run_build(&content_dir, &content_dir, &output_dir, &config),
+            None => {
+                print_usage(&args[0]);
+                Err("--out argument is required.".to_string())
+            }
+        },
+        Command::Dupes => run_dupes(&content_dir, &config),
+        Command::Publish => match args_parsed.output_dir {
+            Some(output_dir) => {
+                run_publish(&content_dir, &output_dir, &config, args_parsed.dry_run)
+            }
+            None => {
+                print_usage(&args[0]);
+                Err("--out argument is required.".to_string())
+            }
+        },
+        Command::Transcode => unreachable!("handled before loading the content"),
+        Command::Check => run_check(
+            &content_dir,
+            &config,
+            args_parsed.fail_on_dupes,
+            args_parsed.published_dir.as_deref(),
+        ),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Write a poster, and a rendition when `format` is given, for each animated
+/// input. Returns false when an input isn't animated or couldn't be processed.
+fn run_transcode(inputs: &[PathBuf], format: Option<&str>) -> bool {
+    let format = match format.map(animation::TranscodeFormat::parse) {
+        Some(None) => {
+            eprintln!("Error: --format should be \"mp4\" or \"webp\".");
+            return false;
+        }
+        Some(format) => format,
+        None => None,
+    };
+
+    let mut ok = true;
+    for input in inputs {
+        match animation::process_animation(input, input, format, |p| to_forward_slashes(p)) {
+            Some(found) => {
+                println!(
+                    "{}: {} frames, {}ms",
+                    input.display(),
+                    found.frames,
+                    found.duration_ms
+                );
+                if let Some(poster) = found.poster {
+                    println!("  poster: {}", poster);
+                }
+                match found.transcoded {
+                    Some(transcoded) => println!("  transcoded: {}", transcoded),
+                    None if format.is_some() => ok = false,
+                    None => {}
+                }
+            }
+            None => {
+                eprintln!("Error: '{}' is not an animated image.", input.display());
+                ok = false;
+            }
+        }
     }
+    ok
+}
 
+fn run_build(
+    content_dir: &Path,
+    history_dir: &Path,
+    output_dir: &Path,
+    config: &Config,
+) -> Result<(), String> {
     if !output_dir.exists() {
-        if let Err(e) = fs::create_dir_all(&output_dir) {
-            eprintln!(
-                "Error: failed to create output directory '{}': {}",
+        fs::create_dir_all(output_dir).map_err(|e| {
+            format!(
+                "failed to create output directory '{}': {}",
                 output_dir.display(),
                 e
-            );
-            return;
-        }
+            )
+        })?;
     }
 
-    match build_directory_structure(&content_dir) {
-        Ok(entries) => {
-            let json = match serde_json::to_string_pretty(&entries) {
-                Ok(j) => j,
-                Err(e) => {
-                    eprintln!("Error serializing directory structure to JSON: {}", e);
-                    return;
-                }
-            };
-
-            let output_path = output_dir.join("directory_structure.json");
-            match File::create(&output_path) {
-                Ok(mut file) => {
-                    if let Err(e) = file.write_all(json.as_bytes()) {
-                        eprintln!("Error writing to '{}': {}", output_path.display(), e);
-                        return;
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error creating file '{}': {}", output_path.display(), e);
-                    return;
+    // The site serves files from the output's content directory, which is
+    // `content_dir` itself when publishing
+    let derived_dir = output_dir.join(PUBLISHED_CONTENT_DIR);
+    let index = build_site_index(content_dir, history_dir, &derived_dir, config)
+        .map_err(|e| format!("building directory structure: {}", e))?;
+    let json = serde_json::to_string_pretty(&index)
+        .map_err(|e| format!("serializing directory structure to JSON: {}", e))?;
+
+    let output_path = output_dir.join("directory_structure.json");
+    let mut file = File::create(&output_path)
+        .map_err(|e| format!("creating file '{}': {}", output_path.display(), e))?;
+    file.write_all(json.as_bytes())
+        .map_err(|e| format!("writing to '{}': {}", output_path.display(), e))?;
+
+    println!("Directory structure saved to '{}'.", output_path.display());
+    Ok(())
+}
+
+fn run_dupes(content_dir: &Path, config: &Config) -> Result<(), String> {
+    let images = build_img_structure(content_dir, None, &config.images)
+        .map_err(|e| format!("reading images: {}", e))?;
+    find_duplicates(content_dir, &images, config.images.duplicate_distance).print();
+    Ok(())
+}
+
+/// Sync the content into `output_dir`'s content directory, then index the
+/// published copy in the same run
+fn run_publish(
+    content_dir: &Path,
+    output_dir: &Path,
+    config: &Config,
+    dry_run: bool,
+) -> Result<(), String> {
+    let published_dir = output_dir.join(PUBLISHED_CONTENT_DIR);
+    let (summary, report) = sync_content(content_dir, &published_dir, &config.privacy, dry_run)
+        .map_err(|e| format!("publishing content: {}", e))?;
+    summary.print(dry_run);
+    for withheld in &report.withheld {
+        eprintln!(
+            "Warning: {} was not published, its location ({}) can't be stripped from this format.",
+            withheld.path,
+            withheld.location.join(", ")
+        );
+    }
+    if dry_run {
+        println!(
+            "Metadata would be stripped from {} files, nothing was written.",
+            report.files.len()
+        );
+        return Ok(());
+    }
+
+    // The report holds the stripped values, so it stays beside the config
+    // rather than in the published tree
+    let report_path = &config.privacy.report;
+    let json = serde_json::to_string_pretty(&report)
+        .map_err(|e| format!("serializing privacy report to JSON: {}", e))?;
+    fs::write(report_path, json)
+        .map_err(|e| format!("writing to '{}': {}", report_path.display(), e))?;
+    println!(
+        "Metadata stripped from {} files (see '{}').",
+        report.files.len(),
+        report_path.display()
+    );
+
+    // The published copy isn't committed, history comes from the source
+    run_build(&published_dir, content_dir, output_dir, config)
+}
+
+/// Published images that still carry a location, as (path, tags)
+fn find_published_locations(published_dir: &Path) -> std::io::Result<Vec<(String, Vec<String>)>> {
+    let mut found = Vec::new();
+    let mut dirs = vec![published_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry_res in fs::read_dir(&dir)? {
+            let entry = entry_res?;
+            let path = entry.path();
+            if entry.metadata()?.is_dir() {
+                dirs.push(path);
+            } else if is_image_file(&path) {
+                let tags = privacy::location_data(&fs::read(&path)?);
+                if !tags.is_empty() {
+                    let relative_path = path.strip_prefix(published_dir).unwrap_or(&path);
+                    found.push((format!("/{}", to_forward_slashes(relative_path)), tags));
                 }
             }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
 
-            println!("Directory structure saved to '{}'.", output_path.display());
+/// Print every warning found, failing with the problems that should stop a
+/// deploy
+fn run_check(
+    content_dir: &Path,
+    config: &Config,
+    fail_on_dupes: bool,
+    published_dir: Option<&Path>,
+) -> Result<(), String> {
+    let mut failures: Vec<String> = Vec::new();
+
+    match build_img_structure(content_dir, None, &config.images) {
+        Ok(images) => {
+            let report = find_duplicates(content_dir, &images, config.images.duplicate_distance);
+            if !report.is_empty() {
+                let message = format!(
+                    "{} exact and {} near duplicate image clusters, run `dupes` for details",
+                    report.exact.len(),
+                    report.near.len()
+                );
+                if fail_on_dupes {
+                    failures.push(message);
+                } else {
+                    eprintln!("Warning: {}.", message);
+                }
+            }
         }
-        Err(e) => {
-            eprintln!("Error building directory structure: {}", e);
+        Err(e) => failures.push(format!("reading images: {}", e)),
+    }
+
+    if let Some(published_dir) = published_dir {
+        match find_published_locations(published_dir) {
+            Ok(found) => {
+                for (path, tags) in found {
+                    eprintln!(
+                        "Warning: published {} contains location data ({}), run `publish` again.",
+                        path,
+                        tags.join(", ")
+                    );
+                }
+            }
+            Err(e) => failures.push(format!("reading published content: {}", e)),
         }
     }
-}
 
-fn system_time_to_iso8601(time: SystemTime) -> Option<String> {
-    let datetime: DateTime<Utc> = time.into();
-    Some(datetime.to_rfc3339())
+    if !failures.is_empty() {
+        return Err(format!("check failed: {}", failures.join("; ")));
+    }
+    println!("Check passed.");
+    Ok(())
 }
 
 fn to_forward_slashes<P: AsRef<Path>>(path: P) -> String {
     path.as_ref().to_string_lossy().replace('\\', "/")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// Same content written in opposite orders, so the two trees differ in
+    /// `read_dir` order and timestamps
+    fn write_content(base: &Path, reverse: bool) {
+        let mut files: Vec<(&str, &str)> = vec![
+            (
+                "blog/first.md",
+                "---\nname: First\ntags: [Blog, Notes]\ndate: 2024-01-02\nauthor: me\n---\n![[shot.png]]",
+            ),
+            (
+                "blog/2023-05-06-second.md",
+                "---\nzeta: 1\nalpha: 2\n---\nbody",
+            ),
+            ("pictures/readme.md", "# Pictures"),
+            ("readme.md", "# Home"),
+        ];
+        if reverse {
+            files.reverse();
+        }
+        for (path, text) in files {
+            let path = base.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        RgbImage::from_fn(12, 8, |x, y| Rgb([(x * 20) as u8, (y * 30) as u8, 90]))
+            .save(base.join("blog/shot.png"))
+            .unwrap();
+    }
+
+    #[test]
+    fn index_is_byte_identical_across_trees() {
+        let root = std::env::temp_dir().join(format!("olifm-index-{}", std::process::id()));
+        let (a, b) = (root.join("a"), root.join("b"));
+        write_content(&a, false);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write_content(&b, true);
+
+        let config = Config::default();
+        let index = |base: &Path| {
+            let index = build_site_index(base, base, base, &config).unwrap();
+            serde_json::to_string_pretty(&index).unwrap()
+        };
+        let first = index(&a);
+        assert_eq!(first, index(&b));
+        assert_eq!(first, index(&a));
+
+        let index = content_service::parse_site_index(&first).unwrap();
+        assert_eq!(index.generated_at.as_deref(), Some("2024-01-02T00:00:00Z"));
+        let entries = index.entries;
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/blog",
+                "/blog/2023-05-06-second.md",
+                "/blog/first.md",
+                "/pictures",
+                "/pictures/readme.md",
+                "/readme.md"
+            ]
+        );
+        assert_eq!(entries[1].date.as_deref(), Some("2023-05-06"));
+        assert_eq!(entries[4].date, None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn classifies_files_and_summarises_directories() {
+        let base = std::env::temp_dir().join(format!("olifm-types-{}", std::process::id()));
+        for (path, text) in [
+            (
+                "notes/readme.md",
+                "---\ntitle: Field notes\nlayout: timeline\n---\nbody",
+            ),
+            (
+                "notes/2024-03-01-walk.md",
+                "---\npinned: true\nfeatured: yes\n---\nwalk",
+            ),
+            ("notes/2024-05-09-cv.pdf", "%PDF"),
+            ("notes/drafts/idea.md", "idea"),
+            ("notes/drafts/scratch.tmp", "scratch"),
+            ("notes/.DS_Store", "junk"),
+            (".obsidian/workspace.json", "{}"),
+            (".olifmignore", "*.tmp\n"),
+        ] {
+            let path = base.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        let entries = build_directory_structure(&base, &base, &base, &Config::default()).unwrap();
+        let types: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.entry_type.as_str()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("/notes", "directory"),
+                ("/notes/2024-03-01-walk.md", "document"),
+                ("/notes/2024-05-09-cv.pdf", "pdf"),
+                ("/notes/drafts", "directory"),
+                ("/notes/drafts/idea.md", "document"),
+                ("/notes/readme.md", "document"),
+            ]
+        );
+
+        let notes = &entries[0];
+        assert_eq!(notes.name, "Field notes");
+        assert_eq!(notes.metadata["layout"], "timeline");
+        assert_eq!(notes.date.as_deref(), Some("2024-05-09"));
+        let children: Vec<(&str, u32)> = notes
+            .children
+            .iter()
+            .map(|(t, n)| (t.as_str(), *n))
+            .collect();
+        assert_eq!(
+            children,
+            vec![("directory", 1), ("document", 2), ("pdf", 1)]
+        );
+        assert_eq!(entries[3].name, "drafts");
+        assert!(entries[1].pinned && entries[1].featured);
+        assert!(!entries[4].pinned && !entries[4].featured);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
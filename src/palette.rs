@@ -0,0 +1,106 @@
+use image::DynamicImage;
+use std::collections::HashMap;
+
+/// Dominant colour followed by up to four accents
+const PALETTE_SIZE: usize = 5;
+
+/// Images are shrunk to fit this box before counting, the palette only needs
+/// the broad strokes
+const SAMPLE_SIZE: u32 = 64;
+
+/// Minimum RGB distance between palette entries so accents don't just repeat
+/// a shade of the dominant colour
+const MIN_DISTANCE: f32 = 48.0;
+
+/// Extract a palette as `#rrggbb` strings, most common colour first
+pub fn extract_palette(img: &DynamicImage) -> Vec<String> {
+    let thumb = if img.width() > SAMPLE_SIZE || img.height() > SAMPLE_SIZE {
+        img.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).to_rgb8()
+    } else {
+        img.to_rgb8()
+    };
+
+    // Bucket pixels by the top four bits of each channel, remembering the
+    // running sum so each bucket resolves to its average colour
+    let mut buckets: HashMap<u16, (u32, [u64; 3])> = HashMap::new();
+    for px in thumb.pixels() {
+        let [r, g, b] = px.0;
+        let key = ((r as u16 >> 4) << 8) | ((g as u16 >> 4) << 4) | (b as u16 >> 4);
+        let bucket = buckets.entry(key).or_insert((0, [0; 3]));
+        bucket.0 += 1;
+        bucket.1[0] += r as u64;
+        bucket.1[1] += g as u64;
+        bucket.1[2] += b as u64;
+    }
+
+    let mut ranked: Vec<(u16, u32, [u8; 3])> = buckets
+        .into_iter()
+        .map(|(key, (count, sum))| {
+            let avg = [
+                (sum[0] / count as u64) as u8,
+                (sum[1] / count as u64) as u8,
+                (sum[2] / count as u64) as u8,
+            ];
+            (key, count, avg)
+        })
+        .collect();
+    // Ties broken by bucket so the output doesn't depend on hash order
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    for (_, _, colour) in ranked {
+        if palette
+            .iter()
+            .all(|picked| distance(picked, &colour) >= MIN_DISTANCE)
+        {
+            palette.push(colour);
+            if palette.len() == PALETTE_SIZE {
+                break;
+            }
+        }
+    }
+
+    palette
+        .iter()
+        .map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b))
+        .collect()
+}
+
+fn distance(a: &[u8; 3], b: &[u8; 3]) -> f32 {
+    let dr = a[0] as f32 - b[0] as f32;
+    let dg = a[1] as f32 - b[1] as f32;
+    let db = a[2] as f32 - b[2] as f32;
+    (dr * dr + dg * dg + db * db).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn dominant_colour_comes_first() {
+        let img = RgbImage::from_fn(10, 10, |x, _| {
+            if x < 7 {
+                Rgb([200, 20, 20])
+            } else {
+                Rgb([20, 20, 200])
+            }
+        });
+        let palette = extract_palette(&DynamicImage::ImageRgb8(img));
+        assert_eq!(palette, vec!["#c81414", "#1414c8"]);
+    }
+
+    #[test]
+    fn near_identical_shades_collapse() {
+        let img = RgbImage::from_fn(10, 10, |x, _| {
+            if x < 5 {
+                Rgb([100, 100, 100])
+            } else {
+                Rgb([112, 112, 112])
+            }
+        });
+        let palette = extract_palette(&DynamicImage::ImageRgb8(img));
+        assert_eq!(palette.len(), 1);
+    }
+}
@@ -0,0 +1,68 @@
+use image::DynamicImage;
+use image::imageops::FilterType;
+
+/// 64-bit difference hash: each bit says whether a pixel of a 9x8 greyscale
+/// thumbnail is brighter than its right-hand neighbour. Resizing, recompression
+/// and small edits only flip a few bits.
+pub fn perceptual_hash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y).0[0];
+            let right = small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | (left > right) as u64;
+        }
+    }
+    hash
+}
+
+pub fn to_hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+pub fn from_hex(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex, 16).ok()
+}
+
+/// Number of differing bits, 0 means visually identical
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn gradient(width: u32, height: u32, offset: u8) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let v = ((x * 255 / width) as u8).saturating_add(offset);
+            Rgb([v, v / 2, ((y * 255) / height) as u8])
+        }))
+    }
+
+    #[test]
+    fn resized_and_brightened_copies_stay_close() {
+        let original = perceptual_hash(&gradient(400, 300, 0));
+        let resized = perceptual_hash(&gradient(200, 150, 0));
+        let brighter = perceptual_hash(&gradient(400, 300, 20));
+        assert!(distance(original, resized) <= 4);
+        assert!(distance(original, brighter) <= 4);
+    }
+
+    #[test]
+    fn mirrored_image_is_far_away() {
+        let original = gradient(400, 300, 0);
+        let mirrored = original.fliph();
+        assert!(distance(perceptual_hash(&original), perceptual_hash(&mirrored)) > 20);
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(
+            from_hex(&to_hex(0x00ff_1234_abcd_0001)),
+            Some(0x00ff_1234_abcd_0001)
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use exif::experimental::Writer;
+use exif::{Context, Exif, Field, In, Reader, Tag};
+use img_parts::jpeg::markers;
+use img_parts::{Bytes, DynImage, ImageEXIF};
+use serde::Serialize;
+use std::io::Cursor;
+
+use crate::config::PrivacyConfig;
+
+/// Start of a JPEG APP1 segment holding XMP rather than EXIF
+const XMP_JPEG_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Keyword of the PNG iTXt chunk holding XMP
+const XMP_PNG_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+/// XMP properties that place a photo on a map
+const XMP_LOCATION_PROPERTIES: [&[u8]; 3] = [b"GPSLatitude", b"GPSLongitude", b"LocationShown"];
+
+/// Longest value kept in the report, maker notes run to kilobytes of hex
+const MAX_REPORT_VALUE: usize = 96;
+
+/// A piece of metadata taken out of a published copy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RemovedField {
+    pub tag: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    pub removed: Vec<RemovedField>,
+}
+
+/// An image left unpublished because its format can't be sanitised and it
+/// still says where it was taken
+#[derive(Debug, Serialize)]
+pub struct WithheldFile {
+    pub path: String,
+    pub location: Vec<String>,
+}
+
+/// What publishing stripped, written next to the config and never published
+#[derive(Debug, Default, Serialize)]
+pub struct PrivacyReport {
+    pub files: Vec<FileReport>,
+    pub withheld: Vec<WithheldFile>,
+}
+
+impl RemovedField {
+    fn new(field: &Field, exif: &Exif) -> RemovedField {
+        let mut value = field.display_value().with_unit(exif).to_string();
+        if value.len() > MAX_REPORT_VALUE {
+            let full_length = value.len();
+            let mut end = MAX_REPORT_VALUE;
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            value.truncate(end);
+            value.push_str(&format!("... ({} characters)", full_length));
+        }
+        RemovedField {
+            tag: field.tag.to_string(),
+            value,
+        }
+    }
+}
+
+fn should_strip(field: &Field, config: &PrivacyConfig) -> bool {
+    if config.strip_gps && field.tag.context() == Context::Gps {
+        return true;
+    }
+    let name = field.tag.to_string();
+    config
+        .strip_fields
+        .iter()
+        .any(|strip| strip.eq_ignore_ascii_case(&name))
+}
+
+fn has_xmp_location(data: &[u8]) -> bool {
+    XMP_LOCATION_PROPERTIES.iter().any(|property| {
+        data.windows(property.len())
+            .any(|window| window == *property)
+    })
+}
+
+/// Rewrite a raw EXIF (TIFF) block without the stripped fields. Returns the
+/// fields taken out and the block to store, `None` meaning drop it entirely.
+/// Only the primary image's fields are rewritten, the embedded thumbnail goes.
+fn sanitise_exif(raw: &Bytes, config: &PrivacyConfig) -> (Option<Bytes>, Vec<RemovedField>) {
+    let exif = match Reader::new().read_raw(raw.to_vec()) {
+        Ok(exif) => exif,
+        // Can't tell what is in there, so it can't be published
+        Err(e) => {
+            let removed = RemovedField {
+                tag: "Exif".to_string(),
+                value: format!("unreadable, dropped ({})", e),
+            };
+            return (None, vec![removed]);
+        }
+    };
+
+    let mut kept: Vec<&Field> = Vec::new();
+    let mut removed: Vec<RemovedField> = Vec::new();
+    let mut has_thumbnail = false;
+    for field in exif.fields() {
+        if field.ifd_num != In::PRIMARY {
+            has_thumbnail = true;
+        } else if should_strip(field, config) {
+            removed.push(RemovedField::new(field, &exif));
+        } else if !matches!(
+            field.tag,
+            Tag::ExifIFDPointer | Tag::GPSInfoIFDPointer | Tag::InteropIFDPointer
+        ) {
+            kept.push(field);
+        }
+    }
+
+    if removed.is_empty() {
+        return (Some(raw.clone()), removed);
+    }
+    if has_thumbnail {
+        removed.push(RemovedField {
+            tag: "Thumbnail".to_string(),
+            value: "embedded preview".to_string(),
+        });
+    }
+    if kept.is_empty() {
+        return (None, removed);
+    }
+
+    let mut writer = Writer::new();
+    for field in &kept {
+        writer.push_field(field);
+    }
+    let mut buffer = Cursor::new(Vec::new());
+    match writer.write(&mut buffer, exif.little_endian()) {
+        Ok(()) => (Some(Bytes::from(buffer.into_inner())), removed),
+        Err(e) => {
+            removed.push(RemovedField {
+                tag: "Exif".to_string(),
+                value: format!("could not be rewritten, dropped ({})", e),
+            });
+            (None, removed)
+        }
+    }
+}
+
+/// Remove XMP packets that carry a location, returning how many went
+fn strip_xmp_location(image: &mut DynImage) -> usize {
+    match image {
+        DynImage::Jpeg(jpeg) => {
+            let before = jpeg.segments().len();
+            jpeg.segments_mut().retain(|segment| {
+                !(segment.marker() == markers::APP1
+                    && segment.contents().starts_with(XMP_JPEG_PREFIX)
+                    && has_xmp_location(segment.contents()))
+            });
+            before - jpeg.segments().len()
+        }
+        DynImage::Png(png) => {
+            let before = png.chunks().len();
+            png.chunks_mut().retain(|chunk| {
+                !(chunk.kind() == *b"iTXt"
+                    && chunk.contents().starts_with(XMP_PNG_KEYWORD)
+                    && has_xmp_location(chunk.contents()))
+            });
+            before - png.chunks().len()
+        }
+        DynImage::WebP(webp) => {
+            let before = webp.chunks().len();
+            webp.chunks_mut().retain(|chunk| {
+                !(chunk.id() == *b"XMP "
+                    && chunk
+                        .content()
+                        .data()
+                        .is_some_and(|data| has_xmp_location(data)))
+            });
+            before - webp.chunks().len()
+        }
+    }
+}
+
+/// Sanitised copy of a JPEG, PNG or WebP file. ICC profiles and pixel data
+/// are untouched, and files with nothing to strip come back byte for byte.
+/// Other formats are returned as they are.
+pub fn sanitise_image(data: Vec<u8>, config: &PrivacyConfig) -> (Vec<u8>, Vec<RemovedField>) {
+    let mut image = match DynImage::from_bytes(Bytes::from(data.clone())) {
+        Ok(Some(image)) => image,
+        _ => return (data, Vec::new()),
+    };
+
+    let mut removed = Vec::new();
+    if config.strip_gps {
+        let packets = strip_xmp_location(&mut image);
+        for _ in 0..packets {
+            removed.push(RemovedField {
+                tag: "XMP".to_string(),
+                value: "packet with location".to_string(),
+            });
+        }
+    }
+
+    if let Some(raw) = image.exif() {
+        let (exif, exif_removed) = sanitise_exif(&raw, config);
+        if !exif_removed.is_empty() {
+            image.set_exif(exif);
+            removed.extend(exif_removed);
+        }
+    }
+
+    if removed.is_empty() {
+        return (data, removed);
+    }
+    (image.encoder().bytes().to_vec(), removed)
+}
+
+/// Location metadata still present in an image, as tag names
+pub fn location_data(data: &[u8]) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+
+    if let Ok(exif) = Reader::new().read_from_container(&mut Cursor::new(data)) {
+        for field in exif.fields() {
+            // The version tag alone doesn't say where a photo was taken
+            if field.tag.context() == Context::Gps && field.tag != Tag::GPSVersionID {
+                found.push(field.tag.to_string());
+            }
+        }
+    }
+
+    if let Ok(Some(mut image)) = DynImage::from_bytes(Bytes::copy_from_slice(data))
+        && strip_xmp_location(&mut image) > 0
+    {
+        found.push("XMP".to_string());
+    }
+
+    found.dedup();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::Value;
+    use image::{ImageFormat, RgbImage};
+    use img_parts::ImageICC;
+    use img_parts::jpeg::Jpeg;
+
+    fn exif_block(fields: &[Field]) -> Bytes {
+        let mut writer = Writer::new();
+        for field in fields {
+            writer.push_field(field);
+        }
+        let mut buffer = Cursor::new(Vec::new());
+        writer.write(&mut buffer, false).unwrap();
+        Bytes::from(buffer.into_inner())
+    }
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    #[test]
+    fn strips_gps_and_keeps_icc() {
+        let mut jpeg_bytes = Vec::new();
+        RgbImage::new(8, 8)
+            .write_to(&mut Cursor::new(&mut jpeg_bytes), ImageFormat::Jpeg)
+            .unwrap();
+
+        let mut jpeg = Jpeg::from_bytes(Bytes::from(jpeg_bytes)).unwrap();
+        jpeg.set_exif(Some(exif_block(&[
+            field(Tag::Make, Value::Ascii(vec![b"Fujifilm".to_vec()])),
+            field(Tag::BodySerialNumber, Value::Ascii(vec![b"1234".to_vec()])),
+            field(Tag::GPSLatitudeRef, Value::Ascii(vec![b"S".to_vec()])),
+        ])));
+        jpeg.set_icc_profile(Some(Bytes::from_static(b"not a real profile")));
+        let original = jpeg.encoder().bytes().to_vec();
+        assert_eq!(location_data(&original), vec!["GPSLatitudeRef"]);
+
+        let config = PrivacyConfig {
+            strip_fields: vec!["bodyserialnumber".to_string()],
+            ..PrivacyConfig::default()
+        };
+        let (sanitised, removed) = sanitise_image(original, &config);
+        let tags: Vec<&str> = removed.iter().map(|r| r.tag.as_str()).collect();
+        assert_eq!(tags, vec!["BodySerialNumber", "GPSLatitudeRef"]);
+        assert!(location_data(&sanitised).is_empty());
+
+        let published = Jpeg::from_bytes(Bytes::from(sanitised.clone())).unwrap();
+        assert_eq!(
+            published.icc_profile().as_deref(),
+            Some(&b"not a real profile"[..])
+        );
+        let exif = Reader::new()
+            .read_raw(published.exif().unwrap().to_vec())
+            .unwrap();
+        assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+
+        // Nothing left to strip, so a second pass is a byte-for-byte copy
+        let (again, removed) = sanitise_image(sanitised.clone(), &config);
+        assert!(removed.is_empty());
+        assert_eq!(again, sanitised);
+    }
+}
@@ -0,0 +1,333 @@
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::animation::source_stem;
+use crate::config::PrivacyConfig;
+use crate::ignore::content_files;
+use crate::images::is_image_file;
+use crate::privacy::{FileReport, PrivacyReport, WithheldFile, location_data, sanitise_image};
+use crate::to_forward_slashes;
+
+/// Directory under the publish output that mirrors the content, the site
+/// fetches files from `/content/...`
+pub const PUBLISHED_CONTENT_DIR: &str = "content";
+
+/// What a sync changed, or would change on a dry run. Paths are relative to
+/// the content directory in the repo's "/dir/file" form.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl SyncSummary {
+    pub fn print(&self, dry_run: bool) {
+        for path in &self.added {
+            println!("  + {}", path);
+        }
+        for path in &self.updated {
+            println!("  ~ {}", path);
+        }
+        for path in &self.removed {
+            println!("  - {}", path);
+        }
+        let prefix = if dry_run {
+            "Dry run, would sync"
+        } else {
+            "Synced"
+        };
+        println!(
+            "{}: {} added, {} updated, {} removed, {} unchanged.",
+            prefix,
+            self.added.len(),
+            self.updated.len(),
+            self.removed.len(),
+            self.unchanged
+        );
+    }
+}
+
+fn digest(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// Hash of a file read a block at a time, videos needn't fit in memory
+fn file_digest(path: &Path) -> Option<[u8; 32]> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).ok()?;
+    Some(hasher.finalize().into())
+}
+
+/// Whether two files hold the same bytes, sizes are compared before hashing
+fn same_contents(a: &Path, b: &Path) -> bool {
+    let size = |path: &Path| fs::metadata(path).map(|meta| meta.len()).ok();
+    match (size(a), size(b)) {
+        (Some(a_size), Some(b_size)) if a_size == b_size => {
+            file_digest(a).is_some_and(|digest| file_digest(b) == Some(digest))
+        }
+        _ => false,
+    }
+}
+
+/// Every file under `dir`, relative to it, ignore rules aside
+fn list_files(dir: &Path) -> std::io::Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry_res in fs::read_dir(&current)? {
+            let entry = entry_res?;
+            let meta = entry.metadata()?;
+            let path = entry.path();
+            if meta.is_dir() {
+                dirs.push(path);
+            } else if meta.is_file() {
+                files.insert(path.strip_prefix(dir).unwrap_or(&path).to_path_buf());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Posters and renditions are written into the published tree while indexing,
+/// they stay as long as the file they were made from is still published.
+/// `sources` holds the directory and stem of each published file.
+fn is_kept_derived(relative: &Path, sources: &HashSet<(&Path, &str)>) -> bool {
+    let Some(stem) = source_stem(relative) else {
+        return false;
+    };
+    let parent = relative.parent().unwrap_or(Path::new(""));
+    sources.contains(&(parent, stem))
+}
+
+/// Remove directories left empty by pruning, deepest first
+fn remove_empty_dirs(dir: &Path) -> std::io::Result<()> {
+    let mut found = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry_res in fs::read_dir(&current)? {
+            let path = entry_res?.path();
+            if path.is_dir() {
+                dirs.push(path.clone());
+                found.push(path);
+            }
+        }
+    }
+    found.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+    for path in found {
+        if fs::read_dir(&path)?.next().is_none() {
+            fs::remove_dir(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Sync `content_dir` into `published_dir`. Images are written as sanitised
+/// copies, a file is only written when the published bytes differ by hash
+/// from what is already there, and files gone from the source are pruned.
+/// With `dry_run` nothing is touched and the summary says what would change.
+pub fn sync_content(
+    content_dir: &Path,
+    published_dir: &Path,
+    config: &PrivacyConfig,
+    dry_run: bool,
+) -> std::io::Result<(SyncSummary, PrivacyReport)> {
+    let mut summary = SyncSummary::default();
+    let mut report = PrivacyReport::default();
+
+    // Ignored files are left out, and pruned if an earlier sync copied them
+    let sources: BTreeSet<PathBuf> = content_files(content_dir)?
+        .into_iter()
+        .map(|path| {
+            path.strip_prefix(content_dir)
+                .unwrap_or(&path)
+                .to_path_buf()
+        })
+        .collect();
+    let existing = list_files(published_dir)?;
+
+    let mut published: BTreeSet<&Path> = BTreeSet::new();
+    for relative in &sources {
+        let source = content_dir.join(relative);
+        let destination = published_dir.join(relative);
+        let path_str = format!("/{}", to_forward_slashes(relative));
+
+        // Images are sanitised in memory, anything else is copied as it is
+        let sanitised = if is_image_file(&source) {
+            let (data, removed) = sanitise_image(fs::read(&source)?, config);
+            if !removed.is_empty() {
+                report.files.push(FileReport {
+                    path: path_str.clone(),
+                    removed,
+                });
+            } else if config.strip_gps {
+                // e.g. TIFF, which can't be rewritten without its location
+                let location = location_data(&data);
+                if !location.is_empty() {
+                    report.withheld.push(WithheldFile {
+                        path: path_str,
+                        location,
+                    });
+                    continue;
+                }
+            }
+            Some(data)
+        } else {
+            None
+        };
+        published.insert(relative);
+
+        let exists = existing.contains(relative);
+        let same = exists
+            && match &sanitised {
+                Some(data) => file_digest(&destination) == Some(digest(data)),
+                None => same_contents(&source, &destination),
+            };
+        let change = if !exists {
+            &mut summary.added
+        } else if !same {
+            &mut summary.updated
+        } else {
+            summary.unchanged += 1;
+            continue;
+        };
+        change.push(path_str);
+
+        if !dry_run {
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            match sanitised {
+                Some(data) => fs::write(&destination, data)?,
+                None => {
+                    fs::copy(&source, &destination)?;
+                }
+            }
+        }
+    }
+
+    let stems: HashSet<(&Path, &str)> = published
+        .iter()
+        .filter_map(|relative| {
+            let stem = relative.file_stem()?.to_str()?;
+            Some((relative.parent().unwrap_or(Path::new("")), stem))
+        })
+        .collect();
+    for relative in &existing {
+        if published.contains(relative.as_path()) || is_kept_derived(relative, &stems) {
+            continue;
+        }
+        summary
+            .removed
+            .push(format!("/{}", to_forward_slashes(relative)));
+        if !dry_run {
+            fs::remove_file(published_dir.join(relative))?;
+        }
+    }
+    if !dry_run && published_dir.is_dir() {
+        remove_empty_dirs(published_dir)?;
+    }
+
+    Ok((summary, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syncs_by_hash_and_prunes() {
+        let root = std::env::temp_dir().join(format!("olifm-sync-{}", std::process::id()));
+        let content = root.join("content");
+        let published = root.join("published");
+        fs::create_dir_all(content.join("notes")).unwrap();
+        fs::write(content.join("notes/a.md"), "a").unwrap();
+        fs::write(content.join("notes/b.md"), "b").unwrap();
+        fs::write(content.join("clip.mp4"), "clip").unwrap();
+        fs::create_dir_all(content.join(".obsidian")).unwrap();
+        fs::write(content.join(".obsidian/workspace.json"), "{}").unwrap();
+        let config = PrivacyConfig::default();
+
+        let (summary, _) = sync_content(&content, &published, &config, false).unwrap();
+        assert_eq!(
+            summary.added,
+            vec!["/clip.mp4", "/notes/a.md", "/notes/b.md"]
+        );
+
+        // Posters written while indexing survive while their clip does
+        fs::write(published.join("clip.poster.jpg"), "poster").unwrap();
+        fs::write(content.join("notes/a.md"), "a, edited").unwrap();
+        fs::remove_file(content.join("notes/b.md")).unwrap();
+
+        let (dry, _) = sync_content(&content, &published, &config, true).unwrap();
+        let expected = SyncSummary {
+            added: Vec::new(),
+            updated: vec!["/notes/a.md".to_string()],
+            removed: vec!["/notes/b.md".to_string()],
+            unchanged: 1,
+        };
+        assert_eq!(dry, expected);
+        assert!(published.join("notes/b.md").exists());
+
+        let (summary, _) = sync_content(&content, &published, &config, false).unwrap();
+        assert_eq!(summary, expected);
+        assert!(!published.join("notes/b.md").exists());
+        assert!(published.join("clip.poster.jpg").exists());
+        assert_eq!(
+            fs::read_to_string(published.join("notes/a.md")).unwrap(),
+            "a, edited"
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn withholds_images_that_keep_their_location() {
+        use exif::experimental::Writer;
+        use exif::{Field, In, Tag, Value};
+
+        let root = std::env::temp_dir().join(format!("olifm-withhold-{}", std::process::id()));
+        let content = root.join("content");
+        let published = root.join("published");
+        fs::create_dir_all(&content).unwrap();
+
+        // An EXIF block is a TIFF of its own, and TIFFs are copied unsanitised
+        let latitude = Field {
+            tag: Tag::GPSLatitudeRef,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"S".to_vec()]),
+        };
+        let mut writer = Writer::new();
+        writer.push_field(&latitude);
+        let mut tiff = io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        fs::write(content.join("map.tiff"), tiff.into_inner()).unwrap();
+        fs::write(content.join("map.md"), "map").unwrap();
+
+        // Published by an earlier sync, before it was noticed
+        fs::create_dir_all(&published).unwrap();
+        fs::write(published.join("map.tiff"), "old copy").unwrap();
+        fs::write(published.join("map.poster.png"), "poster").unwrap();
+
+        let config = PrivacyConfig::default();
+        let (summary, report) = sync_content(&content, &published, &config, false).unwrap();
+        assert_eq!(summary.added, vec!["/map.md"]);
+        assert_eq!(summary.removed, vec!["/map.tiff"]);
+        assert_eq!(report.withheld.len(), 1);
+        assert_eq!(report.withheld[0].path, "/map.tiff");
+        assert_eq!(report.withheld[0].location, vec!["GPSLatitudeRef"]);
+        assert!(!published.join("map.tiff").exists());
+        // map.md is still published, so what's named after it stays
+        assert!(published.join("map.poster.png").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::NaiveDate;
+use content_service::models::{JsonEntry, Similar};
+use std::collections::HashSet;
+
+use crate::config::SimilarConfig;
+use crate::dates::parse_content_date;
+use crate::phash;
+
+// How much each signal contributes to the final score, summing to 1
+const VISUAL_WEIGHT: f32 = 0.45;
+const TAG_WEIGHT: f32 = 0.3;
+const DATE_WEIGHT: f32 = 0.15;
+const CAMERA_WEIGHT: f32 = 0.1;
+
+/// Hash distance treated as "nothing in common", unrelated photos land
+/// around half of the 64 bits
+const UNRELATED_DISTANCE: f32 = 32.0;
+
+/// Days over which the date signal decays to about a third
+const DATE_DECAY_DAYS: f32 = 365.0;
+
+/// What the ranking looks at for one entry
+struct Features {
+    phash: Option<u64>,
+    tags: HashSet<String>,
+    medium: Option<String>,
+    date: Option<NaiveDate>,
+}
+
+impl Features {
+    fn of(entry: &JsonEntry) -> Features {
+        Features {
+            phash: entry
+                .images
+                .first()
+                .and_then(|img| img.phash.as_deref())
+                .and_then(phash::from_hex),
+            tags: entry.tags().into_iter().collect(),
+            medium: entry.metadata.get("medium").cloned(),
+            date: entry
+                .metadata
+                .get("date")
+                .and_then(|d| parse_content_date(d)),
+        }
+    }
+}
+
+fn score(a: &Features, b: &Features) -> f32 {
+    let visual = match (a.phash, b.phash) {
+        (Some(ha), Some(hb)) => {
+            (1.0 - phash::distance(ha, hb) as f32 / UNRELATED_DISTANCE).max(0.0)
+        }
+        _ => 0.0,
+    };
+
+    let union = a.tags.union(&b.tags).count();
+    let tags = if union == 0 {
+        0.0
+    } else {
+        a.tags.intersection(&b.tags).count() as f32 / union as f32
+    };
+
+    let camera = match (&a.medium, &b.medium) {
+        (Some(ma), Some(mb)) if ma == mb => 1.0,
+        _ => 0.0,
+    };
+
+    let date = match (a.date, b.date) {
+        (Some(da), Some(db)) => {
+            let days = (da - db).num_days().abs() as f32;
+            (-days / DATE_DECAY_DAYS).exp()
+        }
+        _ => 0.0,
+    };
+
+    VISUAL_WEIGHT * visual + TAG_WEIGHT * tags + DATE_WEIGHT * date + CAMERA_WEIGHT * camera
+}
+
+/// Top-level section of an index path, "/pictures/Puffy.md" -> "pictures"
+fn section_of(path: &str) -> &str {
+    path.trim_start_matches('/').split('/').next().unwrap_or("")
+}
+
+/// Fill in `similar` for every photo entry in the configured sections,
+/// ranking against the other photos of the same section
+pub fn attach_similar(entries: &mut [JsonEntry], config: &SimilarConfig) {
+    let candidates: Vec<usize> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            !entry.images.is_empty()
+                && config
+                    .sections
+                    .iter()
+                    .any(|section| section == section_of(&entry.path))
+        })
+        .map(|(i, _)| i)
+        .collect();
+    let features: Vec<Features> = candidates
+        .iter()
+        .map(|&i| Features::of(&entries[i]))
+        .collect();
+
+    for (a, &entry_index) in candidates.iter().enumerate() {
+        let section = section_of(&entries[entry_index].path);
+        let mut ranked: Vec<(f32, &str)> = candidates
+            .iter()
+            .enumerate()
+            .filter(|&(b, &other)| b != a && section_of(&entries[other].path) == section)
+            .map(|(b, &other)| {
+                (
+                    score(&features[a], &features[b]),
+                    entries[other].path.as_str(),
+                )
+            })
+            .filter(|(s, _)| *s >= config.min_score)
+            .collect();
+        // Path breaks ties so the output is stable between runs
+        ranked.sort_by(|x, y| y.0.total_cmp(&x.0).then(x.1.cmp(y.1)));
+        ranked.truncate(config.max);
+
+        let similar: Vec<Similar> = ranked
+            .into_iter()
+            .map(|(s, path)| Similar {
+                path: path.to_string(),
+                score: (s * 100.0).round() as u8,
+            })
+            .collect();
+        entries[entry_index].similar = similar;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use content_service::models::{Img, entry_types};
+    use std::collections::BTreeMap;
+
+    fn photo(path: &str, phash: u64, tags: &[&str], medium: &str, date: &str) -> JsonEntry {
+        let mut metadata = BTreeMap::new();
+        let tags: Vec<String> = tags.iter().map(|t| format!("String(\"{}\")", t)).collect();
+        metadata.insert(
+            "tags".to_string(),
+            format!("Sequence [{}]", tags.join(", ")),
+        );
+        metadata.insert("medium".to_string(), medium.to_string());
+        metadata.insert("date".to_string(), date.to_string());
+        JsonEntry {
+            path: path.to_string(),
+            entry_type: entry_types::DOCUMENT.to_string(),
+            name: path.to_string(),
+            images: vec![Img {
+                blurhash: String::new(),
+                aspect_ratio: "1/1".to_string(),
+                name: String::new(),
+                path: String::new(),
+                palette: Vec::new(),
+                placeholder: None,
+                phash: Some(phash::to_hex(phash)),
+                animation: None,
+            }],
+            metadata,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ranks_shared_subject_above_unrelated_shot() {
+        let mut entries = vec![
+            photo(
+                "/pictures/magpie.md",
+                0x0f0f,
+                &["Bird", "Perth"],
+                "P900",
+                "2025-06-12",
+            ),
+            photo(
+                "/pictures/crow.md",
+                0x0f1f,
+                &["Bird", "Perth"],
+                "P900",
+                "2025-08-12",
+            ),
+            photo(
+                "/pictures/beach.md",
+                !0x0f0f,
+                &["Beach"],
+                "Pixel",
+                "2019-12-12",
+            ),
+            photo("/blog/post.md", 0x0f0f, &["Bird"], "P900", "2025-06-12"),
+        ];
+        let config = SimilarConfig::default();
+        attach_similar(&mut entries, &config);
+
+        let magpie = &entries[0].similar;
+        assert_eq!(magpie.len(), 1);
+        assert_eq!(magpie[0].path, "/pictures/crow.md");
+        assert!(entries[3].similar.is_empty());
+    }
+}
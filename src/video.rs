@@ -0,0 +1,247 @@
+use content_service::models::Video;
+use serde_json::Value as JsonValue;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::animation::is_derived_file;
+use crate::config::ImageConfig;
+use crate::ignore::content_files;
+use crate::images::summarise_image;
+use crate::to_forward_slashes;
+
+/// How far into a clip the poster frame is taken from, capped to a tenth of
+/// the clip so very short loops still land on a real frame
+const POSTER_OFFSET_MS: u64 = 1000;
+
+pub fn is_video_file(path: &Path) -> bool {
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        matches!(
+            ext.to_lowercase().as_str(),
+            "mp4" | "m4v" | "webm" | "mkv" | "mov"
+        )
+    } else {
+        false
+    }
+}
+
+struct Probe {
+    width: u32,
+    height: u32,
+    duration_ms: Option<u64>,
+}
+
+fn ffmpeg_available() -> bool {
+    Command::new("ffprobe")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Read the first video stream's dimensions and the container duration
+fn probe_video(path: &Path) -> Option<Probe> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=width,height:format=duration",
+            "-of",
+            "json",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let json: JsonValue = serde_json::from_slice(&output.stdout).ok()?;
+    let stream = json.get("streams")?.get(0)?;
+    let width = stream.get("width")?.as_u64()? as u32;
+    let height = stream.get("height")?.as_u64()? as u32;
+    // ffprobe reports the duration as a string of seconds
+    let duration_ms = json
+        .get("format")
+        .and_then(|f| f.get("duration"))
+        .and_then(|d| d.as_str())
+        .and_then(|d| d.parse::<f64>().ok())
+        .map(|seconds| (seconds * 1000.0).round() as u64);
+
+    Some(Probe {
+        width,
+        height,
+        duration_ms,
+    })
+}
+
+/// `blog/clip.mp4` gets its poster written as `blog/clip.poster.jpg` under
+/// `posters_dir`, so the content being indexed is left as it was
+fn poster_path_for(relative_path: &Path, posters_dir: &Path) -> PathBuf {
+    let stem = relative_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("video");
+    posters_dir
+        .join(relative_path)
+        .with_file_name(format!("{}.poster.jpg", stem))
+}
+
+fn extract_poster(path: &Path, duration_ms: Option<u64>, poster: &Path) -> bool {
+    if let Some(parent) = poster.parent()
+        && fs::create_dir_all(parent).is_err()
+    {
+        return false;
+    }
+
+    // Reuse a poster that is newer than the clip
+    let is_fresh = match (fs::metadata(path), fs::metadata(poster)) {
+        (Ok(video), Ok(existing)) => match (video.modified(), existing.modified()) {
+            (Ok(video_time), Ok(poster_time)) => poster_time >= video_time,
+            _ => false,
+        },
+        _ => false,
+    };
+    if is_fresh {
+        return true;
+    }
+
+    let offset_ms = duration_ms.map_or(0, |d| (d / 10).min(POSTER_OFFSET_MS));
+    let offset = format!("{}.{:03}", offset_ms / 1000, offset_ms % 1000);
+    Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-ss", &offset, "-i"])
+        .arg(path)
+        .args(["-frames:v", "1", "-q:v", "3"])
+        .arg(poster)
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Probe every clip under `base`. Posters go under `posters_dir`, the
+/// directory the site serves `/content/...` from, and are indexed relative
+/// to it.
+pub fn build_video_structure(
+    base: &Path,
+    posters_dir: &Path,
+    config: &ImageConfig,
+) -> std::io::Result<Vec<Video>> {
+    index_videos(base, posters_dir, config, ffmpeg_available())
+}
+
+/// Without ffmpeg clips are still indexed, just without sizes or posters
+fn index_videos(
+    base: &Path,
+    posters_dir: &Path,
+    config: &ImageConfig,
+    has_ffmpeg: bool,
+) -> std::io::Result<Vec<Video>> {
+    let mut videos: Vec<Video> = Vec::new();
+    if !base.is_dir() {
+        return Ok(videos);
+    }
+
+    let mut warned = false;
+
+    for path in content_files(base)? {
+        if is_video_file(&path) && !is_derived_file(&path) {
+            let relative_path = path.strip_prefix(base).unwrap_or(&path);
+            let path_str = format!("/{}", to_forward_slashes(relative_path));
+            let name = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string();
+
+            let mut video = Video {
+                name,
+                path: path_str.clone(),
+                width: None,
+                height: None,
+                duration_ms: None,
+                aspect_ratio: None,
+                poster: None,
+                blurhash: None,
+                placeholder: None,
+            };
+
+            if !has_ffmpeg {
+                if !warned {
+                    println!("ffprobe not found, videos are indexed without posters");
+                    warned = true;
+                }
+            } else if let Some(probe) = probe_video(&path) {
+                video.width = Some(probe.width);
+                video.height = Some(probe.height);
+                video.duration_ms = probe.duration_ms;
+                if probe.height > 0 {
+                    video.aspect_ratio = Some(format!("{}/{}", probe.width, probe.height));
+                }
+
+                let poster = poster_path_for(relative_path, posters_dir);
+                if extract_poster(&path, probe.duration_ms, &poster) {
+                    let relative_poster = poster.strip_prefix(posters_dir).unwrap_or(&poster);
+                    video.poster = Some(format!("/{}", to_forward_slashes(relative_poster)));
+                    if let Some(summary) = summarise_image(&poster, config) {
+                        video.blurhash = Some(summary.blurhash);
+                        video.placeholder = summary.placeholder;
+                    }
+                } else {
+                    println!("Could not extract poster frame: {}", path_str);
+                }
+            } else {
+                println!("Could not probe video: {}", path_str);
+            }
+
+            println!("Video added: {}", path_str);
+            videos.push(video);
+        }
+    }
+
+    Ok(videos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posters_go_under_the_posters_dir() {
+        let posters_dir = Path::new("/site/content");
+        assert_eq!(
+            poster_path_for(Path::new("sounds/live/set.mp4"), posters_dir),
+            PathBuf::from("/site/content/sounds/live/set.poster.jpg")
+        );
+        assert_eq!(
+            poster_path_for(Path::new("clip.MOV"), posters_dir),
+            PathBuf::from("/site/content/clip.poster.jpg")
+        );
+    }
+
+    #[test]
+    fn indexes_clips_without_ffmpeg() {
+        let root = std::env::temp_dir().join(format!("olifm-video-{}", std::process::id()));
+        let (base, posters_dir) = (root.join("content"), root.join("out/content"));
+        fs::create_dir_all(base.join("sounds/live")).unwrap();
+        fs::write(base.join("sounds/live/set.mp4"), "not really a video").unwrap();
+        fs::write(base.join("sounds/live/set.poster.jpg"), "derived").unwrap();
+
+        let videos = index_videos(&base, &posters_dir, &ImageConfig::default(), false).unwrap();
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].path, "/sounds/live/set.mp4");
+        assert_eq!((videos[0].width, videos[0].poster.as_deref()), (None, None));
+        assert!(!posters_dir.exists());
+        // Nothing was written beside the source either
+        let mut files: Vec<_> = fs::read_dir(base.join("sounds/live"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["set.mp4", "set.poster.jpg"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
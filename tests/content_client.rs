@@ -0,0 +1,258 @@
+//! `ContentServiceClient` end to end: the helper publishes the fixture
+//! content, the stand-in server in `support` serves it over HTTP and the
+//! client reads it the way the site does.
+
+mod support;
+
+use content_service::models::entry_types;
+use content_service::{
+    Cache, ContentQuery, ContentServiceClient, ContentServiceError, HttpTransport, RetryPolicy,
+};
+use pollster::block_on;
+use std::time::{Duration, Instant};
+
+fn client(base_url: String) -> ContentServiceClient<HttpTransport> {
+    ContentServiceClient::with_transport(base_url, HttpTransport::new())
+}
+
+fn names(entries: &[content_service::JsonEntry]) -> Vec<&str> {
+    entries.iter().map(|entry| entry.name.as_str()).collect()
+}
+
+#[test]
+fn filters_and_sorts_the_published_index() {
+    let site = support::site();
+    let client = client(site.base_url.clone());
+
+    block_on(async {
+        let blog = client
+            .get_content(
+                "/blog/".to_string(),
+                Some(entry_types::DOCUMENT.to_string()),
+            )
+            .await
+            .unwrap();
+        // Pinned first, then newest first, undated last
+        assert_eq!(
+            names(&blog),
+            vec![
+                "pinned.md",
+                "2024-03-04-second.md",
+                "2024-01-02-first.md",
+                "readme.md"
+            ]
+        );
+        assert!(blog.iter().all(|entry| !entry.path.ends_with(".tmp")));
+
+        let tagged = client
+            .query(&ContentQuery::new("/blog").all_tags(["rust", "notes"]))
+            .await
+            .unwrap();
+        assert_eq!(names(&tagged.entries), vec!["2024-01-02-first.md"]);
+
+        let mut tags = client.get_tags("/blog".to_string()).await.unwrap();
+        tags.sort();
+        assert_eq!(tags, vec!["notes", "rust"]);
+
+        let section = client.get_section("/blog").await.unwrap();
+        assert_eq!(section.title.as_deref(), Some("Blog"));
+        assert_eq!(section.page_size, Some(2));
+
+        let page = client
+            .get_content_page("/blog/".to_string(), None, 2, section.page_size)
+            .await
+            .unwrap();
+        assert_eq!((page.page(), page.pages()), (2, 2));
+
+        let archive = client.get_archive().await.unwrap();
+        assert_eq!(archive[0].section, "/blog");
+        assert_eq!(archive[0].count, 3);
+    });
+}
+
+#[test]
+fn caches_the_index_and_documents() {
+    let site = support::site();
+    let base_url = site.url("/slow/0");
+    let client = client(base_url);
+
+    block_on(async {
+        for _ in 0..3 {
+            client.get_content("/".to_string(), None).await.unwrap();
+        }
+        let first = client
+            .get_document("/content/blog/2024-01-02-first.md")
+            .await
+            .unwrap();
+        let again = client
+            .get_document("/content/blog/2024-01-02-first.md")
+            .await
+            .unwrap();
+        assert!(first.contains("The first post."));
+        assert_eq!(first, again);
+    });
+
+    assert_eq!(site.hits("/slow/0/directory_structure.json"), 1);
+    assert_eq!(site.hits("/slow/0/content/blog/2024-01-02-first.md"), 1);
+}
+
+#[test]
+fn stale_copies_are_revalidated_with_their_etag() {
+    let site = support::site();
+    let base_url = site.url("/slow/1");
+    // Stale as soon as it's fetched, so every visit asks the server
+    let cache = Cache::memory().with_max_age(Duration::ZERO);
+    let visit = || {
+        let client = client(base_url.clone()).with_cache(cache.clone());
+        block_on(client.get_document("/content/blog/2024-03-04-second.md")).unwrap()
+    };
+
+    let first = visit();
+    let again = visit();
+    assert_eq!(first, again);
+    assert_eq!(site.hits("/slow/1/content/blog/2024-03-04-second.md"), 2);
+    assert_eq!(
+        site.not_modified("/slow/1/content/blog/2024-03-04-second.md"),
+        1
+    );
+
+    // A fresh copy is used without asking
+    let client = client(base_url).with_cache(cache.with_max_age(Duration::from_secs(60)));
+    block_on(client.get_document("/content/blog/2024-03-04-second.md")).unwrap();
+    assert_eq!(site.hits("/slow/1/content/blog/2024-03-04-second.md"), 2);
+}
+
+#[test]
+fn missing_documents_are_errors() {
+    let site = support::site();
+    let client = client(site.base_url.clone());
+
+    let result = block_on(client.get_document("/content/blog/missing.md"));
+    match result {
+        Err(ContentServiceError::NotFound { url }) => {
+            assert!(url.ends_with("/content/blog/missing.md"), "{}", url)
+        }
+        other => panic!("expected a 404, got {:?}", other),
+    }
+    // Not worth asking again
+    assert_eq!(site.hits("/content/blog/missing.md"), 1);
+    assert!(matches!(
+        block_on(client.get_document("  ")),
+        Err(ContentServiceError::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn malformed_index_is_a_parse_error() {
+    let site = support::site();
+    let client = client(site.url("/malformed"));
+
+    let result = block_on(client.get_content("/".to_string(), None));
+    assert!(
+        matches!(result, Err(ContentServiceError::ParseError(_))),
+        "{:?}",
+        result
+    );
+}
+
+/// Retries quickly enough for a test
+fn brisk() -> RetryPolicy {
+    RetryPolicy {
+        max_retries: 2,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(10),
+    }
+}
+
+#[test]
+fn error_statuses_are_typed() {
+    let site = support::site();
+    let fetch = |status: u16| {
+        let client = client(site.url(&format!("/status/{}", status))).with_retry_policy(brisk());
+        let result = block_on(client.get_content("/".to_string(), None));
+        // A failed fetch isn't cached as an empty site
+        assert!(client.files().is_empty());
+        let hits = site.hits(&format!("/status/{}/directory_structure.json", status));
+        (result, hits)
+    };
+
+    let (result, hits) = fetch(404);
+    assert!(
+        matches!(result, Err(ContentServiceError::NotFound { .. })),
+        "{:?}",
+        result
+    );
+    assert_eq!(hits, 1);
+
+    // Retry-After is longer than the policy waits, so it's handed back
+    let (result, hits) = fetch(429);
+    match result {
+        Err(ContentServiceError::RateLimited { retry_after, .. }) => {
+            assert_eq!(retry_after, Some(Duration::from_secs(1)))
+        }
+        other => panic!("expected a rate limit, got {:?}", other),
+    }
+    assert_eq!(hits, 1);
+
+    let (result, hits) = fetch(500);
+    match result {
+        Err(ContentServiceError::Http { status, url }) => {
+            assert_eq!(status, 500);
+            assert!(url.ends_with("/directory_structure.json"), "{}", url);
+        }
+        other => panic!("expected HTTP 500, got {:?}", other),
+    }
+    assert_eq!(hits, 3);
+}
+
+#[test]
+fn transient_failures_are_retried() {
+    let site = support::site();
+    let client = client(site.url("/flaky/2")).with_retry_policy(brisk());
+
+    let entries = block_on(client.get_content("/".to_string(), None)).unwrap();
+    assert!(!entries.is_empty());
+    assert_eq!(site.hits("/flaky/2/directory_structure.json"), 3);
+}
+
+#[test]
+fn retry_after_is_waited_out() {
+    let site = support::site();
+    let policy = RetryPolicy {
+        max_retries: 1,
+        max_delay: Duration::from_secs(2),
+        ..RetryPolicy::default()
+    };
+    let client = client(site.url("/status/429")).with_retry_policy(policy);
+
+    let started = Instant::now();
+    let result = block_on(client.get_document("/content/readme.md"));
+    assert!(
+        matches!(result, Err(ContentServiceError::RateLimited { .. })),
+        "{:?}",
+        result
+    );
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(site.hits("/status/429/content/readme.md"), 2);
+}
+
+#[test]
+fn slow_responses_wait_or_time_out() {
+    let site = support::site();
+
+    let patient = client(site.url("/slow/200"));
+    let entries = block_on(patient.get_content("/".to_string(), None)).unwrap();
+    assert!(!entries.is_empty());
+
+    let hasty = ContentServiceClient::with_transport(
+        site.url("/slow/2000"),
+        HttpTransport::with_timeout(Duration::from_millis(100)),
+    )
+    .with_retry_policy(RetryPolicy::none());
+    let result = block_on(hasty.get_content("/".to_string(), None));
+    assert!(
+        matches!(result, Err(ContentServiceError::NetworkError(_))),
+        "{:?}",
+        result
+    );
+}
@@ -0,0 +1,208 @@
+//! A stand-in for the content host: the fixture content published by the
+//! helper, served over plain HTTP from a local port. Path prefixes make it
+//! misbehave on purpose:
+//!
+//! - `/status/<code>/...` answers with that status
+//! - `/slow/<ms>/...` waits before answering
+//! - `/malformed/...` answers with a body that isn't JSON
+//! - `/flaky/<n>/...` answers 503 to the first `n` requests for each path
+//!
+//! Files carry an `ETag` and are answered 304 when the client already has
+//! them. Requests are counted per path so tests can check what the client
+//! cached.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+pub struct Site {
+    /// e.g. "http://127.0.0.1:41234", no trailing slash
+    pub base_url: String,
+    hits: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl Site {
+    /// Requests so far for `path`, including any misbehaving prefix
+    pub fn hits(&self, path: &str) -> usize {
+        self.hits.lock().unwrap().get(path).copied().unwrap_or(0)
+    }
+
+    /// Requests for `path` answered 304 Not Modified
+    pub fn not_modified(&self, path: &str) -> usize {
+        self.hits(&format!("304 {}", path))
+    }
+
+    /// `base_url` with a misbehaving prefix, e.g. `url("/status/500")`
+    pub fn url(&self, prefix: &str) -> String {
+        format!("{}{}", self.base_url, prefix)
+    }
+}
+
+/// The fixture site, published and served once for the whole test binary
+pub fn site() -> &'static Site {
+    static SITE: OnceLock<Site> = OnceLock::new();
+    SITE.get_or_init(|| {
+        let root = publish_fixtures();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind a local port");
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(Mutex::new(HashMap::new()));
+
+        let (serve_root, serve_hits) = (root.clone(), hits.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (root, hits) = (serve_root.clone(), serve_hits.clone());
+                thread::spawn(move || handle(stream, &root, &hits));
+            }
+        });
+
+        Site { base_url, hits }
+    })
+}
+
+/// Run the helper's `publish` on `tests/fixtures/content`, returning the
+/// output directory with `directory_structure.json` and `content/`
+fn publish_fixtures() -> PathBuf {
+    let out = std::env::temp_dir().join(format!("olifm-client-site-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&out);
+    fs::create_dir_all(&out).unwrap();
+    // Keep the privacy report out of the working directory. The fixtures'
+    // commits would date the undated ones, so history is off.
+    let config = out.with_extension("yaml");
+    fs::write(
+        &config,
+        format!(
+            "privacy:\n  report: {}\nhistory:\n  enabled: false\n",
+            out.with_extension("json").display()
+        ),
+    )
+    .unwrap();
+
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/content");
+    let output = Command::new(env!("CARGO_BIN_EXE_olifm-master"))
+        .arg("publish")
+        .arg("--content")
+        .arg(&fixtures)
+        .arg("--out")
+        .arg(&out)
+        .arg("--config")
+        .arg(&config)
+        .output()
+        .expect("run the helper");
+    assert!(
+        out.join("directory_structure.json").is_file(),
+        "helper didn't write an index:\n{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    out
+}
+
+fn handle(mut stream: TcpStream, root: &Path, hits: &Mutex<HashMap<String, usize>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut if_none_match = None;
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("if-none-match")
+        {
+            if_none_match = Some(value.trim().to_string());
+        }
+        line.clear();
+    }
+
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let path = target.split('?').next().unwrap_or("/").to_string();
+    let count = {
+        let mut hits = hits.lock().unwrap();
+        let count = hits.entry(path.clone()).or_default();
+        *count += 1;
+        *count
+    };
+
+    let (mut status, mut extra, mut body) = respond(&path, count, root);
+    if status == 200 {
+        let etag = format!("\"{:x}\"", etag(&body));
+        if if_none_match.as_deref() == Some(etag.as_str()) {
+            *hits
+                .lock()
+                .unwrap()
+                .entry(format!("304 {}", path))
+                .or_default() += 1;
+            (status, body) = (304, String::new());
+        }
+        extra.push_str(&format!("ETag: {}\r\n", etag));
+    }
+    let reason = match status {
+        200 => "OK",
+        304 => "Not Modified",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Status",
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n",
+        status,
+        reason,
+        body.len(),
+        extra
+    );
+    let _ = stream.write_all(body.as_bytes());
+}
+
+/// FNV-1a of `body`, enough to tell versions of a fixture apart
+fn etag(body: &str) -> u64 {
+    body.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Status, extra header lines and body for the `count`th request of `path`
+fn respond(path: &str, count: usize, root: &Path) -> (u16, String, String) {
+    let mut segments = path.trim_start_matches('/').splitn(3, '/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("status"), Some(code), _) => {
+            let code: u16 = code.parse().unwrap_or(500);
+            let extra = if code == 429 {
+                "Retry-After: 1\r\n".to_string()
+            } else {
+                String::new()
+            };
+            (code, extra, format!("status {}", code))
+        }
+        (Some("slow"), Some(ms), rest) => {
+            thread::sleep(Duration::from_millis(ms.parse().unwrap_or(0)));
+            respond(&format!("/{}", rest.unwrap_or("")), count, root)
+        }
+        (Some("flaky"), Some(failures), rest) => {
+            if count <= failures.parse().unwrap_or(0) {
+                (503, String::new(), "try again".to_string())
+            } else {
+                respond(&format!("/{}", rest.unwrap_or("")), count, root)
+            }
+        }
+        (Some("malformed"), _, _) => (200, String::new(), "{\"schema_version\": 3, ".to_string()),
+        _ => {
+            let relative = path.trim_start_matches('/');
+            if relative.split('/').any(|part| part == "..") {
+                return (404, String::new(), String::new());
+            }
+            match fs::read_to_string(root.join(relative)) {
+                Ok(body) => (200, String::new(), body),
+                Err(_) => (404, String::new(), String::new()),
+            }
+        }
+    }
+}
@@ -1,7 +1,13 @@
 import init, {
   on_article_card_visible,
   on_article_card_click,
+  on_scroll_sentinel_visible,
   on_tag_click,
+  on_track_play,
+  on_track_queue,
+  on_player_toggle,
+  on_player_next,
+  on_player_prev,
 } from "../pkg/web.js";
 import "./article-observer.js";
 
@@ -12,7 +18,13 @@ async function run() {
 
     window.on_article_card_visible = on_article_card_visible;
     window.on_article_card_click = on_article_card_click;
+    window.on_scroll_sentinel_visible = on_scroll_sentinel_visible;
     window.on_tag_click = on_tag_click;
+    window.on_track_play = on_track_play;
+    window.on_track_queue = on_track_queue;
+    window.on_player_toggle = on_player_toggle;
+    window.on_player_next = on_player_next;
+    window.on_player_prev = on_player_prev;
 
     console.log("WASM initialized and functions exported globally");
   } catch (error) {
@@ -0,0 +1,100 @@
+// Start an animated asset: swap the still for the animation, or play the clip
+function startAnimation(el) {
+  if (el.tagName === "VIDEO") {
+    if (!el.src && el.dataset.src) {
+      el.src = el.dataset.src;
+    }
+    el.play().catch(() => {});
+  } else if (el.dataset.src && el.getAttribute("src") !== el.dataset.src) {
+    el.src = el.dataset.src;
+  }
+}
+
+// Stop an animated asset that has scrolled away, gifs go back to their poster
+// so the browser stops decoding frames
+function stopAnimation(el) {
+  if (el.tagName === "VIDEO") {
+    el.pause();
+  } else if (el.dataset.poster && el.getAttribute("src") !== el.dataset.poster) {
+    el.src = el.dataset.poster;
+  }
+}
+
+// A clip that fails to load (e.g. the rendition wasn't built) is replaced by
+// an img pointing at the original animation
+function fallBackToImage(video) {
+  const img = document.createElement("img");
+  img.className = video.className;
+  img.alt = video.getAttribute("aria-label") || "";
+  img.dataset.animated = "";
+  img.dataset.src = video.dataset.fallback;
+  if (video.poster) {
+    img.src = video.poster;
+    img.dataset.poster = video.poster;
+  }
+  img.style.opacity = 1;
+  video.replaceWith(img);
+  window.animationObserver.observe(img);
+}
+
+function setupAnimationObserver() {
+  if (!window.animationObserver) {
+    const reduceMotion = window.matchMedia("(prefers-reduced-motion: reduce)");
+    window.animationObserver = new IntersectionObserver(
+      (entries) => {
+        entries.forEach((entry) => {
+          if (entry.isIntersecting && !reduceMotion.matches) {
+            startAnimation(entry.target);
+          } else {
+            stopAnimation(entry.target);
+          }
+        });
+      },
+      { threshold: 0.25 },
+    );
+  }
+
+  // Observing an element twice is a no-op, so this is safe to call per render
+  document.querySelectorAll("[data-animated]").forEach((el) => {
+    if (el.tagName === "VIDEO" && el.dataset.fallback && !el.onerror) {
+      el.onerror = () => fallBackToImage(el);
+    }
+    window.animationObserver.observe(el);
+  });
+}
+
+function setupMediaObserver() {
+  const observer = new IntersectionObserver(
+    (entries) => {
+      entries.forEach((entry) => {
+        if (entry.isIntersecting) {
+          const video = entry.target;
+
+          // Only fetch the poster (and let the browser see the source) once
+          // the clip is close to being on screen
+          if (video.dataset.poster) {
+            video.poster = video.dataset.poster;
+          }
+          if (video.dataset.src) {
+            video.src = video.dataset.src;
+          }
+
+          observer.unobserve(video);
+        }
+      });
+    },
+    {
+      threshold: 0,
+      rootMargin: "200px",
+    },
+  );
+
+  // Animated assets are handled separately, they play and pause with visibility
+  document.querySelectorAll("video[data-src]:not([data-animated])").forEach((video) => {
+    observer.observe(video);
+  });
+
+  window.mediaObserver = observer;
+
+  setupAnimationObserver();
+}
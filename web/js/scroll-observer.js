@@ -0,0 +1,37 @@
+// Infinite scroll: asks for the next page when a listing's sentinel comes
+// into view. Set up again after every page, which also catches a sentinel
+// that is still on screen because the new cards were too few to push it off.
+function setupScrollObserver() {
+  if (window.scrollObserver) {
+    window.scrollObserver.disconnect();
+  }
+
+  const observer = new IntersectionObserver(
+    (entries) => {
+      entries.forEach((entry) => {
+        const sentinel = entry.target;
+        if (!entry.isIntersecting || sentinel.dataset.loading) {
+          return;
+        }
+        if (window.on_scroll_sentinel_visible) {
+          sentinel.dataset.loading = "true";
+          window.on_scroll_sentinel_visible(
+            sentinel.dataset.section,
+            Number(sentinel.dataset.offset),
+          );
+        } else {
+          console.warn("on_scroll_sentinel_visible not available yet");
+        }
+      });
+    },
+    {
+      rootMargin: "400px",
+    },
+  );
+
+  document.querySelectorAll(".scroll-sentinel").forEach((sentinel) => {
+    observer.observe(sentinel);
+  });
+
+  window.scrollObserver = observer;
+}
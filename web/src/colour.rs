@@ -0,0 +1,265 @@
+use content_service::JsonEntry;
+
+/// Broad colour groups offered by the pictures page filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourFamily {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Neutral,
+}
+
+impl ColourFamily {
+    pub const ALL: [ColourFamily; 7] = [
+        ColourFamily::Red,
+        ColourFamily::Orange,
+        ColourFamily::Yellow,
+        ColourFamily::Green,
+        ColourFamily::Blue,
+        ColourFamily::Purple,
+        ColourFamily::Neutral,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColourFamily::Red => "red",
+            ColourFamily::Orange => "orange",
+            ColourFamily::Yellow => "yellow",
+            ColourFamily::Green => "green",
+            ColourFamily::Blue => "blue",
+            ColourFamily::Purple => "purple",
+            ColourFamily::Neutral => "neutral",
+        }
+    }
+
+    /// Swatch shown on the filter chip
+    pub fn swatch(&self) -> &'static str {
+        match self {
+            ColourFamily::Red => "#c0392b",
+            ColourFamily::Orange => "#e67e22",
+            ColourFamily::Yellow => "#f1c40f",
+            ColourFamily::Green => "#27ae60",
+            ColourFamily::Blue => "#2e86c1",
+            ColourFamily::Purple => "#8e44ad",
+            ColourFamily::Neutral => "#95a5a6",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ColourFamily> {
+        ColourFamily::ALL
+            .into_iter()
+            .find(|family| family.name() == name.to_lowercase())
+    }
+
+    pub fn of(hsl: Hsl) -> ColourFamily {
+        // Washed out or near black/white reads as neutral whatever the hue
+        if hsl.saturation < 0.2 || hsl.lightness < 0.12 || hsl.lightness > 0.92 {
+            return ColourFamily::Neutral;
+        }
+        match hsl.hue {
+            h if !(15.0..345.0).contains(&h) => ColourFamily::Red,
+            h if h < 45.0 => ColourFamily::Orange,
+            h if h < 70.0 => ColourFamily::Yellow,
+            h if h < 170.0 => ColourFamily::Green,
+            h if h < 260.0 => ColourFamily::Blue,
+            _ => ColourFamily::Purple,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsl {
+    pub hue: f32,
+    pub saturation: f32,
+    pub lightness: f32,
+}
+
+/// Parse `#rrggbb` into hue (degrees), saturation and lightness (0-1)
+pub fn hex_to_hsl(hex: &str) -> Option<Hsl> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .ok()
+            .map(|v| v as f32 / 255.0)
+    };
+    let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+    if delta == 0.0 {
+        return Some(Hsl {
+            hue: 0.0,
+            saturation: 0.0,
+            lightness,
+        });
+    }
+
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        60.0 * (((g - b) / delta).rem_euclid(6.0))
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    Some(Hsl {
+        hue,
+        saturation,
+        lightness,
+    })
+}
+
+/// Dominant colour of an entry's first image
+pub fn dominant_colour(entry: &JsonEntry) -> Option<&str> {
+    entry
+        .images
+        .first()
+        .and_then(|img| img.palette.first())
+        .map(|colour| colour.as_str())
+}
+
+/// Whether any colour in the first image's palette falls in `family`, so a
+/// shot with a strong accent still turns up under that colour
+pub fn matches_family(entry: &JsonEntry, family: ColourFamily) -> bool {
+    entry
+        .images
+        .first()
+        .map(|img| {
+            img.palette
+                .iter()
+                .filter_map(|hex| hex_to_hsl(hex))
+                .any(|hsl| ColourFamily::of(hsl) == family)
+        })
+        .unwrap_or(false)
+}
+
+/// Order entries around the colour wheel by dominant hue, neutrals last from
+/// light to dark. Entries without a palette keep their relative order at the end.
+pub fn sort_entries_by_colour(entries: &mut [JsonEntry]) {
+    let key = |entry: &JsonEntry| -> (u8, f32) {
+        match dominant_colour(entry).and_then(hex_to_hsl) {
+            Some(hsl) if ColourFamily::of(hsl) == ColourFamily::Neutral => (1, 1.0 - hsl.lightness),
+            Some(hsl) => (0, hsl.hue),
+            None => (2, 0.0),
+        }
+    };
+    entries.sort_by(|a, b| {
+        let (a_group, a_value) = key(a);
+        let (b_group, b_value) = key(b);
+        a_group.cmp(&b_group).then(a_value.total_cmp(&b_value))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use content_service::models::Img;
+
+    fn family(hex: &str) -> ColourFamily {
+        ColourFamily::of(hex_to_hsl(hex).unwrap())
+    }
+
+    fn entry(path: &str, palette: &[&str]) -> JsonEntry {
+        JsonEntry {
+            path: path.to_string(),
+            images: vec![Img {
+                blurhash: String::new(),
+                aspect_ratio: "1/1".to_string(),
+                name: "shot.jpg".to_string(),
+                path: format!("{}/shot.jpg", path),
+                palette: palette.iter().map(|hex| hex.to_string()).collect(),
+                placeholder: None,
+                phash: None,
+                animation: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn converts_hex_to_hsl() {
+        let red = hex_to_hsl("#ff0000").unwrap();
+        assert_eq!((red.hue, red.saturation, red.lightness), (0.0, 1.0, 0.5));
+        assert_eq!(hex_to_hsl("#00ff00").unwrap().hue, 120.0);
+        assert_eq!(hex_to_hsl("#0000FF").unwrap().hue, 240.0);
+        // Magenta wraps round rather than going negative
+        assert_eq!(hex_to_hsl("#ff00ff").unwrap().hue, 300.0);
+
+        let grey = hex_to_hsl("#808080").unwrap();
+        assert_eq!((grey.hue, grey.saturation), (0.0, 0.0));
+        assert!((grey.lightness - 0.502).abs() < 0.001);
+    }
+
+    #[test]
+    fn rejects_malformed_hex() {
+        for hex in [
+            "ff0000", "#ff000", "#ff00000", "#f00", "#gg0000", "#aééa", "",
+        ] {
+            assert_eq!(hex_to_hsl(hex), None, "{}", hex);
+        }
+    }
+
+    #[test]
+    fn greys_and_near_blacks_are_neutral() {
+        for hex in [
+            "#000000", "#0a0505", "#ffffff", "#fdf8f8", "#808080", "#8a7a7a",
+        ] {
+            assert_eq!(family(hex), ColourFamily::Neutral, "{}", hex);
+        }
+        // Saturated enough and light enough to keep its hue
+        assert_eq!(family("#4a0000"), ColourFamily::Red);
+    }
+
+    #[test]
+    fn families_split_at_hue_boundaries() {
+        let at = |hue: f32| {
+            ColourFamily::of(Hsl {
+                hue,
+                saturation: 0.8,
+                lightness: 0.5,
+            })
+        };
+        assert_eq!(at(0.0), ColourFamily::Red);
+        assert_eq!(at(14.9), ColourFamily::Red);
+        assert_eq!(at(15.0), ColourFamily::Orange);
+        assert_eq!(at(45.0), ColourFamily::Yellow);
+        assert_eq!(at(70.0), ColourFamily::Green);
+        assert_eq!(at(170.0), ColourFamily::Blue);
+        assert_eq!(at(260.0), ColourFamily::Purple);
+        assert_eq!(at(344.9), ColourFamily::Purple);
+        assert_eq!(at(345.0), ColourFamily::Red);
+        assert_eq!(at(359.9), ColourFamily::Red);
+    }
+
+    #[test]
+    fn sorts_round_the_wheel_then_neutrals_then_the_rest() {
+        let mut entries = vec![
+            entry("/none", &[]),
+            entry("/blue", &["#2e86c1"]),
+            entry("/black", &["#050505"]),
+            entry("/red", &["#c0392b", "#2e86c1"]),
+            entry("/white", &["#fafafa"]),
+            entry("/broken", &["not a colour"]),
+            entry("/green", &["#27ae60"]),
+        ];
+        sort_entries_by_colour(&mut entries);
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["/red", "/green", "/blue", "/white", "/black", "/none", "/broken"]
+        );
+
+        // Any palette colour counts when filtering
+        assert!(matches_family(&entries[0], ColourFamily::Blue));
+        assert!(!matches_family(&entries[0], ColourFamily::Green));
+    }
+}
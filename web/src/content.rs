@@ -1,74 +1,176 @@
 use crate::console_log;
 use crate::get_base_url;
-use crate::image::get_base64_from_blurhash;
+use crate::image::placeholder_src;
 use crate::log;
 
-use content_service::{ContentServiceClient, ContentServiceError, Img, JsonEntry};
-use futures::lock::Mutex;
-use std::sync::{Arc, LazyLock};
+use content_service::{
+    Animation, ArchiveSection, ContentPage, ContentQuery, ContentServiceClient,
+    ContentServiceError, Document, Img, JsonEntry, Section, Video,
+};
+use std::sync::LazyLock;
 
-static GLOBAL_CONTENT_CLIENT: LazyLock<Arc<Mutex<ContentServiceClient>>> = LazyLock::new(|| {
-    let url = get_base_url!();
-    Arc::new(Mutex::new(ContentServiceClient::with_base_url(url)))
-});
+// One client for the whole site. It takes `&self`, so pages and cards fetch
+// side by side, and a URL several of them want is only fetched once.
+static GLOBAL_CONTENT_CLIENT: LazyLock<ContentServiceClient> =
+    LazyLock::new(|| ContentServiceClient::with_base_url(get_base_url!()));
 
 pub async fn get_global_content(
     path: String,
     filter: Option<String>,
 ) -> Result<Vec<JsonEntry>, ContentServiceError> {
-    let client = GLOBAL_CONTENT_CLIENT.clone();
-    let mut client_ref = client.lock().await;
-    client_ref.get_content(path, filter).await
+    GLOBAL_CONTENT_CLIENT.get_content(path, filter).await
+}
+
+pub async fn get_global_query(query: &ContentQuery) -> Result<ContentPage, ContentServiceError> {
+    GLOBAL_CONTENT_CLIENT.query(query).await
 }
 
 pub async fn get_global_tags(path: String) -> Result<Vec<String>, ContentServiceError> {
-    let client = GLOBAL_CONTENT_CLIENT.clone();
-    let mut client_ref = client.lock().await;
-    client_ref.get_tags(path).await
+    GLOBAL_CONTENT_CLIENT.get_tags(path).await
 }
 
-pub async fn get_global_document(path: &str) -> Result<String, ContentServiceError> {
-    let client = GLOBAL_CONTENT_CLIENT.clone();
-    let mut client_ref = client.lock().await;
-    client_ref.get_document(path).await
+pub async fn get_global_section(path: &str) -> Result<Section, ContentServiceError> {
+    GLOBAL_CONTENT_CLIENT.get_section(path).await
 }
 
-pub fn global_content_service() -> Arc<Mutex<ContentServiceClient>> {
-    GLOBAL_CONTENT_CLIENT.clone()
+pub async fn get_global_archive() -> Result<Vec<ArchiveSection>, ContentServiceError> {
+    GLOBAL_CONTENT_CLIENT.get_archive().await
 }
 
-pub fn strip_frontmatter(content: &str) -> &str {
-    let trimmed = content.trim_start();
-    if trimmed.starts_with("---") {
-        if let Some(end_pos) = trimmed[3..].find("---") {
-            let after_frontmatter = &trimmed[end_pos + 6..];
-            return after_frontmatter.trim_start();
-        }
-    }
-    content
+pub async fn read_global_entries<R>(
+    read: impl FnOnce(&[JsonEntry]) -> R,
+) -> Result<R, ContentServiceError> {
+    GLOBAL_CONTENT_CLIENT.read_entries(read).await
 }
 
-pub fn replace_images(content: &str, images: &[Img]) -> String {
-    let mut result = content.to_string();
+pub async fn get_global_document(path: &str) -> Result<String, ContentServiceError> {
+    GLOBAL_CONTENT_CLIENT.get_document(path).await
+}
 
-    for img in images {
-        let pattern = format!("![[{}]]", img.name);
-        let img_url = format!("{}/content{}", get_base_url!(), img.path);
+pub async fn get_global_parsed_document(path: &str) -> Result<Document, ContentServiceError> {
+    GLOBAL_CONTENT_CLIENT.get_parsed_document(path).await
+}
+
+pub fn flush_global_cache() {
+    GLOBAL_CONTENT_CLIENT.flush_cache();
+}
 
-        let base64 = get_base64_from_blurhash(&img.blurhash);
+pub fn global_content_service() -> &'static ContentServiceClient {
+    &GLOBAL_CONTENT_CLIENT
+}
 
-        let html_img = format!(
-            r#"<div class="article-image-wrap" style="aspect-ratio: {};">
-                <img class="article-image-blur" src="data:image/bmp;base64,{}" alt="blurred image" />
-                <img class="photo-card-img article-image" src="{}" alt="{}" loading="lazy" onload="this.style.opacity=1" />
-            </div>"#,
-            img.aspect_ratio, base64, img_url, img.name
-        );
+/// Poster-first markup for an animated image, kept on one line for markdown.
+/// setupMediaObserver starts it when it scrolls into view and stops it again
+/// when it leaves, an mp4 rendition falls back to the original if it fails.
+pub fn animated_media_html(
+    class: &str,
+    base: &str,
+    img: &Img,
+    animation: &Animation,
+    alt: &str,
+) -> String {
+    let original_url = format!("{}/content{}", base, img.path);
+    let poster_url = animation
+        .poster
+        .as_ref()
+        .map(|poster| format!("{}/content{}", base, poster));
+    let transcoded_url = animation
+        .transcoded
+        .as_ref()
+        .map(|transcoded| format!("{}/content{}", base, transcoded));
+
+    match transcoded_url {
+        Some(video_url) if video_url.ends_with(".mp4") => format!(
+            r#"<video class="{} animated-media" data-animated data-src="{}" data-fallback="{}" poster="{}" aria-label="{}" style="opacity: 1;" muted loop playsinline preload="none"></video>"#,
+            class,
+            video_url,
+            original_url,
+            poster_url.unwrap_or_default(),
+            alt
+        ),
+        transcoded_url => {
+            let animation_url = transcoded_url.unwrap_or(original_url);
+            match poster_url {
+                Some(poster_url) => format!(
+                    r#"<img class="{} animated-media" data-animated src="{}" data-poster="{}" data-src="{}" alt="{}" loading="lazy" onload="this.style.opacity=1" />"#,
+                    class, poster_url, poster_url, animation_url, alt
+                ),
+                // Without a poster there is no still to hold, show it as is
+                None => format!(
+                    r#"<img class="{}" src="{}" alt="{}" loading="lazy" onload="this.style.opacity=1" />"#,
+                    class, animation_url, alt
+                ),
+            }
+        }
+    }
+}
 
-        result = result.replace(&pattern, &html_img);
+/// Markup for a document's `![[name]]` embed, `None` when `name` isn't one
+/// of its images or videos
+pub fn embed_html(name: &str, images: &[Img], videos: &[Video]) -> Option<String> {
+    if let Some(img) = images.iter().find(|img| img.name == name) {
+        return Some(image_html(img));
     }
+    videos
+        .iter()
+        .find(|video| video.name == name)
+        .map(video_html)
+}
+
+fn image_html(img: &Img) -> String {
+    let img_url = format!("{}/content{}", get_base_url!(), img.path);
+
+    let blur_src = placeholder_src(img.placeholder.as_deref(), &img.blurhash, &img.aspect_ratio);
+
+    let main = match &img.animation {
+        Some(animation) => animated_media_html(
+            "photo-card-img article-image",
+            &get_base_url!(),
+            img,
+            animation,
+            &img.name,
+        ),
+        None => format!(
+            r#"<img class="photo-card-img article-image" src="{}" alt="{}" loading="lazy" onload="this.style.opacity=1" />"#,
+            img_url, img.name
+        ),
+    };
+
+    format!(
+        r#"<div class="article-image-wrap" style="aspect-ratio: {};">
+            <img class="article-image-blur" src="{}" alt="blurred image" />
+            {}
+        </div>"#,
+        img.aspect_ratio, blur_src, main
+    )
+}
 
-    result
+fn video_html(video: &Video) -> String {
+    let base = get_base_url!();
+    let video_url = format!("{}/content{}", base, video.path);
+    let poster_url = video
+        .poster
+        .as_ref()
+        .map(|poster| format!("{}/content{}", base, poster))
+        .unwrap_or_default();
+    let aspect_ratio = video.aspect_ratio.as_deref().unwrap_or("16/9");
+
+    // Kept on one line: a blank line would end the HTML block in markdown
+    let blur = match &video.blurhash {
+        Some(blurhash) => format!(
+            r#"<img class="article-image-blur" src="{}" alt="blurred video frame" />"#,
+            placeholder_src(video.placeholder.as_deref(), blurhash, aspect_ratio)
+        ),
+        None => String::new(),
+    };
+
+    // src and poster are filled in by setupMediaObserver once the clip nears the viewport
+    format!(
+        r#"<div class="article-image-wrap" style="aspect-ratio: {};">
+            {}<video class="article-image article-video" data-src="{}" data-poster="{}" title="{}" preload="none" controls playsinline></video>
+        </div>"#,
+        aspect_ratio, blur, video_url, poster_url, video.name
+    )
 }
 
 pub async fn get_entry_by_path(path: &str) -> Option<JsonEntry> {
@@ -115,3 +217,17 @@ pub fn get_tags_from_path(path: &str) -> String {
     }
     tags
 }
+
+/// Value of `key` in the query string of a route or full URL, if present
+pub fn get_query_param(path: &str, key: &str) -> Option<String> {
+    let (_, query) = path.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| {
+            urlencoding::decode(value)
+                .map(|v| v.into_owned())
+                .unwrap_or_else(|_| value.to_string())
+        })
+}
@@ -1,8 +1,60 @@
 use base64::engine::{Engine, general_purpose};
+use std::cell::RefCell;
+use std::collections::HashMap;
 
-pub fn get_base64_from_blurhash(blurhash: &str) -> String {
-    if let Ok(buf) = blurhash::decode(blurhash, 64, 64, 1.0) {
-        return encode_bmp_from_rgb_or_rgba(&buf, 64, 64);
+/// Longer side of placeholders decoded in the browser, matches the helper's default
+const FALLBACK_PLACEHOLDER_SIZE: u32 = 32;
+
+thread_local! {
+    // Decoded placeholders keyed by blurhash and size, cards re-render on every visit
+    static PLACEHOLDER_CACHE: RefCell<HashMap<(String, u32, u32), String>> =
+        RefCell::new(HashMap::new());
+}
+
+/// `src` for a blurred placeholder. Uses the helper's precomputed data URI
+/// when the index has one, and only decodes the blurhash here as a fallback.
+pub fn placeholder_src(placeholder: Option<&str>, blurhash: &str, aspect_ratio: &str) -> String {
+    if let Some(placeholder) = placeholder {
+        return placeholder.to_string();
+    }
+
+    let (width, height) = fallback_dimensions(aspect_ratio);
+    let key = (blurhash.to_string(), width, height);
+    if let Some(cached) = PLACEHOLDER_CACHE.with(|cache| cache.borrow().get(&key).cloned()) {
+        return cached;
+    }
+
+    let base64 = get_base64_from_blurhash(blurhash, width, height);
+    let src = if base64.is_empty() {
+        String::new()
+    } else {
+        format!("data:image/bmp;base64,{}", base64)
+    };
+    PLACEHOLDER_CACHE.with(|cache| cache.borrow_mut().insert(key, src.clone()));
+    src
+}
+
+/// Placeholder size for an "width/height" aspect ratio, square when it can't be read
+fn fallback_dimensions(aspect_ratio: &str) -> (u32, u32) {
+    let size = FALLBACK_PLACEHOLDER_SIZE;
+    let parsed = aspect_ratio
+        .split_once('/')
+        .and_then(|(w, h)| Some((w.trim().parse::<f64>().ok()?, h.trim().parse::<f64>().ok()?)));
+    match parsed {
+        Some((w, h)) if w > 0.0 && h > 0.0 && w.is_finite() && h.is_finite() => {
+            if w >= h {
+                (size, ((size as f64 * h / w).round() as u32).max(1))
+            } else {
+                (((size as f64 * w / h).round() as u32).max(1), size)
+            }
+        }
+        _ => (size, size),
+    }
+}
+
+pub fn get_base64_from_blurhash(blurhash: &str, width: u32, height: u32) -> String {
+    if let Ok(buf) = blurhash::decode(blurhash, width, height, 1.0) {
+        return encode_bmp_from_rgb_or_rgba(&buf, width, height);
     }
     String::new()
 }
@@ -72,3 +124,47 @@ pub fn bmp_rgb24_top_down(rgb: &[u8], width: u32, height: u32) -> Vec<u8> {
 
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLURHASH: &str = "LEHV6nWB2yk8pyo0adR*.7kCMdnj";
+
+    #[test]
+    fn fallback_dimensions_follow_the_aspect_ratio() {
+        assert_eq!(fallback_dimensions("4/3"), (32, 24));
+        assert_eq!(fallback_dimensions(" 3 / 4 "), (24, 32));
+        assert_eq!(fallback_dimensions("1.5/1"), (32, 21));
+        // Extreme ratios keep at least a pixel on the short side
+        assert_eq!(fallback_dimensions("1000/1"), (32, 1));
+
+        for garbage in [
+            "", "4:3", "0/3", "4/0", "-4/3", "a/b", "NaN/1", "inf/1", "4/3/2",
+        ] {
+            assert_eq!(fallback_dimensions(garbage), (32, 32), "{}", garbage);
+        }
+    }
+
+    #[test]
+    fn placeholder_prefers_the_helpers_data_uri() {
+        let uri = "data:image/png;base64,AAAA";
+        assert_eq!(placeholder_src(Some(uri), BLURHASH, "4/3"), uri);
+    }
+
+    #[test]
+    fn missing_placeholder_decodes_the_blurhash() {
+        let src = placeholder_src(None, BLURHASH, "4/3");
+        let bmp = general_purpose::STANDARD
+            .decode(src.strip_prefix("data:image/bmp;base64,").unwrap())
+            .unwrap();
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(u32::from_le_bytes(bmp[18..22].try_into().unwrap()), 32);
+        assert_eq!(i32::from_le_bytes(bmp[22..26].try_into().unwrap()), -24);
+        // Served from the cache the second time
+        assert_eq!(placeholder_src(None, BLURHASH, "4/3"), src);
+
+        assert_eq!(placeholder_src(None, "not a blurhash", "4/3"), "");
+        assert_eq!(placeholder_src(None, "", "garbage"), "");
+    }
+}
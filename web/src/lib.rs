@@ -1,15 +1,17 @@
-use crate::content::{get_global_document, strip_frontmatter};
+use crate::content::{flush_global_cache, get_global_parsed_document};
+use crate::navigation::spawn_for_page;
 use crate::router::Router;
-use pulldown_cmark::{Parser, html};
 use wasm_bindgen::prelude::*;
-use wasm_bindgen_futures::spawn_local;
 use web_sys::{Element, window};
 
+pub mod colour;
 pub mod content;
 pub mod image;
+pub mod navigation;
 pub mod page;
 
 mod pages;
+mod player;
 mod router;
 
 #[wasm_bindgen(start)]
@@ -17,10 +19,36 @@ pub fn main() {
     let window = window().expect("no global `window` exists");
     let document = window.document().expect("should have a document on window");
 
+    init_cache_flush(&window, &document);
     init_shell(document);
     Router::init();
 }
 
+/// Cache reads only reorder its recency list in memory, so it's saved as
+/// the page is hidden or unloaded, the last moments a visit is sure to get
+fn init_cache_flush(window: &web_sys::Window, document: &web_sys::Document) {
+    let on_visibility = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+        if get_document!().hidden() {
+            flush_global_cache();
+        }
+    }) as Box<dyn FnMut(_)>);
+    document
+        .add_event_listener_with_callback(
+            "visibilitychange",
+            on_visibility.as_ref().unchecked_ref(),
+        )
+        .expect("should register visibilitychange listener");
+    on_visibility.forget();
+
+    let on_pagehide = Closure::wrap(Box::new(move |_event: web_sys::Event| {
+        flush_global_cache();
+    }) as Box<dyn FnMut(_)>);
+    window
+        .add_event_listener_with_callback("pagehide", on_pagehide.as_ref().unchecked_ref())
+        .expect("should register pagehide listener");
+    on_pagehide.forget();
+}
+
 #[wasm_bindgen]
 pub fn on_article_card_visible(card_name: &str, card_path: &str) {
     // let card_id = card_id.to_string();
@@ -29,19 +57,16 @@ pub fn on_article_card_visible(card_name: &str, card_path: &str) {
 
     let url = format!("{}/content{}", get_base_url!(), card_path);
 
-    spawn_local(async move {
-        match { get_global_document(&url).await } {
-            Ok(markdown_content) => {
-                let fixed_content = strip_frontmatter(&markdown_content);
-
-                let parser = Parser::new(&fixed_content);
-                let mut html_output = String::new();
-                html::push_html(&mut html_output, parser);
-
+    spawn_for_page(|navigation| async move {
+        match { get_global_parsed_document(&url).await } {
+            Ok(document) => {
+                if !navigation.is_current() {
+                    return;
+                }
                 let id = format!("content{}", &card_path);
                 match get_document!().get_element_by_id(&id) {
                     Some(element) => {
-                        element.set_inner_html(&html_output);
+                        element.set_inner_html(&document.html);
                     }
                     None => {
                         // do nun
@@ -60,11 +85,41 @@ pub fn on_article_card_click(card_path: &str) {
     Router::navigate_to(&card_path);
 }
 
+#[wasm_bindgen]
+pub fn on_scroll_sentinel_visible(section: &str, offset: usize) {
+    pages::macros::append_next_page(section.to_string(), offset);
+}
+
 #[wasm_bindgen]
 pub fn on_tag_click(tag: &str) {
     console_log!("Tag clicked: {}", tag);
 }
 
+#[wasm_bindgen]
+pub fn on_track_play(entry_path: &str) {
+    player::play_entry(entry_path);
+}
+
+#[wasm_bindgen]
+pub fn on_track_queue(entry_path: &str) {
+    player::enqueue_entry(entry_path);
+}
+
+#[wasm_bindgen]
+pub fn on_player_toggle() {
+    player::player_toggle();
+}
+
+#[wasm_bindgen]
+pub fn on_player_next() {
+    player::player_next();
+}
+
+#[wasm_bindgen]
+pub fn on_player_prev() {
+    player::player_prev();
+}
+
 fn init_shell(document: web_sys::Document) {
     let body = document.body().expect("document should have a body");
 
@@ -74,15 +129,31 @@ fn init_shell(document: web_sys::Document) {
             .expect("Failed to remove element");
     }
 
-    // append image
-    let img = document
-        .create_element("img")
-        .expect("Failed to create img element");
-    img.set_attribute("src", "assets/radio.mkv0001-0250.gif")
-        .expect("Failed to add source to img");
-    img.set_attribute("class", "logo")
-        .expect("Failed to add class to img");
-    body.append_child(&img).expect("Failed to add img");
+    // append logo, the poster shows until setupMediaObserver starts the loop
+    // and the gif is only fetched if the mp4 rendition is missing
+    let logo = document
+        .create_element("video")
+        .expect("Failed to create logo element");
+    for (name, value) in [
+        ("class", "logo"),
+        ("poster", "assets/radio.mkv0001-0250.poster.png"),
+        ("data-src", "assets/radio.mkv0001-0250.anim.mp4"),
+        ("data-fallback", "assets/radio.mkv0001-0250.gif"),
+        ("data-animated", ""),
+        ("aria-label", "olifm radio"),
+        ("muted", ""),
+        ("loop", ""),
+        ("playsinline", ""),
+        ("preload", "none"),
+    ] {
+        logo.set_attribute(name, value)
+            .expect("Failed to set logo attribute");
+    }
+    // The attribute alone isn't always honoured for autoplay on created elements
+    if let Some(media) = logo.dyn_ref::<web_sys::HtmlMediaElement>() {
+        media.set_muted(true);
+    }
+    body.append_child(&logo).expect("Failed to add logo");
 
     // append nav
     let nav = document
@@ -103,6 +174,11 @@ fn init_shell(document: web_sys::Document) {
 
     body.append_child(&app)
         .expect("Failed to append app container");
+
+    // mini player lives outside the app container so it survives navigation
+    player::init_player(&document);
+
+    setup_media_observer();
 }
 
 fn init_nav(document: &web_sys::Document, nav: &web_sys::Element) {
@@ -118,6 +194,10 @@ fn init_nav(document: &web_sys::Document, nav: &web_sys::Element) {
     nav.append_child(&sounds)
         .expect("Failed to append sounds button");
 
+    let archive = create_button(&document, "Archive", "archive");
+    nav.append_child(&archive)
+        .expect("Failed to append archive button");
+
     let about = create_button(&document, "About", "about");
     nav.append_child(&about)
         .expect("Failed to append about button");
@@ -141,6 +221,12 @@ extern "C" {
 
     #[wasm_bindgen(js_name = setupArticleObserver)]
     fn setup_article_observer();
+
+    #[wasm_bindgen(js_name = setupMediaObserver)]
+    fn setup_media_observer();
+
+    #[wasm_bindgen(js_name = setupScrollObserver)]
+    fn setup_scroll_observer();
 }
 
 #[macro_export]
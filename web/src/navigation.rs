@@ -0,0 +1,66 @@
+//! Every route change begins a new navigation and cancels the one before.
+//! Work a page starts for itself, fetching its content and writing it into
+//! `#app`, is spawned under the navigation that rendered it, so a slow
+//! response for a page the reader already left is aborted. Before writing
+//! into the DOM, that work checks its navigation is still current, so it
+//! never lands on the page that replaced it.
+
+use content_service::{spawn_cancellable, CancelToken};
+use std::future::Future;
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
+
+static CURRENT: LazyLock<Mutex<Navigation>> = LazyLock::new(Mutex::default);
+
+#[derive(Debug, Clone, Default)]
+pub struct Navigation {
+    generation: u64,
+    cancel: CancelToken,
+}
+
+fn current() -> MutexGuard<'static, Navigation> {
+    CURRENT.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Navigation {
+    /// Start a navigation, cancelling whatever the last one left running
+    pub fn begin() -> Navigation {
+        let mut current = current();
+        current.cancel.cancel();
+        *current = Navigation {
+            generation: current.generation + 1,
+            cancel: CancelToken::new(),
+        };
+        current.clone()
+    }
+
+    /// The navigation of the page on screen
+    pub fn current() -> Navigation {
+        current().clone()
+    }
+
+    /// Whether this is still the page on screen
+    pub fn is_current(&self) -> bool {
+        current().generation == self.generation
+    }
+
+    /// Cancelled when the next navigation begins
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// Run `future` until it's done or the reader navigates away
+    pub fn spawn(&self, future: impl Future<Output = ()> + 'static) {
+        spawn_cancellable(&self.cancel, future);
+    }
+}
+
+/// Run the work `start` returns for the page on screen, see
+/// `Navigation::spawn`. It's handed that page's navigation to check before
+/// writing into the DOM.
+pub fn spawn_for_page<F>(start: impl FnOnce(Navigation) -> F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    let navigation = Navigation::current();
+    navigation.spawn(start(navigation.clone()));
+}
@@ -1,35 +1,53 @@
 use crate::console_log;
 use crate::content::get_tags_from_path;
 use crate::content::{
-    get_global_content, get_global_document, get_global_tags, parse_debug_sequence,
+    get_global_parsed_document, get_global_query, get_global_section, get_global_tags,
+    parse_debug_sequence,
 };
-use regex::Regex;
 
+use crate::get_document;
 use crate::get_full_url;
 use crate::log;
-use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use crate::navigation::spawn_for_page;
+use crate::pages::{page_home, page_pictures, page_section, page_sounds};
+use content_service::models::entry_types;
 use content_service::ContentServiceError;
-use content_service::JsonEntry;
-use std::cmp::Ordering;
-pub enum Style {
-    Card,
-    Photo,
-    Music,
+use content_service::{ContentPage, ContentQuery, Document, JsonEntry, Layout, Section};
+
+/// Layout class added next to `<section>-container`, so a new section gets
+/// the grid or list of its layout without its own CSS
+pub fn layout_class(layout: Layout) -> &'static str {
+    match layout {
+        Layout::Card => "card-layout",
+        Layout::Photo => "photo-layout",
+        Layout::Music => "music-layout",
+        Layout::Timeline => "timeline-layout",
+    }
 }
 
+/// Render the section at `$path` with the title, layout, sort and pinned
+/// entries from its readme's frontmatter, see `content_service::section`.
+/// With `featured`, the site's featured entries lead as a hero strip.
 #[macro_export]
 macro_rules! render_site {
-    ($path:expr, $style:expr) => {{
+    ($path:expr) => {
+        $crate::render_site!($path, false)
+    };
+    ($path:expr, $featured:expr) => {{
         let content_path = $path.to_string();
-        let style = $style;
-        wasm_bindgen_futures::spawn_local(async move {
+        let featured = $featured;
+        $crate::navigation::spawn_for_page(|navigation| async move {
             let base = get_base_url!().to_string();
             let doc_url = format!("{}/content/{}/readme.md", base, content_path);
             match crate::pages::macros::get_page_content(&content_path, &doc_url).await {
-                Ok((mut repo_content, document, tags)) => {
+                Ok((mut repo_content, document, tags, section)) => {
                     let mut html = String::new();
 
-                    load_readme(&mut repo_content, &mut html, &document);
+                    if featured {
+                        html.push_str(&crate::pages::page_home::page_home_hero_html().await);
+                    }
+
+                    load_readme(&mut repo_content, &mut html, &document, &section);
 
                     html.push_str("<div class=\"tag-container\">");
                     html.push_str("<div class=\"tags\">");
@@ -43,31 +61,67 @@ macro_rules! render_site {
                     html.push_str("</div>");
                     html.push_str("</div>");
 
+                    let full_url = crate::get_full_url!();
+                    if let content_service::Layout::Photo = section.layout {
+                        crate::pages::page_pictures::apply_colour_view(
+                            &mut repo_content,
+                            &full_url,
+                        );
+                        html.push_str(
+                            &crate::pages::page_pictures::page_pictures_colour_controls_html(
+                                &content_path,
+                                &full_url,
+                            ),
+                        );
+                    }
+
+                    let page = crate::content::get_query_param(&full_url, "page")
+                        .and_then(|page| page.parse().ok())
+                        .unwrap_or(1);
+                    let listing = content_service::ContentPage::new(
+                        repo_content,
+                        content_service::paging::page_offset(page, section.page_size),
+                        section.page_size,
+                    );
+
+                    if let content_service::Layout::Music = section.layout {
+                        crate::player::set_playlist(crate::player::tracks_from_entries(
+                            &listing.entries,
+                        ));
+                    }
+
                     if content_path != "" {
-                        let div_class = format!("{}-container", &content_path);
-                        html.push_str(&format!("<div class=\"{}\">", div_class));
-                        for item in repo_content {
-                            match style {
-                                // TODO: Custom music card implementation
-                                Style::Card | Style::Music => html
-                                    .push_str(&crate::pages::page_home::page_home_card_html(item)),
-                                Style::Photo => html.push_str(
-                                    &crate::pages::page_pictures::page_pictures_card_html(item),
-                                ),
-                            }
-                        }
+                        html.push_str(&format!(
+                            "<div class=\"{}-container {}\">",
+                            &content_path,
+                            crate::pages::macros::layout_class(section.layout)
+                        ));
+                        html.push_str(&crate::pages::macros::cards_html(
+                            section.layout,
+                            &listing.entries,
+                        ));
                         html.push_str("</div>");
+                        html.push_str(&crate::pages::macros::pager_html(
+                            &content_path,
+                            &full_url,
+                            &listing,
+                            section.infinite_scroll,
+                        ));
                     }
 
-                    let parser = Parser::new(&html);
-                    let mut html_output = String::new();
-                    html::push_html(&mut html_output, parser);
-
-                    get_app!().set_inner_html(&html_output);
+                    if !navigation.is_current() {
+                        return;
+                    }
+                    get_app!().set_inner_html(&html);
                     setup_article_observer();
+                    crate::setup_media_observer();
+                    crate::setup_scroll_observer();
                 }
                 Err(e) => {
                     crate::console_log!("Error fetching directory data: {:?}", e);
+                    if !navigation.is_current() {
+                        return;
+                    }
                     get_app!().set_inner_html(&format!(
                         "<p style=\"color: red;\">Error loading content: {}</p>",
                         e
@@ -78,115 +132,230 @@ macro_rules! render_site {
     }};
 }
 
-pub async fn get_page_content(
-    _path: &str,
-    doc_url: &str,
-) -> Result<(Vec<JsonEntry>, String, Vec<String>), ContentServiceError> {
-    let full_url = get_full_url!();
-    let path = format!("/{}", _path);
-    let mut items = get_global_content(path.clone(), Some("file".to_string())).await?;
-
-    sort_entries_by_date(&mut items, true);
+/// Cards for `items` in the section's layout
+pub fn cards_html(layout: Layout, items: &[JsonEntry]) -> String {
+    if let Layout::Timeline = layout {
+        return page_section::page_timeline_html(items);
+    }
+    let mut html = String::new();
+    for item in items.iter().cloned() {
+        match layout {
+            Layout::Photo => html.push_str(&page_pictures::page_pictures_card_html(item)),
+            Layout::Music => html.push_str(&page_sounds::page_sounds_card_html(item)),
+            _ => html.push_str(&page_home::page_home_card_html(item)),
+        }
+    }
+    html
+}
 
-    let tags = get_global_tags(path.clone()).await?;
+/// What follows a paged listing: links to the pages either side, or with
+/// infinite scroll a sentinel that loads the next page when it comes into
+/// view. Nothing when everything fits on one page.
+pub fn pager_html(section: &str, url: &str, listing: &ContentPage, infinite: bool) -> String {
+    if infinite {
+        return match listing.next_offset() {
+            Some(offset) => format!(
+                "<div class=\"scroll-sentinel\" data-section=\"{}\" data-offset=\"{}\"></div>",
+                section, offset
+            ),
+            None => String::new(),
+        };
+    }
+    if listing.pages() <= 1 {
+        return String::new();
+    }
 
-    let page_tags_raw = get_tags_from_path(&full_url);
-    let allowed_tags = page_tags_raw
-        .split(',')
-        .map(|tag| tag.trim().to_string())
-        .filter(|tag| !tag.is_empty())
-        .collect::<Vec<String>>();
+    let page = listing.page();
+    let mut html = String::from("<nav class=\"pager\">");
+    if page > 1 {
+        html.push_str(&format!(
+            "<a class=\"tag\" href=\"{}\">← Previous</a>",
+            page_href(section, url, page - 1)
+        ));
+    }
+    html.push_str(&format!(
+        "<span class=\"pager-status\">Page {} of {}</span>",
+        page,
+        listing.pages()
+    ));
+    if listing.next_offset().is_some() {
+        html.push_str(&format!(
+            "<a class=\"tag\" href=\"{}\">Next →</a>",
+            page_href(section, url, page + 1)
+        ));
+    }
+    html.push_str("</nav>");
+    html
+}
 
-    let mut to_keep = Vec::new();
-    if (allowed_tags.len() == 0) {
-        to_keep = items.clone();
+/// Route to `page` of `section`, keeping the tag and colour filters of `url`
+fn page_href(section: &str, url: &str, page: usize) -> String {
+    let mut params: Vec<String> = url
+        .split_once('?')
+        .map(|(_, query)| {
+            query
+                .split('&')
+                .filter(|param| !param.is_empty() && !param.starts_with("page="))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    if page > 1 {
+        params.push(format!("page={}", page));
+    }
+    if params.is_empty() {
+        format!("#/{}", section)
     } else {
-        for item in &items {
-            if let Some(item_tags) = item.metadata.get("tags") {
-                let re = Regex::new(r#"String\("([^"]*)"\)"#).unwrap();
-                let tags: Vec<&str> = re
-                    .captures_iter(item_tags)
-                    .map(|cap| cap.get(1).unwrap().as_str())
-                    .collect();
-                // if item doesn't contain any of the page tags, skip it
-                let mut tag_matches = 0;
-                for item_tag in &tags {
-                    for all_tag in &allowed_tags {
-                        let to_push = item.clone();
-                        if item_tag == all_tag {
-                            tag_matches += 1;
-                        }
-                        if tag_matches == allowed_tags.len() && !to_keep.contains(&to_push) {
-                            to_keep.push(to_push);
-                            break;
-                        }
+        format!("#/{}?{}", section, params.join("&amp;"))
+    }
+}
+
+/// Infinite scroll: add the page of `content_path` starting at `offset` to
+/// the listing, then move the sentinel on or drop it after the last page
+pub fn append_next_page(content_path: String, offset: usize) {
+    spawn_for_page(|navigation| async move {
+        let (section, listing) = match next_page(&content_path, offset).await {
+            Ok(next) => next,
+            Err(e) => {
+                console_log!(
+                    "Failed to load the next page of '{}': {:?}",
+                    content_path,
+                    e
+                );
+                return;
+            }
+        };
+        if let Layout::Music = section.layout {
+            crate::player::extend_playlist(crate::player::tracks_from_entries(&listing.entries));
+        }
+
+        if !navigation.is_current() {
+            return;
+        }
+        let Some(sentinel) = get_document!()
+            .query_selector(".scroll-sentinel")
+            .ok()
+            .flatten()
+        else {
+            return;
+        };
+        if let Some(container) = sentinel.previous_element_sibling() {
+            let html = match section.layout {
+                // A year running over from the last page carries on in its
+                // group rather than getting a second heading
+                Layout::Timeline => {
+                    let last_group = container.last_element_child();
+                    let last_year = last_group
+                        .as_ref()
+                        .and_then(|group| group.get_attribute("data-year"));
+                    let (continued, rest) = page_section::timeline_continuation_html(
+                        &listing.entries,
+                        last_year.as_deref(),
+                    );
+                    if let Some(list) = last_group
+                        .and_then(|group| group.query_selector(".timeline-list").ok().flatten())
+                    {
+                        let _ = list.insert_adjacent_html("beforeend", &continued);
                     }
+                    rest
                 }
+                layout => cards_html(layout, &listing.entries),
+            };
+            let _ = container.insert_adjacent_html("beforeend", &html);
+        }
+        match listing.next_offset() {
+            Some(next) => {
+                let _ = sentinel.set_attribute("data-offset", &next.to_string());
+                let _ = sentinel.remove_attribute("data-loading");
             }
+            None => sentinel.remove(),
         }
+
+        crate::setup_article_observer();
+        crate::setup_media_observer();
+        crate::setup_scroll_observer();
+    });
+}
+
+/// The page of `content_path`'s listing from `offset`, the way the first
+/// page was cut from it once the readme was taken out
+async fn next_page(
+    content_path: &str,
+    offset: usize,
+) -> Result<(Section, ContentPage), ContentServiceError> {
+    let full_url = get_full_url!();
+    let path = format!("/{}", content_path);
+    let section = get_global_section(&path).await?;
+    let query = listing_query(&path, &full_url, &section).exclude(format!("{}/readme.md", path));
+
+    // Colours aren't something the query can filter or sort on, so those
+    // views are cut from the whole listing
+    if section.layout == Layout::Photo && page_pictures::has_colour_view(&full_url) {
+        let mut items = get_global_query(&query).await?.entries;
+        page_pictures::apply_colour_view(&mut items, &full_url);
+        let listing = ContentPage::new(items, offset, section.page_size);
+        return Ok((section, listing));
     }
+    let listing = get_global_query(&query.offset(offset).limit(section.page_size)).await?;
+    Ok((section, listing))
+}
+
+/// The documents under `path` carrying every tag picked in `url`, in the
+/// section's order
+fn listing_query(path: &str, url: &str, section: &Section) -> ContentQuery {
+    let page_tags = get_tags_from_path(url);
+    let picked = page_tags
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty());
+    ContentQuery::new(path)
+        .entry_type(entry_types::DOCUMENT)
+        .all_tags(picked)
+        .arranged_like(section)
+}
+
+pub async fn get_page_content(
+    _path: &str,
+    doc_url: &str,
+) -> Result<(Vec<JsonEntry>, Document, Vec<String>, Section), ContentServiceError> {
+    let full_url = get_full_url!();
+    let path = format!("/{}", _path);
+    let section = get_global_section(&path).await?;
+    let tags = get_global_tags(path.clone()).await?;
+
+    let query = listing_query(&path, &full_url, &section);
+    let items = get_global_query(&query).await?.entries;
 
-    let mut document = get_global_document(doc_url).await?;
+    let document = get_global_parsed_document(doc_url).await?;
 
-    Ok((to_keep, document, tags))
+    Ok((items, document, tags, section))
 }
 
-pub fn load_readme(content: &mut Vec<JsonEntry>, html: &mut String, document: &String) {
+pub fn load_readme(
+    content: &mut Vec<JsonEntry>,
+    html: &mut String,
+    document: &Document,
+    section: &Section,
+) {
     html.push_str("<div class=\"page-title\">");
+    // Readmes usually open with the same heading, which is shown once
+    if let Some(title) = &section.title {
+        let repeated = document.outline.first().is_some_and(|heading| {
+            heading.level == 1 && heading.text.trim().eq_ignore_ascii_case(title.trim())
+        });
+        if !repeated {
+            html.push_str(&format!("<h1>{}</h1>", title));
+        }
+    }
+    // Matched on the file name, frontmatter can rename the entry
     if let Some(index) = content
         .iter()
-        .position(|item| item.name.to_lowercase() == "readme.md")
+        .position(|item| item.path.to_lowercase().ends_with("/readme.md"))
     {
         content.remove(index);
-        html.push_str(&document);
     } else {
         console_log!("No readme found");
-        html.push_str(&document);
     }
+    html.push_str(&document.html);
     html.push_str("</div>");
 }
-
-fn parse_date_to_utc(s: &str) -> Option<DateTime<Utc>> {
-    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
-        return Some(dt.with_timezone(&Utc));
-    }
-    if let Ok(ndt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
-        return Some(DateTime::<Utc>::from_utc(ndt, Utc));
-    }
-
-    if let Ok(nd) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
-        return Some(DateTime::<Utc>::from_utc(nd.and_hms(0, 0, 0), Utc));
-    }
-    let fmts = ["%Y/%m/%d", "%d-%m-%Y", "%m/%d/%Y", "%B %d, %Y", "%b %d, %Y"];
-
-    for fmt in &fmts {
-        if let Ok(ndt) = NaiveDateTime::parse_from_str(s, fmt) {
-            return Some(DateTime::<Utc>::from_utc(ndt, Utc));
-        }
-        if let Ok(nd) = NaiveDate::parse_from_str(s, fmt) {
-            return Some(DateTime::<Utc>::from_utc(nd.and_hms(0, 0, 0), Utc));
-        }
-    }
-
-    None
-}
-
-pub fn sort_entries_by_date(entries: &mut [JsonEntry], newest_first: bool) {
-    entries.sort_by(|a, b| {
-        let a_date = a.metadata.get("date").and_then(|s| parse_date_to_utc(s));
-        let b_date = b.metadata.get("date").and_then(|s| parse_date_to_utc(s));
-
-        match (a_date, b_date) {
-            (Some(ad), Some(bd)) => {
-                if newest_first {
-                    bd.cmp(&ad)
-                } else {
-                    ad.cmp(&bd)
-                }
-            }
-            (Some(_), None) => Ordering::Less,
-            (None, Some(_)) => Ordering::Greater,
-            (None, None) => a.name.cmp(&b.name),
-        }
-    });
-}
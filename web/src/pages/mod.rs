@@ -1,7 +1,9 @@
 pub mod macros;
 pub mod page_about;
+pub mod page_archive;
 pub mod page_document;
 pub mod page_home;
 pub mod page_not_found;
 pub mod page_pictures;
+pub mod page_section;
 pub mod page_sounds;
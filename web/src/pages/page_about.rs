@@ -1,15 +1,14 @@
 use crate::console_log;
-use crate::content::{get_global_content, get_global_document};
+use crate::content::{get_global_content, get_global_parsed_document};
+use content_service::models::entry_types;
 use crate::get_app;
 use crate::get_base_url;
 use crate::log;
 use crate::page::Page as PageType;
 use crate::pages::macros::load_readme;
 
-use crate::pages::macros::Style;
 use crate::render_site;
 use crate::setup_article_observer;
-use pulldown_cmark::{Parser, html};
 use std::collections::HashMap;
 
 pub fn page_about() -> PageType {
@@ -17,7 +16,7 @@ pub fn page_about() -> PageType {
     let render = |_: &PageType| "".to_string();
 
     let on_after_render = || {
-        render_site!("resume", Style::Card);
+        render_site!("resume");
         // render_readme();
         // render_employment_history();
     };
@@ -26,26 +25,27 @@ pub fn page_about() -> PageType {
 }
 
 fn render_readme() {
-    wasm_bindgen_futures::spawn_local(async {
+    crate::navigation::spawn_for_page(|navigation| async move {
         let mut html = String::new();
         let base = get_base_url!().to_string();
         let doc_url = format!("{}/content/readme.md", base).to_string();
-        match get_global_document(&doc_url).await {
-            Ok(document) => {
-                let parser = Parser::new(&document);
-                let mut html_output = String::new();
-                html::push_html(&mut html_output, parser);
-                html.push_str(&html_output);
-            }
+        match get_global_parsed_document(&doc_url).await {
+            Ok(document) => html.push_str(&document.html),
             Err(e) => html.push_str(&format!(
                 "<p style=\"color: red;\">Error loading content: {}</p>",
                 e
             )),
         }
 
-        let items = get_global_content("/resume".to_string(), Some("file".to_string())).await;
+        let items = get_global_content(
+            "/resume".to_string(),
+            Some(entry_types::DOCUMENT.to_string()),
+        )
+        .await;
 
-        get_app!().set_inner_html(&html);
+        if navigation.is_current() {
+            get_app!().set_inner_html(&html);
+        }
     });
 }
 
@@ -0,0 +1,182 @@
+use crate::console_log;
+use crate::content::{get_global_archive, get_global_query, get_global_section};
+use crate::get_document;
+use crate::log;
+use crate::navigation::spawn_for_page;
+use crate::page::Page as PageType;
+
+use chrono::NaiveDate;
+use content_service::dates::parse_content_date;
+use content_service::models::entry_types;
+use content_service::{ArchiveMonth, ArchiveSection, ContentQuery, JsonEntry};
+use std::collections::HashMap;
+
+/// `#/archive` lists every year of each section, `#/archive/<year>` the
+/// posts of that year by month
+pub fn page_archive(year: Option<&str>) -> PageType {
+    let mut params = HashMap::new();
+    if let Some(year) = year {
+        params.insert("year".to_string(), year.to_string());
+    }
+
+    let container_id = "archive-content";
+    let render = move |_: &PageType| {
+        format!(
+            r#"
+            <div class="archive-container">
+                <div id="{}" class="archive-content">
+                    <div class="loading">Fetching archive...</div>
+                </div>
+            </div>
+            "#,
+            container_id
+        )
+    };
+
+    let year = year.map(|year| year.parse::<i32>().ok());
+    let on_after_render = move || {
+        spawn_for_page(|navigation| async move {
+            let html = match year {
+                None => archive_html().await,
+                Some(Some(year)) => archive_year_html(year).await,
+                Some(None) => not_found_html(),
+            };
+            if !navigation.is_current() {
+                return;
+            }
+            if let Some(element) = get_document!().get_element_by_id(container_id) {
+                element.set_inner_html(&html);
+            } else {
+                console_log!("Could not find archive container element");
+            }
+        });
+    };
+
+    PageType::new("Archive", params, render).with_on_after_render(Some(Box::new(on_after_render)))
+}
+
+/// Years of every section with a count each, months folded inside
+async fn archive_html() -> String {
+    let archive = load_archive().await;
+    if archive.is_empty() {
+        return "<h1>Archive</h1><p>Nothing dated yet.</p>".to_string();
+    }
+
+    let mut html = String::from("<h1>Archive</h1>");
+    for section in &archive {
+        html.push_str(&format!(
+            "<h2>{} <span class=\"archive-count\">{}</span></h2>",
+            section_title(section).await,
+            section.count
+        ));
+        for year in &section.years {
+            html.push_str(&format!(
+                "<details class=\"archive-year\"><summary><a href=\"#/archive/{}\">{}</a> <span class=\"archive-count\">{}</span></summary><ul class=\"archive-months\">",
+                year.year, year.year, year.count
+            ));
+            for month in &year.months {
+                html.push_str(&format!(
+                    "<li>{} <span class=\"archive-count\">{}</span></li>",
+                    month_name(year.year, month),
+                    month.count
+                ));
+            }
+            html.push_str("</ul></details>");
+        }
+    }
+    html
+}
+
+/// One year's posts in every section, a collapsible group per month
+async fn archive_year_html(year: i32) -> String {
+    let archive = load_archive().await;
+    let query = ContentQuery::new("")
+        .entry_type(entry_types::DOCUMENT)
+        .since(NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or_default())
+        .until(NaiveDate::from_ymd_opt(year, 12, 31).unwrap_or_default());
+    let entries = match get_global_query(&query).await {
+        Ok(page) => page.entries,
+        Err(e) => {
+            console_log!("Failed to load archive entries: {:?}", e);
+            Vec::new()
+        }
+    };
+    let by_path: HashMap<&str, &JsonEntry> = entries
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+
+    let mut html = format!(
+        "<nav><a href=\"#/archive\">← Archive</a></nav><h1>{}</h1>",
+        year
+    );
+    let mut found = false;
+    for section in &archive {
+        let Some(archive_year) = section.years.iter().find(|y| y.year == year) else {
+            continue;
+        };
+        found = true;
+        html.push_str(&format!(
+            "<h2>{} <span class=\"archive-count\">{}</span></h2>",
+            section_title(section).await,
+            archive_year.count
+        ));
+        for month in &archive_year.months {
+            html.push_str(&format!(
+                "<details class=\"archive-month\" open><summary>{} <span class=\"archive-count\">{}</span></summary><ul class=\"timeline-list\">",
+                month_name(year, month),
+                month.count
+            ));
+            for path in &month.entries {
+                let entry = by_path.get(path.as_str());
+                let name = entry.map(|entry| entry.name.as_str()).unwrap_or(path);
+                let day = entry
+                    .and_then(|entry| entry.date.as_deref())
+                    .and_then(parse_content_date)
+                    .map(|day| day.format("%b %-d").to_string())
+                    .unwrap_or_default();
+                html.push_str(&format!(
+                    "<li class=\"timeline-item\" onclick=\"on_article_card_click('{}')\"><span class=\"timeline-date\">{}</span><strong>{}</strong></li>",
+                    path.replace("'", "\\'"),
+                    day,
+                    name
+                ));
+            }
+            html.push_str("</ul></details>");
+        }
+    }
+
+    if !found {
+        html.push_str("<p>Nothing was posted that year.</p>");
+    }
+    html
+}
+
+async fn load_archive() -> Vec<ArchiveSection> {
+    match get_global_archive().await {
+        Ok(archive) => archive,
+        Err(e) => {
+            console_log!("Failed to load archive: {:?}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// The section readme's title, else its folder name
+async fn section_title(section: &ArchiveSection) -> String {
+    match get_global_section(&section.section).await {
+        Ok(found) if found.title.is_some() => found.title.unwrap_or_default(),
+        _ => section.section.trim_start_matches('/').to_string(),
+    }
+}
+
+fn month_name(year: i32, month: &ArchiveMonth) -> String {
+    NaiveDate::from_ymd_opt(year, month.month, 1)
+        .map(|day| day.format("%B").to_string())
+        .unwrap_or_else(|| month.month.to_string())
+}
+
+fn not_found_html() -> String {
+    "<h1>Archive</h1><p>That isn't a year.</p><nav><a href=\"#/archive\">← Archive</a></nav>"
+        .to_string()
+}
@@ -1,18 +1,20 @@
 use crate::console_log;
 use crate::content::{
-    get_global_content, get_global_document, parse_debug_sequence, replace_images,
-    strip_frontmatter,
+    embed_html, get_global_content, get_global_parsed_document, parse_debug_sequence,
+    read_global_entries,
 };
 use crate::get_base_url;
 use crate::get_document;
+use crate::image::placeholder_src;
 use crate::log;
+use crate::navigation::spawn_for_page;
 use crate::page::Page as PageType;
-use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
-use content_service::{Img, JsonEntry};
-use pulldown_cmark::{Parser, html};
-use std::cmp::Ordering;
+use crate::pages::page_not_found::not_found_html;
+use crate::setup_media_observer;
+use content_service::dates::parse_content_date;
+use content_service::models::entry_types;
+use content_service::{ContentServiceError, Img, JsonEntry, Video};
 use std::collections::HashMap;
-use wasm_bindgen_futures::spawn_local;
 
 pub fn page_document(document: &str) -> PageType {
     let params = HashMap::new();
@@ -36,7 +38,7 @@ pub fn page_document(document: &str) -> PageType {
     let on_after_render = move || {
         let document_path = document_path.clone();
 
-        spawn_local(async move {
+        spawn_for_page(|navigation| async move {
             let decoded_path = urlencoding::decode(&document_path)
                 .unwrap_or_else(|_| document_path.clone().into())
                 .into_owned();
@@ -45,57 +47,76 @@ pub fn page_document(document: &str) -> PageType {
             console_log!("Fetching document from {}", url);
 
             let mut img: Vec<Img> = Vec::new();
+            let mut videos: Vec<Video> = Vec::new();
             let mut metadata_entry: Option<JsonEntry> = None;
 
-            let items: Vec<JsonEntry> =
-                get_global_content(decoded_path.clone(), Some("file".to_string()))
-                    .await
-                    .expect("should have content");
+            let items = match get_global_content(
+                decoded_path.clone(),
+                Some(entry_types::DOCUMENT.to_string()),
+            )
+            .await
+            {
+                Ok(items) => items,
+                Err(e) => {
+                    console_log!("Failed to load the index for '{}': {:?}", decoded_path, e);
+                    if navigation.is_current() {
+                        show_error(container_id, &decoded_path, e);
+                    }
+                    return;
+                }
+            };
 
             if let Some(pos) = items.iter().position(|item| item.path == decoded_path) {
                 let meta = items[pos].clone();
                 img = meta.images.clone();
+                videos = meta.videos.clone();
                 metadata_entry = Some(meta);
             }
 
-            match get_global_document(&url).await {
-                Ok(markdown_content) => {
-                    let untagged_content = strip_frontmatter(&markdown_content);
-                    let fixed_content = replace_images(&untagged_content, &img);
-
-                    let parser = Parser::new(&fixed_content);
-                    let mut html_output = String::new();
-                    html::push_html(&mut html_output, parser);
+            match get_global_parsed_document(&url).await {
+                Ok(document) => {
+                    let html_output = document.render(|name| embed_html(name, &img, &videos));
 
                     // Generate metadata section
-                    let metadata_html = if let Some(entry) = metadata_entry {
-                        render_document_metadata(&entry)
+                    let metadata_html = if let Some(entry) = &metadata_entry {
+                        render_document_metadata(entry)
                     } else {
                         String::new()
                     };
 
-                    // Combine metadata and content
-                    let final_html = format!("{}{}", metadata_html, html_output);
+                    // Recommendations were ranked by the helper, we only look them up
+                    let similar_html = match &metadata_entry {
+                        Some(entry) if !entry.similar.is_empty() => {
+                            match read_global_entries(|index| render_similar_photos(entry, index))
+                                .await
+                            {
+                                Ok(html) => html,
+                                Err(e) => {
+                                    console_log!("Failed to load similar photos: {:?}", e);
+                                    String::new()
+                                }
+                            }
+                        }
+                        _ => String::new(),
+                    };
 
+                    // Combine metadata, content and recommendations
+                    let final_html = format!("{}{}{}", metadata_html, html_output, similar_html);
+
+                    if !navigation.is_current() {
+                        return;
+                    }
                     if let Some(element) = get_document!().get_element_by_id(container_id) {
                         element.set_inner_html(&final_html);
+                        setup_media_observer();
                     } else {
                         console_log!("Could not find document container element");
                     }
                 }
                 Err(e) => {
                     console_log!("Failed to load document '{}': {:?}", decoded_path, e);
-                    if let Some(element) = get_document!().get_element_by_id(container_id) {
-                        element.set_inner_html(&format!(
-                            r#"
-                            <div class="error">
-                                <h2>Document Not Found</h2>
-                                <p>Could not load document: {}</p>
-                                <p>Error: {:?}</p>
-                            </div>
-                            "#,
-                            decoded_path, e
-                        ));
+                    if navigation.is_current() {
+                        show_error(container_id, &decoded_path, e);
                     }
                 }
             }
@@ -105,6 +126,29 @@ pub fn page_document(document: &str) -> PageType {
     PageType::new(document, params, render).with_on_after_render(Some(Box::new(on_after_render)))
 }
 
+/// Not found for a missing document, otherwise say it couldn't be loaded
+fn show_error(container_id: &str, path: &str, e: ContentServiceError) {
+    let html = match e {
+        ContentServiceError::NotFound { .. } => not_found_html(
+            "404 - Document Not Found",
+            &format!("There's no document at {}.", path),
+        ),
+        e => format!(
+            r#"
+            <div class="error">
+                <h2>Document Unavailable</h2>
+                <p>Could not load document: {}</p>
+                <p>{}</p>
+            </div>
+            "#,
+            path, e
+        ),
+    };
+    if let Some(element) = get_document!().get_element_by_id(container_id) {
+        element.set_inner_html(&html);
+    }
+}
+
 fn render_document_metadata(entry: &JsonEntry) -> String {
     let mut html = String::new();
 
@@ -114,7 +158,19 @@ fn render_document_metadata(entry: &JsonEntry) -> String {
     html.push_str(&format!("<h1 style=\"margin: 0;\">{}</h1>", entry.name));
 
     if let Some(date) = entry.metadata.get("date") {
-        html.push_str(&format!("<h2 style=\"margin-top: 0;\">{}</div>", date));
+        html.push_str(&format!("<h2 style=\"margin-top: 0;\">{}</h2>", date));
+    }
+
+    // Only worth saying when the post was edited after the day it went up
+    let published = entry.date.as_deref().and_then(parse_content_date);
+    let updated = entry.updated.as_deref().and_then(parse_content_date);
+    if let (Some(published), Some(updated)) = (published, updated) {
+        if updated > published {
+            html.push_str(&format!(
+                "<p class=\"document-updated\">Updated {}</p>",
+                updated.format("%-d %B %Y")
+            ));
+        }
     }
 
     html.push_str("</div>");
@@ -145,6 +201,22 @@ fn render_document_metadata(entry: &JsonEntry) -> String {
         ));
     }
 
+    if !entry.changes.is_empty() {
+        let changes: String = entry
+            .changes
+            .iter()
+            .map(|change| format!("<li>{} &middot; {}</li>", change.date, change.summary))
+            .collect();
+        metadata_rows.push_str(&format!(
+            "<tr>
+                <td class=\"list-cell\">
+                    <details class=\"document-changes\"><summary>Changes</summary><ul>{}</ul></details>
+                </td>
+            </tr>",
+            changes
+        ));
+    }
+
     if !metadata_rows.is_empty() {
         html.push_str(&format!(
             "<div class=\"document-metadata\">
@@ -158,3 +230,54 @@ fn render_document_metadata(entry: &JsonEntry) -> String {
 
     html
 }
+
+fn render_similar_photos(entry: &JsonEntry, index: &[JsonEntry]) -> String {
+    let base = get_base_url!().to_string();
+    let mut cards = String::new();
+
+    for similar in &entry.similar {
+        let Some(other) = index.iter().find(|item| item.path == similar.path) else {
+            continue;
+        };
+        let Some(img) = other.images.first() else {
+            continue;
+        };
+
+        let blur_src =
+            placeholder_src(img.placeholder.as_deref(), &img.blurhash, &img.aspect_ratio);
+        let background = img
+            .palette
+            .first()
+            .map(|colour| format!(" background-color: {};", colour))
+            .unwrap_or_default();
+
+        cards.push_str(&format!(
+            "<div class=\"similar-card\" onclick=\"on_article_card_click('{}')\">
+                <div class=\"photo-card-img-wrap\" style=\"aspect-ratio: {};{}\">
+                    <img class=\"photo-card-blur\" src=\"{}\" alt=\"blurred image\" />
+                    <img class=\"photo-card-img\" src=\"{}/content{}\" alt=\"{}\" loading=\"lazy\" onload=\"this.style.opacity=1\"/>
+                </div>
+                <span>{}</span>
+            </div>",
+            other.path.replace("'", "\\'"),
+            img.aspect_ratio,
+            background,
+            blur_src,
+            base,
+            img.path,
+            other.name,
+            other.name
+        ));
+    }
+
+    if cards.is_empty() {
+        return String::new();
+    }
+    format!(
+        "<div class=\"similar-photos\">
+            <h3>Similar photos</h3>
+            <div class=\"similar-strip\">{}</div>
+        </div>",
+        cards
+    )
+}
@@ -1,18 +1,19 @@
+use crate::colour::dominant_colour;
 use crate::console_log;
 use crate::content::get_global_document;
-use crate::content::parse_debug_sequence;
+use crate::content::{get_global_content, parse_debug_sequence};
 use crate::get_app;
 
 use crate::get_base_url;
+use crate::image::placeholder_src;
 use crate::log;
 use crate::page::Page as PageType;
-use crate::pages::macros::Style;
 use crate::pages::macros::load_readme;
 use crate::render_site;
 use crate::setup_article_observer;
 
 use content_service::JsonEntry;
-use pulldown_cmark::{Parser, html};
+use content_service::models::entry_types;
 use std::collections::HashMap;
 
 pub fn page_home() -> PageType {
@@ -21,7 +22,7 @@ pub fn page_home() -> PageType {
     let render = |_: &PageType| "loading contents...".to_string();
 
     let on_after_render = || {
-        render_site!("blog", Style::Card);
+        render_site!("blog", true);
         load_cache();
     };
 
@@ -30,15 +31,64 @@ pub fn page_home() -> PageType {
 
 fn load_cache() {
     wasm_bindgen_futures::spawn_local(async {
-        let pages: [&str; 3] = ["pictures", "sounds", "resume"];
-        for page in pages {
-            let base = get_base_url!().to_string();
-            let doc_url = format!("{}/content/{}/readme.md", base, page).to_string();
-            get_global_document(&doc_url).await;
-        }
+        let base = get_base_url!().to_string();
+        let doc_urls = ["pictures", "sounds", "resume"]
+            .map(|page| format!("{}/content/{}/readme.md", base, page));
+        futures::future::join_all(doc_urls.iter().map(|url| get_global_document(url))).await;
     });
 }
 
+/// Strip of every entry marked `featured: true`, pinned ones first then the
+/// newest. Empty when nothing is featured. Kept on one line for markdown.
+pub async fn page_home_hero_html() -> String {
+    let featured: Vec<JsonEntry> =
+        match get_global_content(String::new(), Some(entry_types::DOCUMENT.to_string())).await {
+            Ok(items) => items.into_iter().filter(|item| item.featured).collect(),
+            Err(e) => {
+                console_log!("Failed to load featured entries: {:?}", e);
+                return String::new();
+            }
+        };
+    if featured.is_empty() {
+        return String::new();
+    }
+
+    let base = get_base_url!().to_string();
+    let mut html = String::from("<div class=\"hero-strip\">");
+    for item in &featured {
+        let image = match item.images.first() {
+            Some(img) => {
+                // Animated images show their still poster here
+                let src = img
+                    .animation
+                    .as_ref()
+                    .and_then(|animation| animation.poster.as_ref())
+                    .unwrap_or(&img.path);
+                format!(
+                    "<img class=\"hero-card-img\" src=\"{}/content{}\" alt=\"{}\" loading=\"lazy\" style=\"background-image: url('{}');\"/>",
+                    base,
+                    src,
+                    item.name,
+                    placeholder_src(img.placeholder.as_deref(), &img.blurhash, &img.aspect_ratio)
+                )
+            }
+            None => String::new(),
+        };
+        let colour = dominant_colour(item)
+            .map(|colour| format!(" style=\"background-color: {};\"", colour))
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<div class=\"base-card hero-card\"{} onclick=\"on_article_card_click('{}')\">{}<strong>{}</strong></div>",
+            colour,
+            item.path.replace("'", "\\'"),
+            image,
+            item.name
+        ));
+    }
+    html.push_str("</div>");
+    html
+}
+
 pub fn page_home_card_html(item: JsonEntry) -> String {
     let card_id = format!("card-{}", item.name.replace(" ", "-").to_lowercase());
     let mut html = String::new();
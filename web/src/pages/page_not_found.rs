@@ -17,13 +17,19 @@ pub fn page_not_found() -> PageType {
             .map(|s| s.as_str())
             .unwrap_or("Page not found");
 
-        format!(
-            "<h1>{}</h1>\
-            <p>{}</p>\
-            <nav><a href=\"#/\">← Go Home</a></nav>",
-            title, message
-        )
+        not_found_html(title, message)
     };
 
     PageType::new("NotFound", params, render)
 }
+
+/// The not-found markup, for pages that only learn after loading that
+/// there's nothing to show
+pub fn not_found_html(title: &str, message: &str) -> String {
+    format!(
+        "<h1>{}</h1>\
+        <p>{}</p>\
+        <nav><a href=\"#/\">← Go Home</a></nav>",
+        title, message
+    )
+}
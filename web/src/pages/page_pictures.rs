@@ -1,16 +1,15 @@
+use crate::colour::{ColourFamily, dominant_colour, matches_family, sort_entries_by_colour};
 use crate::console_log;
-use crate::content::parse_debug_sequence;
+use crate::content::{animated_media_html, get_query_param, parse_debug_sequence};
 use crate::get_app;
 use crate::get_base_url;
-use crate::image::get_base64_from_blurhash;
+use crate::image::placeholder_src;
 use crate::log;
 use crate::page::Page as PageType;
-use crate::pages::macros::Style;
 use crate::pages::macros::load_readme;
 use crate::render_site;
 use crate::setup_article_observer;
 use content_service::JsonEntry;
-use pulldown_cmark::{Parser, html};
 use std::collections::HashMap;
 
 pub fn page_pictures() -> PageType {
@@ -18,7 +17,7 @@ pub fn page_pictures() -> PageType {
     let render = |_: &PageType| "".to_string();
 
     let on_after_render = || {
-        render_site!("pictures", Style::Photo);
+        render_site!("pictures");
     };
 
     PageType::new("Home", params, render).with_on_after_render(Some(Box::new(on_after_render)))
@@ -34,21 +33,35 @@ pub fn page_pictures_card_html(item: JsonEntry) -> String {
     }
 
     let first_img = item.images.first().unwrap();
-    let blurhash = &first_img.blurhash;
-
-    let base64 = get_base64_from_blurhash(&blurhash);
+    let blur_src = placeholder_src(
+        first_img.placeholder.as_deref(),
+        &first_img.blurhash,
+        &first_img.aspect_ratio,
+    );
 
     let img_url = format!("{}/content{}", base, first_img.path);
-    let wrapper_style = format!("aspect-ratio: {};", first_img.aspect_ratio);
+    // Dominant colour fills the frame until the blur and photo arrive
+    let wrapper_style = match dominant_colour(&item) {
+        Some(colour) => format!(
+            "aspect-ratio: {}; background-color: {};",
+            first_img.aspect_ratio, colour
+        ),
+        None => format!("aspect-ratio: {};", first_img.aspect_ratio),
+    };
 
     let img_blur = format!(
-        "<img class=\"photo-card-blur\" src=\"data:image/bmp;base64,{}\" alt=\"blurred image\" />",
-        base64
-    );
-    let img_main = format!(
-        "<img class=\"photo-card-img\" src=\"{}\" alt=\"{}\" loading=\"lazy\" onload=\"this.style.opacity=1\"/>",
-        img_url, item.name
+        "<img class=\"photo-card-blur\" src=\"{}\" alt=\"blurred image\" />",
+        blur_src
     );
+    let img_main = match &first_img.animation {
+        Some(animation) => {
+            animated_media_html("photo-card-img", &base, first_img, animation, &item.name)
+        }
+        None => format!(
+            "<img class=\"photo-card-img\" src=\"{}\" alt=\"{}\" loading=\"lazy\" onload=\"this.style.opacity=1\"/>",
+            img_url, item.name
+        ),
+    };
 
     let card_id = format!("card-{}", item.name.replace(" ", "-").to_lowercase());
     let mut item_date = String::new();
@@ -107,3 +120,78 @@ pub fn page_pictures_card_html(item: JsonEntry) -> String {
     html.push_str("</div>");
     html
 }
+
+/// Whether `url` filters or sorts the pictures by colour
+pub fn has_colour_view(url: &str) -> bool {
+    get_query_param(url, "colour").is_some()
+        || get_query_param(url, "sort").as_deref() == Some("colour")
+}
+
+/// Apply the `colour` filter and `sort=colour` ordering from the page URL
+pub fn apply_colour_view(items: &mut Vec<JsonEntry>, url: &str) {
+    if let Some(family) = get_query_param(url, "colour").and_then(|c| ColourFamily::from_name(&c))
+    {
+        items.retain(|item| matches_family(item, family));
+    }
+    if get_query_param(url, "sort").as_deref() == Some("colour") {
+        sort_entries_by_colour(items);
+    }
+}
+
+/// Sort toggle and colour swatches, each a link that keeps the other query
+/// parameters of the current view of `section`
+pub fn page_pictures_colour_controls_html(section: &str, url: &str) -> String {
+    let tags = get_query_param(url, "tags");
+    let colour = get_query_param(url, "colour");
+    let sort_by_colour = get_query_param(url, "sort").as_deref() == Some("colour");
+
+    let mut html = String::new();
+    html.push_str("<div class=\"tag-container colour-container\">");
+    html.push_str("<div class=\"tags\">");
+
+    html.push_str(&format!(
+        "<a class=\"tag{}\" href=\"{}\">Sort by colour</a>",
+        if sort_by_colour { " tag-active" } else { "" },
+        pictures_href(section, tags.as_deref(), colour.as_deref(), !sort_by_colour)
+    ));
+
+    for family in ColourFamily::ALL {
+        let active = colour.as_deref() == Some(family.name());
+        // Clicking the active swatch clears the filter
+        let target = if active { None } else { Some(family.name()) };
+        html.push_str(&format!(
+            "<a class=\"tag colour-tag{}\" href=\"{}\" title=\"{}\"><span class=\"colour-swatch\" style=\"background-color: {};\"></span></a>",
+            if active { " tag-active" } else { "" },
+            pictures_href(section, tags.as_deref(), target, sort_by_colour),
+            family.name(),
+            family.swatch()
+        ));
+    }
+
+    html.push_str("</div>");
+    html.push_str("</div>");
+    html
+}
+
+fn pictures_href(
+    section: &str,
+    tags: Option<&str>,
+    colour: Option<&str>,
+    sort_by_colour: bool,
+) -> String {
+    let mut params = Vec::new();
+    if let Some(tags) = tags {
+        params.push(format!("tags={}", urlencoding::encode(tags)));
+    }
+    if let Some(colour) = colour {
+        params.push(format!("colour={}", colour));
+    }
+    if sort_by_colour {
+        params.push("sort=colour".to_string());
+    }
+    if params.is_empty() {
+        format!("#/{}", section)
+    } else {
+        format!("#/{}?{}", section, params.join("&amp;"))
+    }
+}
@@ -0,0 +1,124 @@
+use crate::get_app;
+use crate::get_base_url;
+use crate::log;
+use crate::page::Page as PageType;
+use crate::pages::macros::load_readme;
+use crate::render_site;
+use crate::setup_article_observer;
+
+use content_service::dates::parse_content_date;
+use content_service::JsonEntry;
+use std::collections::HashMap;
+
+/// Any top-level content folder, laid out as its readme declares
+pub fn page_section(section: &str) -> PageType {
+    let mut params = HashMap::new();
+    params.insert("section".to_string(), section.to_string());
+    let render = |_: &PageType| "".to_string();
+
+    let section = section.to_string();
+    let on_after_render = move || {
+        render_site!(section);
+    };
+
+    PageType::new("Section", params, render).with_on_after_render(Some(Box::new(on_after_render)))
+}
+
+/// Entries grouped under the year they are dated, in the order given. Kept
+/// free of blank lines so markdown passes it through as one HTML block.
+pub fn page_timeline_html(items: &[JsonEntry]) -> String {
+    let mut html = String::new();
+    let mut current_year: Option<String> = None;
+
+    for item in items {
+        let year = timeline_year(item);
+        if current_year.as_ref() != Some(&year) {
+            if current_year.is_some() {
+                html.push_str("</ul></div>");
+            }
+            html.push_str(&format!(
+                "<div class=\"timeline-year\" data-year=\"{}\"><h2>{}</h2><ul class=\"timeline-list\">",
+                year, year
+            ));
+            current_year = Some(year);
+        }
+        html.push_str(&timeline_item_html(item));
+    }
+    if current_year.is_some() {
+        html.push_str("</ul></div>");
+    }
+
+    html
+}
+
+/// A further page of a timeline whose last group is `last_year`: the items
+/// still in that year, to add to its list, then groups for the rest
+pub fn timeline_continuation_html(
+    items: &[JsonEntry],
+    last_year: Option<&str>,
+) -> (String, String) {
+    let continued = items
+        .iter()
+        .take_while(|item| last_year == Some(timeline_year(item).as_str()))
+        .count();
+    let (same_year, rest) = items.split_at(continued);
+    (
+        same_year.iter().map(timeline_item_html).collect(),
+        page_timeline_html(rest),
+    )
+}
+
+fn timeline_year(item: &JsonEntry) -> String {
+    match item.date.as_deref().and_then(parse_content_date) {
+        Some(day) => day.format("%Y").to_string(),
+        None => "Undated".to_string(),
+    }
+}
+
+fn timeline_item_html(item: &JsonEntry) -> String {
+    let day = item.date.as_deref().and_then(parse_content_date);
+    format!(
+        "<li class=\"timeline-item\" onclick=\"on_article_card_click('{}')\"><span class=\"timeline-date\">{}</span><strong>{}</strong></li>",
+        item.path.replace("'", "\\'"),
+        day.map(|day| day.format("%b %-d").to_string())
+            .unwrap_or_default(),
+        item.name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, date: &str) -> JsonEntry {
+        JsonEntry {
+            path: format!("/notes/{}", name),
+            name: name.to_string(),
+            date: Some(date.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_year_over_a_page_boundary_keeps_one_heading() {
+        let first = page_timeline_html(&[entry("a.md", "2024-05-01"), entry("b.md", "2024-03-01")]);
+        assert!(first.ends_with("</ul></div>"));
+        assert!(first.contains("data-year=\"2024\""));
+
+        let next = [
+            entry("c.md", "2024-01-01"),
+            entry("d.md", "2023-12-01"),
+            entry("e.md", "2023-06-01"),
+        ];
+        let (continued, rest) = timeline_continuation_html(&next, Some("2024"));
+        assert_eq!(continued.matches("<li").count(), 1);
+        assert!(continued.contains("/notes/c.md"));
+        assert!(!rest.contains("<h2>2024</h2>"));
+        assert_eq!(rest.matches("<h2>2023</h2>").count(), 1);
+        assert_eq!(rest.matches("<li").count(), 2);
+
+        let (continued, rest) = timeline_continuation_html(&next, Some("2022"));
+        assert!(continued.is_empty());
+        assert_eq!(rest.matches("<h2>").count(), 2);
+    }
+}
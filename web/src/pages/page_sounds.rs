@@ -3,12 +3,12 @@ use crate::get_app;
 use crate::get_base_url;
 use crate::log;
 use crate::page::Page as PageType;
-use crate::pages::macros::Style;
 use crate::pages::macros::load_readme;
+use crate::pages::page_home::page_home_card_html;
 use crate::render_site;
 use crate::setup_article_observer;
 
-use pulldown_cmark::{Parser, html};
+use content_service::JsonEntry;
 use std::collections::HashMap;
 
 pub fn page_sounds() -> PageType {
@@ -16,8 +16,71 @@ pub fn page_sounds() -> PageType {
     let render = |_: &PageType| "".to_string();
 
     let on_after_render = || {
-        render_site!("sounds", Style::Music);
+        render_site!("sounds");
     };
 
     PageType::new("Home", params, render).with_on_after_render(Some(Box::new(on_after_render)))
 }
+
+pub fn page_sounds_card_html(item: JsonEntry) -> String {
+    // Posts without a recording still show up, just as a regular card
+    let Some(track) = item.audio.first() else {
+        return page_home_card_html(item);
+    };
+
+    let card_id = format!("card-{}", item.name.replace(" ", "-").to_lowercase());
+    let escaped_path = item.path.replace("'", "\\'");
+    let mut html = String::new();
+
+    html.push_str(&format!(
+        "<div class=\"base-card music-card\"
+              data-card-id=\"{}\"
+              data-card-name=\"{}\"
+              data-card-path=\"{}\"
+              onclick=\"on_article_card_click('{}')\">",
+        card_id, item.name, item.path, escaped_path
+    ));
+
+    html.push_str(&format!("<div><strong>{}</strong>", item.name));
+    if let Some(date) = item.metadata.get("date") {
+        html.push_str(&format!(" • {}", date));
+    }
+    if let Some(duration_ms) = track.duration_ms {
+        let seconds = duration_ms / 1000;
+        html.push_str(&format!(" • {}:{:02}", seconds / 60, seconds % 60));
+    }
+    html.push_str("</div>");
+
+    html.push_str(&format!(
+        "<div class=\"music-card-row\">
+            <button class=\"music-card-button\" onclick=\"event.stopPropagation(); on_track_play('{}')\" title=\"Play\">▶</button>
+            {}
+            <button class=\"music-card-button\" onclick=\"event.stopPropagation(); on_track_queue('{}')\" title=\"Add to queue\">+</button>
+        </div>",
+        escaped_path,
+        waveform_svg(&track.peaks),
+        escaped_path
+    ));
+
+    html.push_str("</div>");
+    html
+}
+
+/// Static preview of the precomputed peaks, the player draws the live one
+fn waveform_svg(peaks: &[u8]) -> String {
+    let mut bars = String::new();
+    for (i, peak) in peaks.iter().enumerate() {
+        let height = (*peak as f32 / 255.0 * 40.0).max(1.0);
+        bars.push_str(&format!(
+            "<rect x=\"{}\" y=\"{:.1}\" width=\"0.7\" height=\"{:.1}\" />",
+            i,
+            (40.0 - height) / 2.0,
+            height
+        ));
+    }
+    format!(
+        "<svg class=\"music-card-waveform\" viewBox=\"0 0 {} 40\" preserveAspectRatio=\"none\">{}</svg>",
+        peaks.len().max(1),
+        bars
+    )
+}
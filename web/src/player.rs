@@ -0,0 +1,507 @@
+use crate::console_log;
+use crate::get_base_url;
+use crate::get_document;
+use crate::log;
+
+use content_service::JsonEntry;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    window, CanvasRenderingContext2d, Document, Event, HtmlAudioElement, HtmlCanvasElement,
+    KeyboardEvent, MouseEvent,
+};
+
+// The player sits beside `#app` rather than in it, so `Router::render` and
+// `clear_app!` can replace the page without stopping playback.
+const PLAYER_ID: &str = "player";
+const WAVEFORM_ID: &str = "player-waveform";
+const TITLE_ID: &str = "player-title";
+const TIME_ID: &str = "player-time";
+const TOGGLE_ID: &str = "player-toggle";
+const QUEUE_ID: &str = "player-queue";
+
+const SEEK_STEP_SECONDS: f64 = 5.0;
+// Pressing "previous" this far into a track restarts it instead
+const RESTART_THRESHOLD_SECONDS: f64 = 3.0;
+
+const PLAYED_COLOUR: &str = "#333";
+const UNPLAYED_COLOUR: &str = "#c1c1c1";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub title: String,
+    pub src: String,
+    pub entry_path: String,
+    pub peaks: Vec<u8>,
+    pub duration_ms: Option<u64>,
+}
+
+struct PlayerState {
+    audio: HtmlAudioElement,
+    playlist: Vec<Track>,
+    queue: VecDeque<Track>,
+    history: Vec<Track>,
+    current: Option<Track>,
+    playlist_pos: Option<usize>,
+}
+
+thread_local! {
+    static PLAYER: RefCell<Option<PlayerState>> = const { RefCell::new(None) };
+}
+
+/// Build the player shell and wire up its listeners. Called once from `init_shell`.
+pub fn init_player(document: &Document) {
+    let body = document.body().expect("document should have a body");
+
+    let player = document
+        .create_element("div")
+        .expect("Failed to create player div");
+    player.set_id(PLAYER_ID);
+    player
+        .set_attribute("class", "player player-hidden")
+        .expect("Failed to set class attribute for player");
+    // Focusable so its keyboard shortcuts work after clicking the waveform
+    let _ = player.set_attribute("tabindex", "0");
+    player.set_inner_html(&format!(
+        "<div class=\"player-controls\">
+            <button class=\"player-button\" onclick=\"on_player_prev()\" title=\"Previous\">⏮</button>
+            <button id=\"{}\" class=\"player-button\" onclick=\"on_player_toggle()\" title=\"Play/Pause\">▶</button>
+            <button class=\"player-button\" onclick=\"on_player_next()\" title=\"Next\">⏭</button>
+        </div>
+        <div class=\"player-body\">
+            <div class=\"player-info\">
+                <strong id=\"{}\"></strong>
+                <span id=\"{}\" class=\"player-time\"></span>
+                <span id=\"{}\" class=\"player-queue\"></span>
+            </div>
+            <canvas id=\"{}\" class=\"player-waveform\"></canvas>
+        </div>",
+        TOGGLE_ID, TITLE_ID, TIME_ID, QUEUE_ID, WAVEFORM_ID
+    ));
+    body.append_child(&player).expect("Failed to append player");
+
+    let audio = HtmlAudioElement::new().expect("Failed to create audio element");
+    audio.set_preload("metadata");
+
+    add_listener(&audio, "timeupdate", |_| redraw());
+    add_listener(&audio, "play", |_| redraw());
+    add_listener(&audio, "pause", |_| redraw());
+    add_listener(&audio, "ended", |_| player_next());
+
+    if let Some(canvas) = document.get_element_by_id(WAVEFORM_ID) {
+        add_listener(&canvas, "click", |event| {
+            if let Some(event) = event.dyn_ref::<MouseEvent>() {
+                seek_to_click(event);
+            }
+        });
+    }
+
+    if let Some(window) = window() {
+        add_listener(&window, "keydown", |event| {
+            if let Some(event) = event.dyn_ref::<KeyboardEvent>() {
+                on_key(event);
+            }
+        });
+        add_listener(&window, "resize", |_| redraw());
+    }
+
+    PLAYER.with(|player| {
+        *player.borrow_mut() = Some(PlayerState {
+            audio,
+            playlist: Vec::new(),
+            queue: VecDeque::new(),
+            history: Vec::new(),
+            current: None,
+            playlist_pos: None,
+        });
+    });
+}
+
+fn add_listener<T, F>(target: &T, event: &str, handler: F)
+where
+    T: AsRef<web_sys::EventTarget>,
+    F: FnMut(Event) + 'static,
+{
+    let closure = Closure::wrap(Box::new(handler) as Box<dyn FnMut(Event)>);
+    target
+        .as_ref()
+        .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+        .expect("should register player listener");
+    closure.forget();
+}
+
+/// One track per audio embed, titled after the entry unless it holds several
+pub fn tracks_from_entries(entries: &[JsonEntry]) -> Vec<Track> {
+    let base = get_base_url!().to_string();
+    let mut tracks = Vec::new();
+    for entry in entries {
+        for audio in &entry.audio {
+            let title = if entry.audio.len() == 1 {
+                entry.name.clone()
+            } else {
+                format!("{} - {}", entry.name, audio.name)
+            };
+            tracks.push(Track {
+                title,
+                src: format!("{}/content{}", base, audio.path),
+                entry_path: entry.path.clone(),
+                peaks: audio.peaks.clone(),
+                duration_ms: audio.duration_ms,
+            });
+        }
+    }
+    tracks
+}
+
+/// Replace the section playlist, keeping our place if the current track is in it
+pub fn set_playlist(tracks: Vec<Track>) {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow_mut().as_mut() {
+            state.playlist_pos = state
+                .current
+                .as_ref()
+                .and_then(|current| tracks.iter().position(|t| t.src == current.src));
+            state.playlist = tracks;
+        }
+    });
+}
+
+/// Add the tracks of another page of the section to the end of its playlist
+pub fn extend_playlist(tracks: Vec<Track>) {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow_mut().as_mut() {
+            state.playlist.extend(tracks);
+        }
+    });
+}
+
+/// Play the first track of the entry at `entry_path` from the playlist
+pub fn play_entry(entry_path: &str) {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow_mut().as_mut() {
+            match state
+                .playlist
+                .iter()
+                .position(|t| t.entry_path == entry_path)
+            {
+                Some(pos) => {
+                    let track = state.playlist[pos].clone();
+                    state.playlist_pos = Some(pos);
+                    start(state, track);
+                }
+                None => console_log!("No track found for {}", entry_path),
+            }
+        }
+    });
+    redraw();
+}
+
+/// Add every track of the entry at `entry_path` to the end of the queue
+pub fn enqueue_entry(entry_path: &str) {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow_mut().as_mut() {
+            let tracks: Vec<Track> = state
+                .playlist
+                .iter()
+                .filter(|t| t.entry_path == entry_path)
+                .cloned()
+                .collect();
+            let idle = state.current.is_none();
+            state.queue.extend(tracks);
+            if idle {
+                advance(state);
+            }
+        }
+    });
+    redraw();
+}
+
+pub fn player_toggle() {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow_mut().as_mut() {
+            if state.current.is_none() {
+                advance(state);
+            } else if state.audio.paused() {
+                let _ = state.audio.play();
+            } else {
+                let _ = state.audio.pause();
+            }
+        }
+    });
+    redraw();
+}
+
+pub fn player_next() {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow_mut().as_mut() {
+            advance(state);
+        }
+    });
+    redraw();
+}
+
+pub fn player_prev() {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow_mut().as_mut() {
+            if state.audio.current_time() > RESTART_THRESHOLD_SECONDS {
+                state.audio.set_current_time(0.0);
+                return;
+            }
+            if let Some(track) = state.history.pop() {
+                state.playlist_pos = state.playlist.iter().position(|t| t.src == track.src);
+                // Going back shouldn't push the track we left onto the history
+                state.current = None;
+                start(state, track);
+            } else {
+                state.audio.set_current_time(0.0);
+            }
+        }
+    });
+    redraw();
+}
+
+/// Queued tracks play first, then the playlist carries on from where it was
+fn advance(state: &mut PlayerState) {
+    if let Some(track) = state.queue.pop_front() {
+        start(state, track);
+        return;
+    }
+
+    let next_pos = match state.playlist_pos {
+        Some(pos) => pos + 1,
+        None => 0,
+    };
+    if let Some(track) = state.playlist.get(next_pos).cloned() {
+        state.playlist_pos = Some(next_pos);
+        start(state, track);
+    } else {
+        let _ = state.audio.pause();
+    }
+}
+
+fn start(state: &mut PlayerState, track: Track) {
+    if let Some(previous) = state.current.take() {
+        state.history.push(previous);
+    }
+    state.audio.set_src(&track.src);
+    let _ = state.audio.play();
+    state.current = Some(track);
+}
+
+fn seek_to_click(event: &MouseEvent) {
+    let Some(canvas) = event
+        .current_target()
+        .and_then(|t| t.dyn_into::<HtmlCanvasElement>().ok())
+    else {
+        return;
+    };
+    let rect = canvas.get_bounding_client_rect();
+    if rect.width() <= 0.0 {
+        return;
+    }
+    let fraction = ((event.client_x() as f64 - rect.left()) / rect.width()).clamp(0.0, 1.0);
+
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow().as_ref() {
+            let duration = duration_seconds(state);
+            if duration > 0.0 {
+                state.audio.set_current_time(fraction * duration);
+            }
+        }
+    });
+    redraw();
+}
+
+fn on_key(event: &KeyboardEvent) {
+    // Only keys pressed inside the player, so the page still scrolls and
+    // browser shortcuts keep working everywhere else
+    if event.ctrl_key() || event.alt_key() || event.meta_key() {
+        return;
+    }
+    let Some(target) = event
+        .target()
+        .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+    else {
+        return;
+    };
+    if target
+        .closest(&format!("#{}", PLAYER_ID))
+        .ok()
+        .flatten()
+        .is_none()
+    {
+        return;
+    }
+    let tag = target.tag_name().to_lowercase();
+    if matches!(tag.as_str(), "input" | "textarea" | "select") {
+        return;
+    }
+    // A focused button already acts on space
+    if tag == "button" && event.key() == " " {
+        return;
+    }
+
+    let active = PLAYER.with(|player| {
+        player
+            .borrow()
+            .as_ref()
+            .is_some_and(|state| state.current.is_some())
+    });
+    if !active {
+        return;
+    }
+
+    match event.key().as_str() {
+        " " | "k" => player_toggle(),
+        "ArrowRight" if event.shift_key() => player_next(),
+        "ArrowLeft" if event.shift_key() => player_prev(),
+        "ArrowRight" => seek_by(SEEK_STEP_SECONDS),
+        "ArrowLeft" => seek_by(-SEEK_STEP_SECONDS),
+        "n" => player_next(),
+        "p" => player_prev(),
+        _ => return,
+    }
+    event.prevent_default();
+}
+
+fn seek_by(seconds: f64) {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow().as_ref() {
+            let duration = duration_seconds(state);
+            let target = (state.audio.current_time() + seconds).max(0.0);
+            state.audio.set_current_time(if duration > 0.0 {
+                target.min(duration)
+            } else {
+                target
+            });
+        }
+    });
+    redraw();
+}
+
+/// The element only knows the duration once metadata has loaded, fall back to
+/// the helper's figure until then
+fn duration_seconds(state: &PlayerState) -> f64 {
+    let duration = state.audio.duration();
+    if duration.is_finite() && duration > 0.0 {
+        return duration;
+    }
+    state
+        .current
+        .as_ref()
+        .and_then(|t| t.duration_ms)
+        .map(|ms| ms as f64 / 1000.0)
+        .unwrap_or(0.0)
+}
+
+fn format_time(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!("{}:{:02}", total / 60, total % 60)
+}
+
+fn redraw() {
+    PLAYER.with(|player| {
+        if let Some(state) = player.borrow().as_ref() {
+            render_player(state);
+        }
+    });
+}
+
+fn render_player(state: &PlayerState) {
+    let document = get_document!();
+    let Some(container) = document.get_element_by_id(PLAYER_ID) else {
+        return;
+    };
+    let Some(track) = state.current.as_ref() else {
+        let _ = container.set_attribute("class", "player player-hidden");
+        return;
+    };
+    let _ = container.set_attribute("class", "player");
+
+    let duration = duration_seconds(state);
+    let position = state.audio.current_time();
+
+    if let Some(title) = document.get_element_by_id(TITLE_ID) {
+        title.set_text_content(Some(&track.title));
+    }
+    if let Some(time) = document.get_element_by_id(TIME_ID) {
+        time.set_text_content(Some(&format!(
+            "{} / {}",
+            format_time(position),
+            format_time(duration)
+        )));
+    }
+    if let Some(toggle) = document.get_element_by_id(TOGGLE_ID) {
+        toggle.set_text_content(Some(if state.audio.paused() { "▶" } else { "⏸" }));
+    }
+    if let Some(queue) = document.get_element_by_id(QUEUE_ID) {
+        let label = match state.queue.len() {
+            0 => String::new(),
+            n => format!("{} queued", n),
+        };
+        queue.set_text_content(Some(&label));
+    }
+
+    let progress = if duration > 0.0 {
+        (position / duration).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    draw_waveform(&document, &track.peaks, progress);
+}
+
+fn draw_waveform(document: &Document, peaks: &[u8], progress: f64) {
+    let Some(canvas) = document
+        .get_element_by_id(WAVEFORM_ID)
+        .and_then(|e| e.dyn_into::<HtmlCanvasElement>().ok())
+    else {
+        return;
+    };
+
+    // Match the backing store to the laid out size so bars stay crisp
+    let ratio = window().map(|w| w.device_pixel_ratio()).unwrap_or(1.0);
+    let width = (canvas.client_width() as f64 * ratio).max(1.0);
+    let height = (canvas.client_height() as f64 * ratio).max(1.0);
+    if canvas.width() != width as u32 || canvas.height() != height as u32 {
+        canvas.set_width(width as u32);
+        canvas.set_height(height as u32);
+    }
+
+    let Some(ctx) = canvas
+        .get_context("2d")
+        .ok()
+        .flatten()
+        .and_then(|c| c.dyn_into::<CanvasRenderingContext2d>().ok())
+    else {
+        return;
+    };
+    ctx.clear_rect(0.0, 0.0, width, height);
+
+    let played_x = width * progress;
+    if peaks.is_empty() {
+        // No waveform for this track, draw a plain progress bar
+        let bar = 2.0 * ratio;
+        let y = (height - bar) / 2.0;
+        ctx.set_fill_style_str(UNPLAYED_COLOUR);
+        ctx.fill_rect(0.0, y, width, bar);
+        ctx.set_fill_style_str(PLAYED_COLOUR);
+        ctx.fill_rect(0.0, y, played_x, bar);
+        return;
+    }
+
+    let slot = width / peaks.len() as f64;
+    let bar = (slot * 0.7).max(1.0);
+    for (i, peak) in peaks.iter().enumerate() {
+        let x = i as f64 * slot;
+        let bar_height = (*peak as f64 / 255.0 * height).max(ratio);
+        let y = (height - bar_height) / 2.0;
+        ctx.set_fill_style_str(if x < played_x {
+            PLAYED_COLOUR
+        } else {
+            UNPLAYED_COLOUR
+        });
+        ctx.fill_rect(x, y, bar, bar_height);
+    }
+}
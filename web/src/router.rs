@@ -1,7 +1,9 @@
 use crate::console_log;
+use crate::navigation::Navigation;
 use crate::page::Page as PageType;
 use crate::pages::{
-    page_about, page_document, page_home, page_not_found, page_pictures, page_sounds,
+    page_about, page_archive, page_document, page_home, page_not_found, page_pictures,
+    page_section, page_sounds,
 };
 use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::*;
@@ -32,6 +34,8 @@ impl Router {
     }
 
     fn handle_route(path: &str) {
+        // Whatever the last page is still loading is for nobody now
+        Navigation::begin();
         let mut path = path;
         let mut tags = String::new();
         if let Some(query) = path.split_once('?') {
@@ -45,21 +49,24 @@ impl Router {
             "/about" | "resume" => page_about::page_about(),
             "/pictures" => page_pictures::page_pictures(),
             "/sounds" => page_sounds::page_sounds(),
+            "/archive" => page_archive::page_archive(None),
             _ => {
                 // Check for ? for query parameters
                 if let Some(query) = path.split_once('?') {
                     console_log!("Query parameters: {:?}", query);
                     page_not_found::page_not_found()
                 } else {
-                    // Check for wildcard routes
-                    if let Some(_) = Self::extract_wildcard(path, "/blog/") {
-                        page_document::page_document(&path)
-                    } else if let Some(_) = Self::extract_wildcard(path, "/pictures/") {
-                        page_document::page_document(&path)
-                    } else if let Some(_) = Self::extract_wildcard(path, "/resume/") {
-                        page_document::page_document(&path)
-                    } else {
+                    // Any other top-level folder is a section, anything
+                    // deeper is a document in one
+                    let route = path.trim_matches('/');
+                    if route.is_empty() {
                         page_not_found::page_not_found()
+                    } else if let Some(year) = route.strip_prefix("archive/") {
+                        page_archive::page_archive(Some(year))
+                    } else if !route.contains('/') {
+                        page_section::page_section(route)
+                    } else {
+                        page_document::page_document(&path)
                     }
                 }
             }
@@ -78,14 +85,6 @@ impl Router {
         params
     }
 
-    fn extract_wildcard(path: &str, prefix: &str) -> Option<String> {
-        if path.starts_with(prefix) && path.len() > prefix.len() {
-            Some(path[prefix.len()..].to_string())
-        } else {
-            None
-        }
-    }
-
     fn render(page: PageType) {
         let window = window().expect("Cannot render: no global 'window' exists!");
         let document = window